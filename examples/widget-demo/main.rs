@@ -8,11 +8,13 @@ use std::{
 };
 
 use rust_sdl_ui::{
-    color::{self, RgbColor},
-    desktop::{self, CommonWidgetProps},
-    sdl,
+    desktop,
+    feedback::Pattern,
+    input::{InputMapper, VirtualStick},
+    layout, sdl,
+    videosource::{SourceMode, VideoSource},
 };
-use sdl2::{controller::Axis, event::Event, EventPump};
+use sdl2::event::Event;
 
 fn main() {
     tracing_subscriber::fmt()
@@ -26,140 +28,101 @@ fn main() {
 
     let (tx, rx) = mpsc::channel();
 
-    thread::spawn(move || {
-        let video_file = env::var("TEST_VIDEO");
-        if video_file.is_err() {
-            return;
+    // the drone video, e.g. VIDEO_UDP=0.0.0.0:5600 VIDEO_RTP=1, else the file
+    let mut video_source = None;
+    if let Ok(addr) = env::var("VIDEO_UDP") {
+        let mode = match env::var("VIDEO_RTP") {
+            Ok(_) => SourceMode::Rtp,
+            Err(_) => SourceMode::Raw,
+        };
+        match VideoSource::bind(&addr, mode) {
+            Ok(source) => video_source = Some(source.spawn(tx)),
+            Err(e) => tracing::error!("{e}"),
         }
-        let video_file = video_file.unwrap();
-        let file = File::open(video_file);
-        if file.is_err() {
-            return;
-        }
-        let file = file.unwrap();
-        let mut reader = io::BufReader::new(file);
-        let mut buf: [u8; 1460] = [0; 1460];
-        loop {
-            let nread = reader.read(&mut buf);
-            if nread.is_err() {
-                break;
+    } else {
+        thread::spawn(move || {
+            let video_file = env::var("TEST_VIDEO");
+            if video_file.is_err() {
+                return;
             }
-            let nread = nread.unwrap();
-            if nread == 0 {
-                break;
+            let video_file = video_file.unwrap();
+            let file = File::open(video_file);
+            if file.is_err() {
+                return;
             }
-            let _ = tx.send(buf[0..nread].to_vec());
-        }
-    });
+            let file = file.unwrap();
+            let mut reader = io::BufReader::new(file);
+            let mut buf: [u8; 1460] = [0; 1460];
+            loop {
+                let nread = reader.read(&mut buf);
+                if nread.is_err() {
+                    break;
+                }
+                let nread = nread.unwrap();
+                if nread == 0 {
+                    break;
+                }
+                let _ = tx.send(buf[0..nread].to_vec());
+            }
+        });
+    }
 
-    let _video = desktop::VideoWidget::new(
+    let mut video = desktop::VideoWidget::new(
         desktop::CommonWidgetProps::new(&canvas)
             .place(0.5, 0.3)
             .size(0.5, 0.25),
-        &mut canvas,
-        960,
-        720,
         5,
     )
-    .on_window(&mut win, rx);
-
-    let text = desktop::TextWidget::new(
-        desktop::CommonWidgetProps::new(&canvas)
-            .place(0.5, 0.5)
-            .size(0.4, 0.2),
-    )
-    .on_window(&mut win);
-
-    text.write().unwrap().set("hello".to_owned());
-
-    let sensitivity = desktop::HorizSliderWidget::new(
-        desktop::CommonWidgetProps::new(&canvas)
-            .place(0.2, 0.5)
-            .size(0.15, 0.003),
-        0.0,
-        1.0,
-        5.0,
-    )
-    .on_window(&mut win);
-
-    let left_stick = desktop::GamepadStickWidget::new(
-        desktop::CommonWidgetProps::new(&canvas)
-            .place(0.2, 0.7)
-            .rect(0.1),
-    )
-    .on_window(&mut win);
-
-    let right_stick = desktop::GamepadStickWidget::new(
-        desktop::CommonWidgetProps::new(&canvas)
-            .place(0.8, 0.7)
-            .rect(0.1),
-    )
-    .on_window(&mut win);
-
-    let vert_thrust =
-        desktop::VertThrustWidget::new(CommonWidgetProps::new(&canvas).place(0.2, 0.2).rect(0.1))
-            .on_window(&mut win);
-
-    let temperature =
-        desktop::VertThrustWidget::new(CommonWidgetProps::new(&canvas).place(0.3, 0.2).rect(0.1))
-            .on_window(&mut win);
-
-    let mut t = temperature.write().unwrap();
-    t.set_color1(RgbColor::new(0.0, 0.3, 1.0, 1.0));
-    t.set_color2(RgbColor::new(1.0, 0.0, 0.0, 1.0));
-    t.set_color_scale_factor(0.65);
-    t.set(-65.0);
-    t.set_scale(0.01);
-    drop(t);
-
-    let battery = desktop::BatteryStatusWidget::new(
-        CommonWidgetProps::new(&canvas)
-            .place(0.1, 0.5)
-            .size(0.02, 0.12),
-    )
-    .on_window(&mut win);
-
-    let wifi_strength =
-        desktop::WifiStrengthWidget::new(CommonWidgetProps::new(&canvas).place(0.8, 0.2).rect(0.1))
-            .on_window(&mut win);
-
-    let _light_signal =
-        desktop::LightSignalWidget::new(CommonWidgetProps::new(&canvas).place(0.8, 0.45).rect(0.1))
-            .on_window(&mut win);
-
-    let horizon = desktop::HorizonWidget::new(
-        CommonWidgetProps::new(&canvas).place(0.5, 0.7).rect(0.12),
-        40.0,
-        color::YELLOW.clone(),
-    )
-    .on_window(&mut win);
-
-    let image_carousel = desktop::ImageCarouselWidget::new(
-        CommonWidgetProps::new(&canvas)
-            .place(0.5, 0.9)
-            .size(0.8, 0.1),
-        "examples/widget-demo/images",
-        10,
-    )
-    .on_window(&mut win);
-
-    let drone_yaw =
-        desktop::DroneYawWidget::new(CommonWidgetProps::new(&canvas).place(0.35, 0.7).rect(0.12))
-            .on_window(&mut win);
-
-    let _flight_log =
-        desktop::FlightLogWidget::new(CommonWidgetProps::new(&canvas).place(0.65, 0.7).rect(0.12))
-            .on_window(&mut win);
-
-    battery.write().unwrap().set(0.09);
-    wifi_strength.write().unwrap().set(0.4);
-
-    sensitivity.write().unwrap().inc();
+    .freeze_on_loss(true)
+    .on_keyframe_request(|| tracing::info!("keyframe requested"));
+    if let Some(ref source) = video_source {
+        video = video.source_stats(source.stats());
+    }
+    let _video = video.on_window(&mut win, rx);
+
+    let layout_file = env::var("LAYOUT").unwrap_or("examples/widget-demo/layout.toml".to_owned());
+    let layout = layout::Layout::from_file(&layout_file).expect("can't load layout");
+    let handles = layout
+        .load(&mut win, &mut canvas)
+        .expect("can't create widgets");
+    // edit the layout or the images while running, changes show up immediately
+    win.watch(Some(&layout_file), Some("images"));
+
+    let sensitivity = handles.horiz_slider("sensitivity").unwrap();
+    let left_stick = handles.gamepad_stick("left_stick").unwrap();
+    let right_stick = handles.gamepad_stick("right_stick").unwrap();
+    let vert_thrust = handles.vert_thrust("vert_thrust").unwrap();
+    let horizon = handles.horizon("horizon").unwrap();
+    let image_carousel = handles.image_carousel("image_carousel").unwrap();
+    let drone_yaw = handles.drone_yaw("drone_yaw").unwrap();
+    let take_off = handles.button("take_off").unwrap();
+    let record = handles.toggle("record").unwrap();
+    let mission = handles.text_input("mission").unwrap();
+
+    // rumble when the battery runs low or the link gets weak
+    let battery = handles.battery_status("battery").unwrap();
+    let wifi = handles.wifi_strength("wifi_strength").unwrap();
+    win.feedback.below(
+        move || battery.read().unwrap().get(),
+        desktop::BATTERY_LOW,
+        Pattern::low_battery(),
+    );
+    win.feedback.below(
+        move || wifi.read().unwrap().get(),
+        desktop::WIFI_WARNING,
+        Pattern::weak_signal(),
+    );
 
     let mut pitch = 0.0;
     let mut roll = 0.0;
     let mut angle = 0.0;
     let mut drone = DroneHandling::default();
+    let input_file = env::var("INPUT").unwrap_or("examples/widget-demo/input.toml".to_owned());
+    let mut input = InputMapper::from_file(&input_file).expect("can't load input mapping");
+    // keyboard sticks used while no gamepad is connected
+    let mut wasd = VirtualStick::wasd();
+    let mut arrows = VirtualStick::arrows();
+    let mut frame_start = Instant::now();
     while playing {
         // reset game state
 
@@ -167,10 +130,38 @@ fn main() {
         'running: loop {
             let start = Instant::now();
             // handle keyboard events
-            if drone.drone_handler(&mut win.event_pump) {
+            // only the active gamepad flies the drone
+            let events: Vec<Event> = win
+                .poll_events()
+                .into_iter()
+                .filter(|e| win.gamepads.gamepad_of(e).is_none() || win.gamepads.is_active(e))
+                .collect();
+            for event in events.iter() {
+                wasd.handle_event(event);
+                arrows.handle_event(event);
+            }
+            let active_pad = win.gamepads.active_instance();
+            if drone.drone_handler(events, &mut input, active_pad) {
                 playing = false;
                 break 'running;
             }
+            let left = wasd.update(frame_start.elapsed());
+            let right = arrows.update(frame_start.elapsed());
+            frame_start = Instant::now();
+            if win.gamepads.active().is_none() {
+                (drone.slide_right, drone.forward) = left;
+                drone.turn_clockwise = right.0;
+            }
+
+            if take_off.write().unwrap().take_clicks() > 0 {
+                drone.take_off = true;
+            }
+            if record.write().unwrap().take_clicks() > 0 {
+                tracing::info!(on = record.read().unwrap().is_checked(), "video recording");
+            }
+            if let Some(name) = mission.write().unwrap().take_submitted() {
+                tracing::info!(name, "mission");
+            }
 
             tracing::info!("drone={:?}", drone);
             // clear before drawing
@@ -186,7 +177,13 @@ fn main() {
             //     light_signal.write().unwrap().timestamp(utils::now_msecs);
             // }
 
-            sensitivity.write().unwrap().set(drone.sensitivity);
+            // dragged with the mouse, otherwise follow the gamepad
+            let mut slider = sensitivity.write().unwrap();
+            match slider.take_changed() {
+                Some(v) => drone.sensitivity = v,
+                None => slider.set(drone.sensitivity),
+            }
+            drop(slider);
 
             let ls = (
                 drone.slide_right * drone.sensitivity,
@@ -248,49 +245,17 @@ impl DroneHandling {
         self.img_carousel_right = false;
     }
 
-    pub fn drone_handler(&mut self, event_pump: &mut EventPump) -> bool {
+    pub fn drone_handler(
+        &mut self,
+        events: Vec<Event>,
+        input: &mut InputMapper,
+        active_pad: Option<u32>,
+    ) -> bool {
         tracing::info!("running drone event");
-        for event in event_pump.poll_iter() {
+        for event in events {
             tracing::info!("events={:?}", event);
+            input.handle_event(&event, active_pad);
             match event {
-                Event::ControllerButtonUp { button, .. } => {
-                    tracing::info!("Button {:?} up", button);
-                    match button {
-                        sdl2::controller::Button::A => self.take_picture = true,
-                        sdl2::controller::Button::B => self.toggle_video = true,
-                        sdl2::controller::Button::X => self.img_carousel_toggle_zoom = true,
-
-                        sdl2::controller::Button::Guide => self.hover = true,
-                        sdl2::controller::Button::Start => self.take_off = true,
-
-                        sdl2::controller::Button::LeftShoulder => self.sensitivity -= 0.2,
-                        sdl2::controller::Button::RightShoulder => self.sensitivity += 0.2,
-                        sdl2::controller::Button::DPadLeft => self.img_carousel_left = true,
-                        sdl2::controller::Button::DPadRight => self.img_carousel_right = true,
-                        _ => {}
-                    }
-                }
-
-                Event::ControllerAxisMotion {
-                    axis, value: val, ..
-                } => {
-                    // Axis motion is an absolute value in the range
-                    // [-32768, 32767]. Let's simulate a very rough dead
-                    // zone to ignore spurious events.
-                    // let dead_zone = 10_000;
-                    // if val > dead_zone || val < -dead_zone {
-                    tracing::info!("Axis {:?} moved to {}", axis, val);
-                    match axis {
-                        Axis::LeftX => self.slide_right = val as f32 / 32767.0,
-                        Axis::LeftY => self.forward = val as f32 / 32767.0,
-                        Axis::RightX => self.turn_clockwise = val as f32 / 32767.0,
-                        Axis::TriggerRight => self.vert_accel = val as f32 / 32767.0,
-                        Axis::TriggerLeft => self.vert_decel = val as f32 / 32767.0,
-                        _ => {}
-                    }
-                    // }
-                }
-
                 sdl2::event::Event::Quit { .. } => {
                     return true;
                 }
@@ -304,6 +269,25 @@ impl DroneHandling {
                 _ => {}
             }
         }
+        for action in input.take_pressed() {
+            match action.as_str() {
+                "take_picture" => self.take_picture = true,
+                "toggle_video" => self.toggle_video = true,
+                "carousel_zoom" => self.img_carousel_toggle_zoom = true,
+                "hover" => self.hover = true,
+                "take_off" => self.take_off = true,
+                "sensitivity_down" => self.sensitivity -= 0.2,
+                "sensitivity_up" => self.sensitivity += 0.2,
+                "carousel_left" => self.img_carousel_left = true,
+                "carousel_right" => self.img_carousel_right = true,
+                _ => {}
+            }
+        }
+        self.slide_right = input.axis("slide_right");
+        self.forward = input.axis("forward");
+        self.turn_clockwise = input.axis("turn_clockwise");
+        self.vert_accel = input.axis("vert_accel");
+        self.vert_decel = input.axis("vert_decel");
         false
     }
 }
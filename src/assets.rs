@@ -0,0 +1,144 @@
+use std::{path::Path, sync::RwLock};
+
+use sdl2::{
+    image::LoadTexture,
+    render::{Texture, TextureCreator},
+    video::WindowContext,
+};
+
+use crate::{error::Error, utils};
+
+// Default artwork of the widgets, compiled in with the `embedded-assets`
+// feature so the binary works from any directory.
+#[cfg(feature = "embedded-assets")]
+static EMBEDDED: &[(&str, &[u8])] = &[
+    (
+        "images/horizon-gauge-fg.png",
+        include_bytes!("../images/horizon-gauge-fg.png"),
+    ),
+    (
+        "images/joy-stick.png",
+        include_bytes!("../images/joy-stick.png"),
+    ),
+    ("images/joy.png", include_bytes!("../images/joy.png")),
+    (
+        "images/light-bg.png",
+        include_bytes!("../images/light-bg.png"),
+    ),
+    (
+        "images/radar-bg.png",
+        include_bytes!("../images/radar-bg.png"),
+    ),
+    (
+        "images/radius-bg.png",
+        include_bytes!("../images/radius-bg.png"),
+    ),
+    (
+        "images/slider-bg.png",
+        include_bytes!("../images/slider-bg.png"),
+    ),
+    (
+        "images/slider-button.png",
+        include_bytes!("../images/slider-button.png"),
+    ),
+    ("images/vert.png", include_bytes!("../images/vert.png")),
+    ("images/yaw-bg.png", include_bytes!("../images/yaw-bg.png")),
+    ("images/yaw-fg.png", include_bytes!("../images/yaw-fg.png")),
+];
+
+#[cfg(not(feature = "embedded-assets"))]
+static EMBEDDED: &[(&str, &[u8])] = &[];
+
+lazy_static! {
+    static ref OVERRIDE_DIR: RwLock<Option<String>> = {
+        let dir = utils::get_env("SDL_UI_ASSETS", "");
+        RwLock::new(if dir.is_empty() { None } else { Some(dir) })
+    };
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Asset {
+    File(String),
+    Embedded(&'static [u8]),
+}
+
+// Directory searched first for every asset, e.g. a skin. Defaults to the
+// `SDL_UI_ASSETS` env variable.
+pub fn set_override_dir(dir: Option<&str>) {
+    *OVERRIDE_DIR.write().unwrap() = dir.map(|d| d.to_owned());
+}
+
+// Finds the asset: the override directory is checked first (with the full
+// name, then just the file name), then the name as a file path and finally
+// the embedded artwork.
+pub fn resolve(name: &str) -> Option<Asset> {
+    resolve_in(OVERRIDE_DIR.read().unwrap().as_deref(), name)
+}
+
+fn resolve_in(override_dir: Option<&str>, name: &str) -> Option<Asset> {
+    if let Some(dir) = override_dir {
+        let dir = Path::new(dir);
+        let mut candidates = vec![dir.join(name)];
+        if let Some(file_name) = Path::new(name).file_name() {
+            candidates.push(dir.join(file_name));
+        }
+        for candidate in candidates {
+            if candidate.is_file() {
+                return Some(Asset::File(candidate.to_string_lossy().into_owned()));
+            }
+        }
+    }
+    if Path::new(name).is_file() {
+        return Some(Asset::File(name.to_owned()));
+    }
+    EMBEDDED
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, data)| Asset::Embedded(data))
+}
+
+// Modification time of the resolved asset in ms, 0 for embedded ones.
+pub(crate) fn modified(name: &str) -> u128 {
+    match resolve(name) {
+        Some(Asset::File(path)) => utils::file_mtime(&path),
+        _ => 0,
+    }
+}
+
+pub(crate) fn load_texture(
+    tc: &TextureCreator<WindowContext>,
+    name: &str,
+) -> Result<Texture, Error> {
+    match resolve(name) {
+        Some(Asset::File(path)) => tc
+            .load_texture(&path)
+            .map_err(|e| Error::Asset(format!("{path}: {e}"))),
+        Some(Asset::Embedded(data)) => tc
+            .load_texture_bytes(data)
+            .map_err(|e| Error::Asset(format!("{name} (embedded): {e}"))),
+        None => Err(Error::Asset(format!("not found: {name}"))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{resolve_in, Asset};
+
+    #[test]
+    fn override_dir_first() {
+        let dir = std::env::temp_dir().join(format!("sdl-ui-assets-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let skin = dir.join("joy.png");
+        fs::write(&skin, b"png").unwrap();
+
+        // the global override dir is left alone, tests run in parallel
+        assert_eq!(
+            Some(Asset::File(skin.to_string_lossy().into_owned())),
+            resolve_in(dir.to_str(), "images/joy.png")
+        );
+        assert_eq!(None, resolve_in(None, "images/missing.png"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,386 @@
+use sdl2::{
+    event::Event,
+    rect::{Point, Rect},
+    render::Canvas,
+};
+
+use crate::{
+    color::RgbColor,
+    desktop::{
+        dispatch_to_widgets, draw_widget, drawable_size, CommonWidgetProps, EventContext,
+        EventResult, Widget, WidgetParent,
+    },
+    error::Error,
+    font::FontManager,
+};
+
+type SdlWin = sdl2::video::Window;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+// How the child widget fills the cell computed by the container.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fit {
+    Fill,
+    // biggest square centered in the cell, for round gauges
+    Square,
+}
+
+impl Anchor {
+    // -1 for left, 0 for center, 1 for right
+    pub(crate) fn horizontal(&self) -> i32 {
+        match self {
+            Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => -1,
+            Anchor::Top | Anchor::Center | Anchor::Bottom => 0,
+            Anchor::TopRight | Anchor::Right | Anchor::BottomRight => 1,
+        }
+    }
+
+    // -1 for top, 0 for center, 1 for bottom
+    pub(crate) fn vertical(&self) -> i32 {
+        match self {
+            Anchor::TopLeft | Anchor::Top | Anchor::TopRight => -1,
+            Anchor::Left | Anchor::Center | Anchor::Right => 0,
+            Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight => 1,
+        }
+    }
+}
+
+// Center coordinate of the widget of `size` pixels anchored to one side of
+// the `total` pixels, margin is the fraction of the total.
+pub(crate) fn anchored(side: i32, margin: f32, size: i32, total: u32) -> i32 {
+    let margin = (margin * total as f32) as i32;
+    match side {
+        -1 => margin + size / 2,
+        1 => total as i32 - margin - size / 2,
+        _ => total as i32 / 2,
+    }
+}
+
+pub fn inset(area: Rect, padding: i32) -> Rect {
+    let w = (area.width() as i32 - 2 * padding).max(1);
+    let h = (area.height() as i32 - 2 * padding).max(1);
+    Rect::new(area.x() + padding, area.y() + padding, w as u32, h as u32)
+}
+
+pub fn fit(cell: Rect, fit: Fit) -> Rect {
+    match fit {
+        Fit::Fill => cell,
+        Fit::Square => {
+            let side = cell.width().min(cell.height());
+            Rect::from_center(cell.center(), side, side)
+        }
+    }
+}
+
+// Splits the area into `n` equal cells next to each other.
+pub fn stack_rects(
+    area: Rect,
+    n: usize,
+    orientation: Orientation,
+    spacing: i32,
+    padding: i32,
+) -> Vec<Rect> {
+    if n == 0 {
+        return Vec::new();
+    }
+    let content = inset(area, padding);
+    let total = match orientation {
+        Orientation::Horizontal => content.width() as i32,
+        Orientation::Vertical => content.height() as i32,
+    };
+    let cell = ((total - spacing * (n as i32 - 1)) / n as i32).max(1);
+    (0..n as i32)
+        .map(|i| {
+            let offset = i * (cell + spacing);
+            match orientation {
+                Orientation::Horizontal => Rect::new(
+                    content.x() + offset,
+                    content.y(),
+                    cell as u32,
+                    content.height(),
+                ),
+                Orientation::Vertical => Rect::new(
+                    content.x(),
+                    content.y() + offset,
+                    content.width(),
+                    cell as u32,
+                ),
+            }
+        })
+        .collect()
+}
+
+// Splits the area into grid cells with given number of columns, rows are
+// added as needed. Cells are filled row by row.
+pub fn grid_rects(area: Rect, n: usize, columns: usize, spacing: i32, padding: i32) -> Vec<Rect> {
+    if n == 0 || columns == 0 {
+        return Vec::new();
+    }
+    let rows = n.div_ceil(columns);
+    let content = inset(area, padding);
+    let cell_w =
+        ((content.width() as i32 - spacing * (columns as i32 - 1)) / columns as i32).max(1);
+    let cell_h = ((content.height() as i32 - spacing * (rows as i32 - 1)) / rows as i32).max(1);
+    (0..n)
+        .map(|i| {
+            let col = (i % columns) as i32;
+            let row = (i / columns) as i32;
+            Rect::new(
+                content.x() + col * (cell_w + spacing),
+                content.y() + row * (cell_h + spacing),
+                cell_w as u32,
+                cell_h as u32,
+            )
+        })
+        .collect()
+}
+
+enum Arrangement {
+    Stack(Orientation),
+    Grid(usize),
+    // all children share the content area of the panel
+    Panel,
+}
+
+// Container widget computing the areas of its children. Children are added
+// with their `on_window` call, then the container itself is put on the window:
+//
+// ```
+// let mut row = ContainerWidget::stack(props, Orientation::Horizontal).spacing(0.01);
+// let yaw = DroneYawWidget::new(CommonWidgetProps::new(&canvas)).on_window(&mut row);
+// row.on_window(&mut win);
+// ```
+//
+// Spacing and padding are fractions of the window width, so the gauges keep
+// their proportions on any resolution.
+pub struct ContainerWidget {
+    widget: CommonWidgetProps,
+    children: Vec<Box<dyn Widget>>,
+    arrangement: Arrangement,
+    spacing: f32,
+    padding: f32,
+    fit: Fit,
+    background: Option<RgbColor>,
+    pointer_grab: Option<usize>,
+}
+
+impl ContainerWidget {
+    fn new(widget: CommonWidgetProps, arrangement: Arrangement) -> Self {
+        Self {
+            widget,
+            children: Vec::new(),
+            arrangement,
+            spacing: 0.0,
+            padding: 0.0,
+            fit: Fit::Fill,
+            background: None,
+            pointer_grab: None,
+        }
+    }
+
+    pub fn stack(widget: CommonWidgetProps, orientation: Orientation) -> Self {
+        Self::new(widget, Arrangement::Stack(orientation))
+    }
+
+    pub fn grid(widget: CommonWidgetProps, columns: usize) -> Self {
+        Self::new(widget, Arrangement::Grid(columns))
+    }
+
+    // Panel is usually placed with `CommonWidgetProps::anchor`.
+    pub fn panel(widget: CommonWidgetProps) -> Self {
+        Self::new(widget, Arrangement::Panel)
+    }
+
+    pub fn spacing(self, spacing: f32) -> Self {
+        Self { spacing, ..self }
+    }
+
+    pub fn padding(self, padding: f32) -> Self {
+        Self { padding, ..self }
+    }
+
+    pub fn fit(self, fit: Fit) -> Self {
+        Self { fit, ..self }
+    }
+
+    pub fn background(self, color: RgbColor) -> Self {
+        Self {
+            background: Some(color),
+            ..self
+        }
+    }
+
+    pub fn on_window(self, window: &mut impl WidgetParent) {
+        window.add_widget(Box::new(self));
+    }
+
+    fn layout_children(&mut self, area: Rect, window_width: u32) {
+        let spacing = (self.spacing * window_width as f32) as i32;
+        let padding = (self.padding * window_width as f32) as i32;
+        let n = self.children.len();
+        let cells = match self.arrangement {
+            Arrangement::Stack(orientation) => stack_rects(area, n, orientation, spacing, padding),
+            Arrangement::Grid(columns) => grid_rects(area, n, columns, spacing, padding),
+            Arrangement::Panel => vec![inset(area, padding); n],
+        };
+        for (child, cell) in self.children.iter_mut().zip(cells) {
+            child.common_mut().set_area(fit(cell, self.fit));
+        }
+    }
+}
+
+impl WidgetParent for ContainerWidget {
+    fn add_widget(&mut self, widget: Box<dyn Widget>) {
+        self.children.push(widget);
+    }
+}
+
+impl Widget for ContainerWidget {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
+        let area = self.widget.area();
+        if let Some(ref bg) = self.background {
+            canvas.set_draw_color(bg.to_sdl_rgba());
+            let _ = canvas.fill_rect(area);
+        }
+        let (window_width, _) = drawable_size(canvas);
+        self.layout_children(area, window_width);
+        for child in self.children.iter_mut() {
+            draw_widget(child, canvas, fonts);
+        }
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
+    }
+
+    fn reload_assets(&mut self) {
+        for child in self.children.iter_mut() {
+            child.reload_assets();
+        }
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.widget.set_canvas_size(width, height);
+        for child in self.children.iter_mut() {
+            child.resize(width, height);
+        }
+    }
+
+    fn hover(&mut self, pointer: (i32, i32)) {
+        for child in self.children.iter_mut() {
+            child.hover(pointer);
+        }
+    }
+
+    fn contains_point(&self, point: Point) -> bool {
+        self.widget.area().contains_point(point)
+            || self.children.iter().any(|c| c.contains_point(point))
+    }
+
+    fn captures_keyboard(&self) -> bool {
+        self.children.iter().any(|c| c.captures_keyboard())
+    }
+
+    fn release_keyboard(&mut self) {
+        for child in self.children.iter_mut() {
+            child.release_keyboard();
+        }
+    }
+
+    fn children(&self) -> &[Box<dyn Widget>] {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut [Box<dyn Widget>] {
+        &mut self.children
+    }
+
+    fn handle_event(&mut self, event: &Event, ctx: &EventContext) -> EventResult {
+        dispatch_to_widgets(
+            &mut self.children,
+            &mut self.pointer_grab,
+            event,
+            ctx.pointer,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sdl2::rect::Rect;
+
+    use super::{anchored, fit, grid_rects, stack_rects, Fit, Orientation};
+
+    #[test]
+    fn stack_horizontal() {
+        let cells = stack_rects(Rect::new(0, 0, 320, 100), 3, Orientation::Horizontal, 10, 5);
+        assert_eq!(
+            vec![
+                Rect::new(5, 5, 96, 90),
+                Rect::new(111, 5, 96, 90),
+                Rect::new(217, 5, 96, 90)
+            ],
+            cells
+        );
+    }
+
+    #[test]
+    fn stack_vertical() {
+        let cells = stack_rects(Rect::new(10, 20, 50, 200), 2, Orientation::Vertical, 0, 0);
+        assert_eq!(
+            vec![Rect::new(10, 20, 50, 100), Rect::new(10, 120, 50, 100)],
+            cells
+        );
+    }
+
+    #[test]
+    fn grid_rows() {
+        let cells = grid_rects(Rect::new(0, 0, 200, 200), 3, 2, 0, 0);
+        assert_eq!(
+            vec![
+                Rect::new(0, 0, 100, 100),
+                Rect::new(100, 0, 100, 100),
+                Rect::new(0, 100, 100, 100)
+            ],
+            cells
+        );
+    }
+
+    #[test]
+    fn fit_square() {
+        assert_eq!(
+            Rect::new(50, 0, 100, 100),
+            fit(Rect::new(0, 0, 200, 100), Fit::Square)
+        );
+    }
+
+    #[test]
+    fn anchor_sides() {
+        assert_eq!(60, anchored(-1, 0.05, 40, 800));
+        assert_eq!(400, anchored(0, 0.05, 40, 800));
+        assert_eq!(740, anchored(1, 0.05, 40, 800));
+    }
+}
@@ -1,41 +1,164 @@
 use std::{
+    collections::HashMap,
     f32::consts::PI,
     sync::{mpsc::Receiver, Arc, RwLock},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crate::{
     color::{self, RgbColor},
-    sdl::{self, sdl_scale_text},
+    container::{self, Anchor},
+    error::Error,
+    feedback::{Feedback, FeedbackDevice},
+    focus::{self, Direction, FocusAction},
+    font::{self, FontManager},
+    gamepad::GamepadRegistry,
+    hotreload::FileWatcher,
+    layout::{Layout, WidgetHandle},
+    replay::{Player, Recorder},
+    sdl::{self, sdl_scale_text, WindowConfig},
     texcache::TextureCache,
+    text::{self, Span, TextStyle},
+    textedit::{Key, Keyboard, TextEdit},
     utils,
-    video::{StreamAction, VideoStreamDecoder},
+    video::{InputFormat, KeyframeRequest, StreamAction, StreamInfo, VideoStreamDecoder},
+    videosource::SourceStats,
 };
 use sdl2::{
-    controller::GameController,
+    controller::Button,
+    event::{Event, WindowEvent},
     gfx::primitives::DrawRenderer,
+    keyboard::{Keycode, Mod},
+    mouse::MouseButton,
     pixels::Color,
-    rect::Rect,
-    render::{Canvas, Texture, TextureCreator},
-    ttf::Sdl2TtfContext,
-    video::WindowContext,
+    rect::{Point, Rect},
+    render::{Canvas, Texture},
+    VideoSubsystem,
 };
 
 type SdlWin = sdl2::video::Window;
 
+// Battery level below which `BatteryStatusWidget` blinks red.
+pub const BATTERY_LOW: f32 = 0.1;
+// Signal strength below which `WifiStrengthWidget` turns red.
+pub const WIFI_WARNING: f32 = 0.45;
+
+// Textures which failed to load are tried again after this long, or when the
+// assets are reloaded.
+const TEXTURE_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum EventResult {
+    // event was handled by the widget, stop dispatching
+    Consumed,
+    // event was not interesting for the widget, try the next one
+    Propagate,
+}
+
+// Extra information passed along with the event to the widget.
+pub struct EventContext {
+    // widget area in window coordinates
+    pub area: Rect,
+    // pointer position for mouse and touch events, in canvas pixels
+    pub pointer: Option<(i32, i32)>,
+}
+
 pub trait Widget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext);
+    // Errors are not fatal, the window draws a placeholder instead of the widget.
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error>;
+
+    fn common(&self) -> &CommonWidgetProps;
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps;
+
+    // Drops loaded textures, they are loaded again on the next draw.
+    fn reload_assets(&mut self) {
+        self.common_mut().unload_textures();
+    }
+
+    // Called by the window when the drawable size changes, width and height
+    // are in pixels.
+    fn resize(&mut self, width: u32, height: u32) {
+        self.common_mut().set_canvas_size(width, height);
+    }
+
+    // Called by `Window::dispatch_event`. Pointer events (mouse, touch) are
+    // only delivered when the pointer is inside the widget area, or to the
+    // widget which consumed the button/finger down event until it is released.
+    // Keyboard and controller events are offered to all widgets, topmost first.
+    fn handle_event(&mut self, _event: &Event, _ctx: &EventContext) -> EventResult {
+        EventResult::Propagate
+    }
+
+    // Called on every pointer motion with the pointer position, also when the
+    // pointer is outside of the widget, e.g. to highlight it on hover.
+    fn hover(&mut self, _pointer: (i32, i32)) {}
+
+    // True when a pointer press at the point belongs to the widget. Widgets
+    // drawing outside of their area, e.g. the on-screen keyboard, extend it.
+    fn contains_point(&self, point: Point) -> bool {
+        self.common().area().contains_point(point)
+    }
+
+    // Focusable widgets can be reached by the D-pad or arrow keys, see
+    // `Window::set_focus_navigation`.
+    fn focusable(&self) -> bool {
+        false
+    }
+
+    // Called on the focused widget. `Adjust` not consumed by the widget moves
+    // the focus to the next widget in that direction.
+    fn focus_action(&mut self, _action: FocusAction) -> EventResult {
+        EventResult::Propagate
+    }
+
+    // True while the widget takes the keyboard input, e.g. a text input being
+    // edited. Key presses then skip the focus navigation.
+    fn captures_keyboard(&self) -> bool {
+        false
+    }
+
+    // Called when the user moved on while the widget captures the keyboard:
+    // on a press outside of the widget or when the focus moved away.
+    fn release_keyboard(&mut self) {}
+
+    // Widgets placed inside this one, e.g. by a container. The focus
+    // navigation reaches focusable children through them.
+    fn children(&self) -> &[Box<dyn Widget>] {
+        &[]
+    }
+
+    fn children_mut(&mut self) -> &mut [Box<dyn Widget>] {
+        &mut []
+    }
 }
 
 pub struct Window {
     widgets: Vec<Box<dyn Widget>>,
+    pointer_grab: Option<usize>,
+    // window size in screen coordinates, pointer events use them
+    window_size: (u32, u32),
+    // size of the canvas in pixels, differs from the window size on HiDPI
+    drawable_size: (u32, u32),
+    watcher: Option<FileWatcher>,
+    layout_widgets: HashMap<String, (usize, WidgetHandle)>,
+    // number of `poll_events` calls, recorded events refer to it
+    frame: u64,
+    recorder: Option<Recorder>,
+    player: Option<Player>,
+    // child indices leading to the focused widget, see `Widget::children`
+    focus: Option<Vec<usize>>,
+    focus_navigation: bool,
+    focus_color: RgbColor,
     pub fps: u32,
     pub width: u32,
     pub height: u32,
     pub event_pump: sdl2::EventPump,
-    pub ttf: sdl2::ttf::Sdl2TtfContext,
-    pub controller: Option<GameController>,
+    pub fonts: FontManager,
+    pub gamepads: GamepadRegistry,
+    // rumble and LED of the active gamepad
+    pub feedback: Feedback,
 }
 
 impl Window {
@@ -45,32 +168,365 @@ impl Window {
         fps: u32,
         gamepad: bool,
     ) -> (Self, Canvas<sdl2::video::Window>) {
-        let (event_pump, canvas, controller, real_width, real_height) =
-            sdl::sdl_init(width, height, gamepad);
-        let ttf = sdl2::ttf::init().expect("can't setup ttf context");
-        (
-            Self {
-                widgets: Vec::new(),
-                width: real_width,
-                height: real_height,
-                fps,
-                event_pump,
-                ttf,
-                controller,
-            },
-            canvas,
-        )
+        Self::try_new(width, height, fps, gamepad).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_new(
+        width: u32,
+        height: u32,
+        fps: u32,
+        gamepad: bool,
+    ) -> Result<(Self, Canvas<sdl2::video::Window>), Error> {
+        Self::try_with_config(&sdl::fullscreen_config(width, height), fps, gamepad)
+    }
+
+    // Window created according to the config, e.g. resizable or windowed.
+    pub fn with_config(
+        config: &WindowConfig,
+        fps: u32,
+        gamepad: bool,
+    ) -> (Self, Canvas<sdl2::video::Window>) {
+        Self::try_with_config(config, fps, gamepad).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_with_config(
+        config: &WindowConfig,
+        fps: u32,
+        gamepad: bool,
+    ) -> Result<(Self, Canvas<sdl2::video::Window>), Error> {
+        let (event_pump, canvas, gamepads, real_width, real_height) =
+            sdl::sdl_init_with(config, gamepad)?;
+        let win = Self::with_canvas(&canvas, event_pump, gamepads, real_width, real_height, fps)?;
+        Ok((win, canvas))
+    }
+
+    // Window rendering into an offscreen software canvas, see `sdl::sdl_init_headless`.
+    pub fn new_headless(width: u32, height: u32, fps: u32) -> (Self, Canvas<sdl2::video::Window>) {
+        Self::try_new_headless(width, height, fps).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_new_headless(
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> Result<(Self, Canvas<sdl2::video::Window>), Error> {
+        let (event_pump, canvas) = sdl::sdl_init_headless(width, height)?;
+        let win = Self::with_canvas(
+            &canvas,
+            event_pump,
+            GamepadRegistry::new(None),
+            width,
+            height,
+            fps,
+        )?;
+        Ok((win, canvas))
+    }
+
+    fn with_canvas(
+        canvas: &Canvas<SdlWin>,
+        event_pump: sdl2::EventPump,
+        gamepads: GamepadRegistry,
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> Result<Self, Error> {
+        let fonts = FontManager::new()?;
+        let mut win = Self {
+            widgets: Vec::new(),
+            pointer_grab: None,
+            window_size: canvas.window().size(),
+            drawable_size: (width, height),
+            watcher: None,
+            layout_widgets: HashMap::new(),
+            width,
+            height,
+            fps,
+            event_pump,
+            fonts,
+            gamepads,
+            feedback: Feedback::new(),
+            frame: 0,
+            recorder: None,
+            player: None,
+            focus: None,
+            focus_navigation: false,
+            focus_color: color::CYBER_COOL_BLUE.clone(),
+        };
+        // sessions can be recorded or replayed without changing the app
+        let replay = utils::get_env("SDL_UI_REPLAY", "");
+        if !replay.is_empty() {
+            win.replay(&replay)?;
+        }
+        let record = utils::get_env("SDL_UI_RECORD", "");
+        if !record.is_empty() {
+            win.record(&record)?;
+        }
+        Ok(win)
+    }
+
+    // Writes all input events handled by `poll_events` to the file.
+    pub fn record(&mut self, path: &str) -> Result<(), Error> {
+        self.recorder = Some(Recorder::new(path)?);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    // Replaces the live input by the recorded session, frame by frame, so
+    // the app has to call `poll_events` once per frame as when recording.
+    // Only quit and window events are taken from the live input until the
+    // recording ends.
+    pub fn replay(&mut self, path: &str) -> Result<(), Error> {
+        self.player = Some(Player::new(path)?);
+        Ok(())
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.player.is_some()
+    }
+
+    // Registers font files of the family, the first usable file is used.
+    // `font::MONOSPACE`, `font::SANS` and `font::SYMBOLS` come preconfigured
+    // with common system font locations.
+    pub fn register_font_family(&mut self, name: &str, paths: &[&str]) {
+        self.fonts.register_family(name, paths);
+    }
+
+    // Watches the layout file and/or the asset directory, changes are applied
+    // in place on the next `draw` call. Widget state handles stay untouched.
+    pub fn watch(&mut self, layout_file: Option<&str>, asset_dir: Option<&str>) {
+        self.watcher = Some(FileWatcher::new(layout_file, asset_dir));
+    }
+
+    // Remembers widget created from the layout, so it can be reconfigured on reload.
+    pub(crate) fn bind_layout_widget(&mut self, id: &str, handle: WidgetHandle) {
+        let idx = self.widgets.len() - 1;
+        self.layout_widgets.insert(id.to_owned(), (idx, handle));
+    }
+
+    fn hot_reload(&mut self) {
+        let changes = match self.watcher.as_mut() {
+            Some(w) => w.poll(),
+            None => return,
+        };
+        if changes.layout {
+            let layout_file = self.watcher.as_ref().unwrap().layout_file.clone().unwrap();
+            match Layout::from_file(&layout_file) {
+                Ok(layout) => self.reconfigure(&layout),
+                Err(e) => tracing::error!("layout reload failed: {e}"),
+            }
+        }
+        if changes.assets {
+            tracing::info!("assets changed, reloading textures");
+            for widget in self.widgets.iter_mut() {
+                widget.reload_assets();
+            }
+        }
+    }
+
+    fn reconfigure(&mut self, layout: &Layout) {
+        for spec in layout.widgets.iter() {
+            match self.layout_widgets.get(&spec.id) {
+                Some((idx, handle)) => {
+                    let widget = &mut self.widgets[*idx];
+                    spec.reconfigure(widget.common_mut(), handle);
+                    // widgets caching textures per size drop them on resize,
+                    // the layout may have changed the size
+                    widget.resize(self.width, self.height);
+                    widget.reload_assets();
+                }
+                None => tracing::warn!(id = spec.id, "new widget in layout, restart to show it"),
+            }
+        }
     }
 
     pub fn draw(&mut self, canvas: &mut Canvas<SdlWin>) {
+        self.hot_reload();
+        self.update_size(canvas);
+        let pad = self.gamepads.active_mut();
+        self.feedback
+            .update(pad.map(|p| &mut p.controller as &mut dyn FeedbackDevice));
+        for widget in self.widgets.iter_mut() {
+            draw_widget(widget, canvas, &mut self.fonts);
+        }
+        self.draw_focus_ring(canvas);
+    }
+
+    fn draw_focus_ring(&self, canvas: &mut Canvas<SdlWin>) {
+        let Some(ref path) = self.focus else {
+            return;
+        };
+        let Some(area) = focus_targets(&self.widgets)
+            .into_iter()
+            .find(|(p, _)| p == path)
+            .map(|(_, area)| area)
+        else {
+            return;
+        };
+        let width = (self.drawable_size.1 / 400).max(2) as i32;
+        canvas.set_draw_color(self.focus_color.to_sdl_rgba());
+        for i in 1..=width {
+            let _ = canvas.draw_rect(Rect::new(
+                area.x() - i - 2,
+                area.y() - i - 2,
+                area.width() + 2 * (i + 2) as u32,
+                area.height() + 2 * (i + 2) as u32,
+            ));
+        }
+    }
+
+    // Moves the focus between focusable widgets with the D-pad and arrow
+    // keys, A/Return activates and B/Backspace goes back. The focused widget
+    // gets the actions first, e.g. sliders are adjusted with left/right.
+    // Off by default, the keys are then left to the app.
+    pub fn set_focus_navigation(&mut self, enabled: bool) {
+        self.focus_navigation = enabled;
+        if !enabled {
+            self.focus = None;
+        }
+    }
+
+    pub fn set_focus_color(&mut self, color: RgbColor) {
+        self.focus_color = color;
+    }
+
+    pub fn clear_focus(&mut self) {
+        self.focus = None;
+    }
+
+    fn navigate(&mut self, action: FocusAction) -> EventResult {
+        let targets = focus_targets(&self.widgets);
+        // the focus module works with indices into the targets
+        let areas: Vec<(usize, Rect)> = targets
+            .iter()
+            .enumerate()
+            .map(|(idx, (_, area))| (idx, *area))
+            .collect();
+        let focused = self
+            .focus
+            .as_ref()
+            .and_then(|path| widget_at(&mut self.widgets, path));
+        let Some(widget) = focused else {
+            // the first press only shows where the focus is
+            self.focus = focus::first(&areas).map(|idx| targets[idx].0.clone());
+            return match self.focus {
+                Some(_) => EventResult::Consumed,
+                None => EventResult::Propagate,
+            };
+        };
+        if widget.focus_action(action) == EventResult::Consumed {
+            return EventResult::Consumed;
+        }
+        match action {
+            FocusAction::Adjust(dir) => {
+                let current = widget.common().area();
+                if let Some(next) = focus::neighbour(&areas, current, dir) {
+                    widget.release_keyboard();
+                    self.focus = Some(targets[next].0.clone());
+                }
+                EventResult::Consumed
+            }
+            FocusAction::Back => {
+                widget.release_keyboard();
+                self.focus = None;
+                EventResult::Consumed
+            }
+            FocusAction::Activate => EventResult::Propagate,
+        }
+    }
+
+    // Canvas pixels per screen coordinate of the window, e.g. 2 on HiDPI
+    // displays.
+    pub fn scale_factor(&self) -> (f32, f32) {
+        sdl::scale_factor(self.window_size, self.drawable_size)
+    }
+
+    // Re-layouts the widgets when the drawable size changed, e.g. the window
+    // was resized or moved to a display with different DPI.
+    fn update_size(&mut self, canvas: &Canvas<SdlWin>) {
+        let drawable_size = drawable_size(canvas);
+        if drawable_size == self.drawable_size {
+            return;
+        }
+        tracing::info!("drawable size changed to {:?}", drawable_size);
+        self.drawable_size = drawable_size;
+        self.window_size = canvas.window().size();
+        (self.width, self.height) = drawable_size;
         for widget in self.widgets.iter_mut() {
-            widget.draw(canvas, &mut self.ttf);
+            widget.resize(drawable_size.0, drawable_size.1);
+        }
+    }
+
+    // Polls all pending SDL events and dispatches them to the widgets.
+    // Events not consumed by any widget are returned to the caller.
+    pub fn poll_events(&mut self) -> Vec<Event> {
+        let mut events: Vec<Event> = self.event_pump.poll_iter().collect();
+        self.frame += 1;
+        if let Some(ref mut player) = self.player {
+            events.retain(|e| matches!(e, Event::Quit { .. } | Event::Window { .. }));
+            events.extend(player.take(self.frame, self.gamepads.active_instance()));
+            if player.finished() {
+                tracing::info!(frame = self.frame, "replay finished");
+                self.player = None;
+            }
+        }
+        if let Some(ref mut recorder) = self.recorder {
+            for event in events.iter() {
+                recorder.record(self.frame, event);
+            }
+            // the tail of the recording survives an abort of the app
+            recorder.flush();
+        }
+        let mut unhandled = Vec::new();
+        for event in events {
+            if self.dispatch_event(&event) == EventResult::Propagate {
+                unhandled.push(event);
+            }
+        }
+        unhandled
+    }
+
+    // Routes single event to the widgets. Widgets added later are drawn on top,
+    // hence they are asked first.
+    pub fn dispatch_event(&mut self, event: &Event) -> EventResult {
+        if let Event::Window {
+            win_event: WindowEvent::Resized(w, h) | WindowEvent::SizeChanged(w, h),
+            ..
+        } = *event
+        {
+            // widgets are resized on the next draw, once the canvas knows
+            // the new drawable size
+            self.window_size = (w as u32, h as u32);
+        }
+        // device events are still passed on, e.g. to show a notice
+        self.gamepads.handle_event(event);
+        // typing on the keyboard doesn't move the focus
+        let typing = is_key_event(event) && self.widgets.iter().any(|w| w.captures_keyboard());
+        if self.focus_navigation && !typing {
+            if let Some(action) = focus::action_of(event) {
+                if self.navigate(action) == EventResult::Consumed {
+                    return EventResult::Consumed;
+                }
+            }
+        }
+        let pointer = pointer_position(event, self.window_size, self.drawable_size);
+        let result = dispatch_to_widgets(&mut self.widgets, &mut self.pointer_grab, event, pointer);
+        // touching a focusable widget focuses it, also inside a container
+        if let (true, Some(idx), Some(p)) = (self.focus_navigation, self.pointer_grab, pointer) {
+            if is_pointer_press(event) {
+                if let Some(path) = focus_target_at(&self.widgets, idx, p) {
+                    self.focus = Some(path);
+                }
+            }
         }
+        result
     }
 
     // installs default Esc+Quit handling event
     pub fn default_keyhandler(&mut self) -> bool {
-        for event in self.event_pump.poll_iter() {
+        for event in self.poll_events() {
             match event {
                 sdl2::event::Event::Quit { .. } => {
                     return true;
@@ -89,112 +545,476 @@ impl Window {
     }
 }
 
+// Position of mouse and touch events in canvas pixels. Only the event itself
+// is used, so a replayed event hits the same widget as the recorded one.
+fn pointer_position(
+    event: &Event,
+    window_size: (u32, u32),
+    drawable_size: (u32, u32),
+) -> Option<(i32, i32)> {
+    let (w, h) = drawable_size;
+    let to_drawable = |x, y| to_drawable(x, y, window_size, drawable_size);
+    match *event {
+        Event::MouseMotion { x, y, .. } => Some(to_drawable(x, y)),
+        Event::MouseButtonDown { x, y, .. } => Some(to_drawable(x, y)),
+        Event::MouseButtonUp { x, y, .. } => Some(to_drawable(x, y)),
+        Event::MouseWheel {
+            mouse_x, mouse_y, ..
+        } => Some(to_drawable(mouse_x, mouse_y)),
+        // touch coordinates are normalized to 0..1
+        Event::FingerDown { x, y, .. }
+        | Event::FingerUp { x, y, .. }
+        | Event::FingerMotion { x, y, .. } => Some(((x * w as f32) as i32, (y * h as f32) as i32)),
+        _ => None,
+    }
+}
+
+// Mouse coordinates are in screen coordinates, widgets use canvas pixels.
+fn to_drawable(x: i32, y: i32, window_size: (u32, u32), drawable_size: (u32, u32)) -> (i32, i32) {
+    let (ww, wh) = window_size;
+    let (dw, dh) = drawable_size;
+    if ww == 0 || wh == 0 {
+        return (x, y);
+    }
+    (
+        (x as i64 * dw as i64 / ww as i64) as i32,
+        (y as i64 * dh as i64 / wh as i64) as i32,
+    )
+}
+
+// Draws the widget, a failed widget is replaced by a placeholder and the
+// error is logged once until the widget draws fine again.
+pub(crate) fn draw_widget(
+    widget: &mut Box<dyn Widget>,
+    canvas: &mut Canvas<SdlWin>,
+    fonts: &mut FontManager,
+) {
+    let result = widget.draw(canvas, fonts);
+    let failed = result.is_err();
+    record_draw_result(widget.common_mut(), result);
+    if failed {
+        draw_placeholder(canvas, widget.common().area());
+    }
+}
+
+// Remembers the last draw error, returns true when the error is new and was
+// logged.
+fn record_draw_result(common: &mut CommonWidgetProps, result: Result<(), Error>) -> bool {
+    match result {
+        Ok(()) => {
+            if common.draw_error.take().is_some() {
+                tracing::info!("widget recovered");
+            }
+            false
+        }
+        Err(e) => {
+            let msg = e.to_string();
+            if common.draw_error.as_ref() == Some(&msg) {
+                return false;
+            }
+            tracing::error!(area = ?common.area(), "widget draw failed: {msg}");
+            common.draw_error = Some(msg);
+            true
+        }
+    }
+}
+
+// Magenta crossed box, hard to miss but does not hide the rest of the HUD.
+fn draw_placeholder(canvas: &mut Canvas<SdlWin>, area: Rect) {
+    canvas.set_draw_color(color::MAGENTA.to_sdl_rgba());
+    let _ = canvas.draw_rect(area);
+    let _ = canvas.draw_line(area.top_left(), area.bottom_right());
+    let _ = canvas.draw_line(area.top_right(), area.bottom_left());
+}
+
+// Focusable widgets with the child indices leading to them, children of
+// containers included.
+pub(crate) fn focus_targets(widgets: &[Box<dyn Widget>]) -> Vec<(Vec<usize>, Rect)> {
+    let mut targets = Vec::new();
+    for (idx, widget) in widgets.iter().enumerate() {
+        if widget.focusable() {
+            targets.push((vec![idx], widget.common().area()));
+        }
+        for (mut path, area) in focus_targets(widget.children()) {
+            path.insert(0, idx);
+            targets.push((path, area));
+        }
+    }
+    targets
+}
+
+// Innermost focusable widget under the pointer within the top level widget.
+fn focus_target_at(
+    widgets: &[Box<dyn Widget>],
+    top: usize,
+    (px, py): (i32, i32),
+) -> Option<Vec<usize>> {
+    focus_targets(widgets)
+        .into_iter()
+        .filter(|(path, area)| path[0] == top && area.contains_point(Point::new(px, py)))
+        .max_by_key(|(path, _)| path.len())
+        .map(|(path, _)| path)
+}
+
+fn widget_at<'a>(
+    widgets: &'a mut [Box<dyn Widget>],
+    path: &[usize],
+) -> Option<&'a mut Box<dyn Widget>> {
+    let (first, rest) = path.split_first()?;
+    let widget = widgets.get_mut(*first)?;
+    if rest.is_empty() {
+        Some(widget)
+    } else {
+        widget_at(widget.children_mut(), rest)
+    }
+}
+
+// Offers the event to the widgets, topmost first. Shared by the window and
+// the containers, `pointer_grab` keeps the widget which consumed the pointer press.
+pub(crate) fn dispatch_to_widgets(
+    widgets: &mut [Box<dyn Widget>],
+    pointer_grab: &mut Option<usize>,
+    event: &Event,
+    pointer: Option<(i32, i32)>,
+) -> EventResult {
+    if pointer.is_none() {
+        for widget in widgets.iter_mut().rev() {
+            if send_event(widget, event, None) == EventResult::Consumed {
+                return EventResult::Consumed;
+            }
+        }
+        return EventResult::Propagate;
+    }
+
+    if let (Event::MouseMotion { .. }, Some(p)) = (event, pointer) {
+        for widget in widgets.iter_mut() {
+            widget.hover(p);
+        }
+    }
+
+    // a press elsewhere ends the text editing
+    if let (true, Some((px, py))) = (is_pointer_press(event), pointer) {
+        for widget in widgets.iter_mut() {
+            let inside = widget.contains_point(Point::new(px, py));
+            if !inside && widget.captures_keyboard() {
+                widget.release_keyboard();
+            }
+        }
+    }
+
+    // the widget which consumed the press gets everything till the release
+    if let Some(idx) = *pointer_grab {
+        if is_pointer_release(event) {
+            *pointer_grab = None;
+        }
+        if idx < widgets.len() {
+            return send_event(&mut widgets[idx], event, pointer);
+        }
+    }
+
+    let (px, py) = pointer.unwrap();
+    for idx in (0..widgets.len()).rev() {
+        if !widgets[idx].contains_point(Point::new(px, py)) {
+            continue;
+        }
+        if send_event(&mut widgets[idx], event, pointer) == EventResult::Consumed {
+            if is_pointer_press(event) {
+                *pointer_grab = Some(idx);
+            }
+            return EventResult::Consumed;
+        }
+    }
+    EventResult::Propagate
+}
+
+fn send_event(
+    widget: &mut Box<dyn Widget>,
+    event: &Event,
+    pointer: Option<(i32, i32)>,
+) -> EventResult {
+    let ctx = EventContext {
+        area: widget.common().area(),
+        pointer,
+    };
+    widget.handle_event(event, &ctx)
+}
+
+fn is_pointer_press(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::MouseButtonDown { .. } | Event::FingerDown { .. }
+    )
+}
+
+fn is_pointer_release(event: &Event) -> bool {
+    matches!(event, Event::MouseButtonUp { .. } | Event::FingerUp { .. })
+}
+
+fn is_key_event(event: &Event) -> bool {
+    matches!(event, Event::KeyDown { .. } | Event::KeyUp { .. })
+}
+
+// Canvas size in pixels, widgets are laid out in these coordinates.
+pub(crate) fn drawable_size(canvas: &Canvas<SdlWin>) -> (u32, u32) {
+    canvas
+        .output_size()
+        .unwrap_or(canvas.window().drawable_size())
+}
+
+// Anything widgets can be placed on: the window or a container.
+pub trait WidgetParent {
+    fn add_widget(&mut self, widget: Box<dyn Widget>);
+}
+
+impl WidgetParent for Window {
+    fn add_widget(&mut self, widget: Box<dyn Widget>) {
+        self.widgets.push(widget);
+    }
+}
+
 pub struct CommonWidgetProps {
     canvas_width: u32,
     canvas_height: u32,
-    aspect_ratio: f32,
     x: f32,
     y: f32,
     w: f32,
     h: f32,
+    // height follows the width in pixels
+    square: bool,
+    anchor: Option<Anchor>,
+    margin: (f32, f32),
+    // area assigned by the parent container, in pixels
+    area_override: Option<Rect>,
     texture_names: Vec<String>,
     textures: Vec<Texture>,
-    tc: TextureCreator<WindowContext>,
+    color: Option<RgbColor>,
+    // last draw error, logged only when it changes
+    draw_error: Option<String>,
+    // failed texture load, not retried every frame
+    texture_error: Option<(Error, Instant)>,
 }
 
 impl CommonWidgetProps {
     pub fn new(canvas: &Canvas<SdlWin>) -> Self {
-        let dim = canvas.window().size();
+        let dim = drawable_size(canvas);
         Self {
             canvas_width: dim.0,
             canvas_height: dim.1,
-            aspect_ratio: dim.0 as f32 / dim.1 as f32,
             x: 0.0,
             y: 0.0,
             w: 0.0,
             h: 0.0,
+            square: false,
+            anchor: None,
+            margin: (0.0, 0.0),
+            area_override: None,
             texture_names: Vec::new(),
             textures: Vec::new(),
-            tc: canvas.texture_creator(),
+            color: None,
+            draw_error: None,
+            texture_error: None,
         }
     }
 
-    fn textures(self, tex_names: Vec<&str>) -> Self {
+    // Overrides the artwork used by the widget, the order of textures is widget specific.
+    pub fn textures(self, tex_names: Vec<&str>) -> Self {
         let texture_names = tex_names.iter().map(|it| it.to_string()).collect();
         Self {
-            canvas_width: self.canvas_width,
-            canvas_height: self.canvas_height,
-            aspect_ratio: self.aspect_ratio,
-            x: self.x,
-            y: self.y,
-            w: self.w,
-            h: self.h,
             texture_names,
-            textures: self.textures,
-            tc: self.tc,
+            ..self
+        }
+    }
+
+    fn default_textures(self, tex_names: Vec<&str>) -> Self {
+        if self.texture_names.is_empty() {
+            self.textures(tex_names)
+        } else {
+            self
         }
     }
 
+    // Places the widget center, x and y are fractions of the window size.
     pub fn place(self, x: f32, y: f32) -> Self {
         Self {
-            canvas_width: self.canvas_width,
-            canvas_height: self.canvas_height,
-            aspect_ratio: self.aspect_ratio,
             x,
             y,
-            w: self.w,
-            h: self.h,
-            texture_names: self.texture_names,
-            textures: self.textures,
-            tc: self.tc,
+            anchor: None,
+            ..self
+        }
+    }
+
+    // Places the widget at the window edge or corner, margins are fractions
+    // of the window size. The center axis of the anchor uses the window center.
+    pub fn anchor(self, anchor: Anchor, margin_x: f32, margin_y: f32) -> Self {
+        Self {
+            anchor: Some(anchor),
+            margin: (margin_x, margin_y),
+            ..self
         }
     }
 
     pub fn size(self, w: f32, h: f32) -> Self {
         Self {
-            canvas_width: self.canvas_width,
-            canvas_height: self.canvas_height,
-            aspect_ratio: self.aspect_ratio,
-            x: self.x,
-            y: self.y,
             w,
             h,
-            texture_names: self.texture_names,
-            textures: self.textures,
-            tc: self.tc,
+            square: false,
+            ..self
         }
     }
 
+    // Square widget, w is the fraction of the window width.
     pub fn rect(self, w: f32) -> Self {
-        let aspect_ratio = self.aspect_ratio;
         Self {
-            canvas_width: self.canvas_width,
-            canvas_height: self.canvas_height,
-            aspect_ratio,
-            x: self.x,
-            y: self.y,
             w,
-            h: w * aspect_ratio,
-            texture_names: self.texture_names,
-            textures: self.textures,
-            tc: self.tc,
+            h: w,
+            square: true,
+            ..self
+        }
+    }
+
+    // Primary color of the widget, e.g. the horizon line or the text color.
+    pub fn color(self, color: RgbColor) -> Self {
+        Self {
+            color: Some(color),
+            ..self
+        }
+    }
+
+    fn default_color(self, color: RgbColor) -> Self {
+        if self.color.is_none() {
+            self.color(color)
+        } else {
+            self
         }
     }
 
+    fn color_or(&self, default: &RgbColor) -> RgbColor {
+        self.color.clone().unwrap_or(default.clone())
+    }
+
+    pub(crate) fn set_place(&mut self, x: f32, y: f32) {
+        self.x = x;
+        self.y = y;
+        self.anchor = None;
+    }
+
+    pub(crate) fn set_size(&mut self, w: f32, h: f32) {
+        self.w = w;
+        self.h = h;
+        self.square = false;
+    }
+
+    pub(crate) fn set_rect(&mut self, w: f32) {
+        self.w = w;
+        self.h = w;
+        self.square = true;
+    }
+
+    pub(crate) fn set_textures(&mut self, tex_names: &[String]) {
+        self.texture_names = tex_names.to_vec();
+    }
+
+    pub(crate) fn set_color(&mut self, color: Option<RgbColor>) {
+        self.color = color;
+    }
+
+    pub(crate) fn set_canvas_size(&mut self, width: u32, height: u32) {
+        self.canvas_width = width;
+        self.canvas_height = height;
+    }
+
+    // Used by containers to assign the child area in pixels.
+    pub(crate) fn set_area(&mut self, area: Rect) {
+        self.area_override = Some(area);
+    }
+
+    // widget rectangle in window coordinates
+    pub fn area(&self) -> Rect {
+        let (x, y, w, h) = self.dim_for(self.canvas_width, self.canvas_height);
+        Rect::from_center(Point::new(x, y), w.max(1) as u32, h.max(1) as u32)
+    }
+
     fn compute_dim(&self, canvas: &mut Canvas<SdlWin>) -> (i32, i32, i32, i32) {
-        let (info_width, info_height) = canvas.window().size();
+        let (info_width, info_height) = drawable_size(canvas);
+        self.dim_for(info_width, info_height)
+    }
 
-        let x = (info_width as f32 * self.x) as i32;
-        let y = (info_height as f32 * self.y) as i32;
+    // widget center and size in pixels for given window size
+    fn dim_for(&self, width: u32, height: u32) -> (i32, i32, i32, i32) {
+        if let Some(area) = self.area_override {
+            let c = area.center();
+            return (c.x(), c.y(), area.width() as i32, area.height() as i32);
+        }
+
+        let w = (width as f32 * self.w) as i32;
+        let h = if self.square {
+            w
+        } else {
+            (height as f32 * self.h) as i32
+        };
 
-        let w = (info_width as f32 * self.w) as i32;
-        let h = (info_height as f32 * self.h) as i32;
+        let (x, y) = match self.anchor {
+            None => (
+                (width as f32 * self.x) as i32,
+                (height as f32 * self.y) as i32,
+            ),
+            Some(anchor) => (
+                container::anchored(anchor.horizontal(), self.margin.0, w, width),
+                container::anchored(anchor.vertical(), self.margin.1, h, height),
+            ),
+        };
         (x, y, w, h)
     }
 
-    fn load_textures(&mut self, canvas: &mut Canvas<SdlWin>) {
-        if self.textures.len() == 0 {
-            self.textures = sdl::sdl_load_textures(canvas, self.texture_names.clone());
+    fn load_textures(&mut self, canvas: &mut Canvas<SdlWin>) -> Result<(), Error> {
+        if !self.textures.is_empty() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        if let Some(e) = self.pending_texture_error(now) {
+            return Err(e);
+        }
+        match sdl::sdl_load_textures(canvas, self.texture_names.clone()) {
+            Ok(textures) => {
+                self.textures = textures;
+                self.texture_error = None;
+                Ok(())
+            }
+            Err(e) => {
+                self.texture_error = Some((e.clone(), now));
+                Err(e)
+            }
+        }
+    }
+
+    // Error of the last failed texture load while the retry is backed off.
+    fn pending_texture_error(&self, now: Instant) -> Option<Error> {
+        match self.texture_error {
+            Some((ref e, failed)) if now.duration_since(failed) < TEXTURE_RETRY_INTERVAL => {
+                Some(e.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn texture(&self, idx: usize) -> Result<&Texture, Error> {
+        self.textures
+            .get(idx)
+            .ok_or_else(|| Error::Asset(format!("widget needs texture #{idx}")))
+    }
+
+    fn texture_name(&self, idx: usize) -> Result<String, Error> {
+        self.texture_names
+            .get(idx)
+            .cloned()
+            .ok_or_else(|| Error::Asset(format!("widget needs texture #{idx}")))
+    }
+
+    pub(crate) fn unload_textures(&mut self) {
+        self.texture_error = None;
+        for texture in self.textures.drain(..) {
+            // textures are only referenced from here
+            unsafe { texture.destroy() };
         }
     }
 }
@@ -202,30 +1022,63 @@ impl CommonWidgetProps {
 pub struct TextWidget {
     widget: CommonWidgetProps,
     props: Arc<RwLock<Text>>,
+    style: Option<TextStyle>,
 }
 
 impl TextWidget {
     pub fn new(widget: CommonWidgetProps) -> Self {
         Self {
-            widget: widget.textures(vec!["images/joy.png", "images/joy-stick.png"]),
-            props: Arc::new(RwLock::new(Text {
-                value: "".to_owned(),
-            })),
+            widget: widget.default_textures(vec!["images/joy.png", "images/joy-stick.png"]),
+            props: Arc::new(RwLock::new(Text { spans: Vec::new() })),
+            style: None,
         }
     }
 
-    pub fn on_window(self, window: &mut Window) -> Arc<RwLock<Text>> {
-        let hz = self.props.clone();
-        window.widgets.push(Box::new(self));
-        hz
-    }
+    // Lays the text out inside the widget area. Without the style the text
+    // is stretched over the whole widget.
+    pub fn style(self, style: TextStyle) -> Self {
+        Self {
+            style: Some(style),
+            ..self
+        }
+    }
+
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<Text>> {
+        let hz = self.props.clone();
+        window.add_widget(Box::new(self));
+        hz
+    }
 }
 
 impl Widget for TextWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
         let (x, y, w, h) = self.widget.compute_dim(canvas);
-        let text = &self.props.read().unwrap().value;
-        sdl_scale_text(ttf, canvas, text, 48, color::WHITE.clone(), x, y, w, h);
+        let spans = &self.props.read().unwrap().spans;
+        match self.style {
+            Some(ref style) => {
+                // widget color overrides the style color, e.g. from the layout file
+                let style = match self.widget.color {
+                    Some(ref c) => style.clone().color(c.clone()),
+                    None => style.clone(),
+                };
+                let area = Rect::from_center(Point::new(x, y), w.max(1) as u32, h.max(1) as u32);
+                text::draw_text(fonts, canvas, spans, &style, area);
+            }
+            None => {
+                let text: String = spans.iter().map(|s| s.text.as_str()).collect();
+                let text_color = self.widget.color_or(&color::WHITE);
+                sdl_scale_text(fonts, canvas, &text, 48, text_color, x, y, w, h);
+            }
+        }
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
     }
 }
 
@@ -237,7 +1090,7 @@ pub struct GamepadStickWidget {
 impl GamepadStickWidget {
     pub fn new(widget: CommonWidgetProps) -> Self {
         Self {
-            widget: widget.textures(vec!["images/joy.png", "images/joy-stick.png"]),
+            widget: widget.default_textures(vec!["images/joy.png", "images/joy-stick.png"]),
             props: Arc::new(RwLock::new(GamepadStick {
                 horiz: 0.0,
                 vert: 0.0,
@@ -245,19 +1098,19 @@ impl GamepadStickWidget {
         }
     }
 
-    pub fn on_window(self, window: &mut Window) -> Arc<RwLock<GamepadStick>> {
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<GamepadStick>> {
         let hz = self.props.clone();
-        window.widgets.push(Box::new(self));
+        window.add_widget(Box::new(self));
         hz
     }
 }
 
 impl Widget for GamepadStickWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, _fonts: &mut FontManager) -> Result<(), Error> {
         let (x, y, w, h) = self.widget.compute_dim(canvas);
 
-        self.widget.load_textures(canvas);
-        sdl::sdl_scale_tex(canvas, &self.widget.textures[0], x, y, w, h);
+        self.widget.load_textures(canvas)?;
+        sdl::sdl_scale_tex(canvas, self.widget.texture(0)?, x, y, w, h)?;
 
         let p = self.props.read().unwrap();
         let horiz = 0.4 * p.horiz;
@@ -267,46 +1120,270 @@ impl Widget for GamepadStickWidget {
         let yy = (y as f32 + vert * h as f32) as i32;
 
         let ww = (0.3 * w as f32) as i32;
-        sdl::sdl_scale_tex(canvas, &self.widget.textures[1], xx, yy, ww, ww);
+        sdl::sdl_scale_tex(canvas, self.widget.texture(1)?, xx, yy, ww, ww)?;
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
     }
 }
 
+// Slider for a value in min..max, e.g. the sensitivity:
+//
+// ```
+// let sensitivity = HorizSliderWidget::new(props, 0.5, 2.0, 6.0)
+//     .ticks(true)
+//     .labels(true)
+//     .readout(true)
+//     .on_change(|v| tracing::info!("sensitivity {v}"))
+//     .on_window(&mut win);
+// if let Some(v) = sensitivity.write().unwrap().take_changed() { ... }
+// ```
+//
+// The value snaps to the steps while dragging, with 0 steps it is continuous.
+// Dragging works with the mouse and touch, the wheel and the focus
+// navigation move it by one step. Change notifications are only sent for user
+// input, not for `HorizSlider::set`.
 pub struct HorizSliderWidget {
     widget: CommonWidgetProps,
     props: Arc<RwLock<HorizSlider>>,
+    dragging: bool,
+    orientation: container::Orientation,
+    ticks: bool,
+    labels: bool,
+    readout: bool,
+    decimals: usize,
+    on_change: Option<Box<dyn FnMut(f32)>>,
 }
 
 impl Widget for HorizSliderWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
         let (x, y, w, h) = self.widget.compute_dim(canvas);
 
-        self.widget.load_textures(canvas);
+        self.widget.load_textures(canvas)?;
 
         let p = self.props.read().unwrap();
-        let dx = -0.5 + p.value / (p.max_value - p.min_value);
+        let (fraction, steps) = (p.fraction(), p.steps);
+        let (min, max, value) = (p.min_value, p.max_value, p.value);
         drop(p);
-        let place_x = x + (w as f32 * dx) as i32;
-        sdl::sdl_scale_tex(canvas, &self.widget.textures[0], x, y, w, h);
-        sdl::sdl_render_tex(canvas, &self.widget.textures[1], place_x, y);
+
+        let vertical = self.is_vertical();
+        // position along the slider, 0 is the min end
+        let at = |f: f32| {
+            if vertical {
+                (x, y + ((0.5 - f) * h as f32) as i32)
+            } else {
+                (x + ((f - 0.5) * w as f32) as i32, y)
+            }
+        };
+        if vertical {
+            // the texture is horizontal, turn it around the center
+            let bg = self.widget.texture(0)?;
+            let dst = Rect::from_center(Point::new(x, y), h.max(1) as u32, w.max(1) as u32);
+            canvas.copy_ex(bg, None, dst, 90.0, None, false, false)?;
+        } else {
+            sdl::sdl_scale_tex(canvas, self.widget.texture(0)?, x, y, w, h)?;
+        }
+
+        let knob = self.widget.texture(1)?.query();
+        let (knob_w, knob_h) = (knob.width as i32, knob.height as i32);
+        // ticks and text are placed across the slider, beside the knob
+        let (across_x, across_y) = if vertical {
+            (knob_w / 2 + 8, 0)
+        } else {
+            (0, knob_h / 2 + 8)
+        };
+        // more ticks than that are just a solid bar
+        if self.ticks && steps > 0.0 && steps <= 100.0 {
+            let c = self.widget.color_or(&color::GREY_80).to_sdl_rgba();
+            for i in 0..=steps as i32 {
+                let (tx, ty) = at(i as f32 / steps);
+                let _ = canvas.thick_line(
+                    (tx - across_x / 2) as i16,
+                    (ty - across_y / 2) as i16,
+                    (tx - across_x) as i16,
+                    (ty - across_y) as i16,
+                    2,
+                    c,
+                );
+            }
+        }
+        if self.labels {
+            for (f, v) in [(0.0, min), (1.0, max)] {
+                let (lx, ly) = at(f);
+                let text = format!("{v:.*}", self.decimals);
+                let (lx, ly) = (lx - across_x * 2, ly - across_y * 2);
+                sdl::sdl_text(fonts, canvas, &text, 20, color::GREY_80.clone(), lx, ly);
+            }
+        }
+        let (kx, ky) = at(fraction);
+        sdl::sdl_render_tex(canvas, self.widget.texture(1)?, kx, ky)?;
+        if self.readout {
+            let text = format!("{value:.*}", self.decimals);
+            let (rx, ry) = (kx + across_x * 2, ky + across_y * 2);
+            sdl::sdl_text(fonts, canvas, &text, 24, color::WHITE.clone(), rx, ry);
+        }
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
+    }
+
+    fn handle_event(&mut self, event: &Event, ctx: &EventContext) -> EventResult {
+        match event {
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                ..
+            }
+            | Event::FingerDown { .. } => {
+                self.dragging = true;
+                self.drag_to(ctx);
+                EventResult::Consumed
+            }
+            Event::MouseMotion { .. } | Event::FingerMotion { .. } if self.dragging => {
+                self.drag_to(ctx);
+                EventResult::Consumed
+            }
+            Event::MouseButtonUp { .. } | Event::FingerUp { .. } if self.dragging => {
+                self.dragging = false;
+                EventResult::Consumed
+            }
+            // horizontal scrolling is left to the widgets below
+            Event::MouseWheel { y, .. } if *y != 0 => {
+                if *y > 0 {
+                    self.change(HorizSlider::inc);
+                } else {
+                    self.change(HorizSlider::dec);
+                }
+                EventResult::Consumed
+            }
+            _ => EventResult::Propagate,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    fn focus_action(&mut self, action: FocusAction) -> EventResult {
+        let (up, down) = if self.is_vertical() {
+            (Direction::Up, Direction::Down)
+        } else {
+            (Direction::Right, Direction::Left)
+        };
+        match action {
+            FocusAction::Adjust(d) if d == up => self.change(HorizSlider::inc),
+            FocusAction::Adjust(d) if d == down => self.change(HorizSlider::dec),
+            _ => return EventResult::Propagate,
+        }
+        EventResult::Consumed
     }
 }
 
 impl HorizSliderWidget {
     pub fn new(widget: CommonWidgetProps, min_value: f32, max_value: f32, steps: f32) -> Self {
         Self {
-            widget: widget.textures(vec!["images/slider-bg.png", "images/slider-button.png"]),
+            widget: widget
+                .default_textures(vec!["images/slider-bg.png", "images/slider-button.png"]),
             props: Arc::new(RwLock::new(HorizSlider {
                 min_value,
                 max_value,
                 value: min_value,
                 steps,
+                changed: false,
             })),
+            dragging: false,
+            orientation: container::Orientation::Horizontal,
+            ticks: false,
+            labels: false,
+            readout: false,
+            decimals: 2,
+            on_change: None,
+        }
+    }
+
+    // Min at the bottom, max at the top. The size is the one on screen, the
+    // textures are drawn turned by 90°.
+    pub fn vertical(self) -> Self {
+        Self {
+            orientation: container::Orientation::Vertical,
+            ..self
+        }
+    }
+
+    // Tick mark for every step.
+    pub fn ticks(self, ticks: bool) -> Self {
+        Self { ticks, ..self }
+    }
+
+    // Min and max value at the ends.
+    pub fn labels(self, labels: bool) -> Self {
+        Self { labels, ..self }
+    }
+
+    // Current value next to the knob.
+    pub fn readout(self, readout: bool) -> Self {
+        Self { readout, ..self }
+    }
+
+    // Decimals of the labels and the readout, 2 by default.
+    pub fn decimals(self, decimals: usize) -> Self {
+        Self { decimals, ..self }
+    }
+
+    // Called with the new value when the user moved the slider.
+    pub fn on_change(self, f: impl FnMut(f32) + 'static) -> Self {
+        Self {
+            on_change: Some(Box::new(f)),
+            ..self
+        }
+    }
+
+    fn is_vertical(&self) -> bool {
+        self.orientation == container::Orientation::Vertical
+    }
+
+    fn change(&mut self, f: impl FnOnce(&mut HorizSlider)) {
+        let mut p = self.props.write().unwrap();
+        let before = p.value;
+        f(&mut p);
+        if p.value == before {
+            return;
+        }
+        p.changed = true;
+        let value = p.value;
+        drop(p);
+        if let Some(ref mut on_change) = self.on_change {
+            on_change(value);
         }
     }
 
-    pub fn on_window(self, window: &mut Window) -> Arc<RwLock<HorizSlider>> {
+    fn drag_to(&mut self, ctx: &EventContext) {
+        let Some((px, py)) = ctx.pointer else {
+            return;
+        };
+        let a = ctx.area;
+        let fraction = if self.is_vertical() {
+            (a.bottom() - py) as f32 / a.height() as f32
+        } else {
+            (px - a.left()) as f32 / a.width() as f32
+        };
+        self.change(|p| p.set_fraction(fraction));
+    }
+
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<HorizSlider>> {
         let hz = self.props.clone();
-        window.widgets.push(Box::new(self));
+        window.add_widget(Box::new(self));
         hz
     }
 }
@@ -317,7 +1394,7 @@ pub struct VertThrustWidget {
 }
 
 impl Widget for VertThrustWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
         let tc = canvas.texture_creator();
         let (x, y, w, h) = self.widget.compute_dim(canvas);
         let p = self.props.read().unwrap();
@@ -328,9 +1405,9 @@ impl Widget for VertThrustWidget {
         let scale = p.scale;
         drop(p);
 
-        self.widget.load_textures(canvas);
+        self.widget.load_textures(canvas)?;
 
-        sdl::sdl_scale_tex(canvas, &self.widget.textures[0], x, y, w, h);
+        sdl::sdl_scale_tex(canvas, self.widget.texture(0)?, x, y, w, h)?;
         let d_color = c2.clone() - c1.clone();
         let dst_color = c1.clone() + d_color.mul(factor * vert_speed.abs() * scale);
         let dw = (w as f32 * 0.12) as i32;
@@ -346,7 +1423,7 @@ impl Widget for VertThrustWidget {
             true,
         );
         sdl::sdl_text(
-            ttf,
+            fonts,
             canvas,
             &vert_speed.to_string(),
             24,
@@ -354,13 +1431,22 @@ impl Widget for VertThrustWidget {
             x,
             y,
         );
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
     }
 }
 
 impl VertThrustWidget {
     pub fn new(widget: CommonWidgetProps) -> Self {
         Self {
-            widget: widget.textures(vec!["images/vert.png"]),
+            widget: widget.default_textures(vec!["images/vert.png"]),
             props: Arc::new(RwLock::new(VertThrust {
                 vert_value: 0.0,
                 color1: color::YELLOW.clone(),
@@ -371,9 +1457,9 @@ impl VertThrustWidget {
         }
     }
 
-    pub fn on_window(self, window: &mut Window) -> Arc<RwLock<VertThrust>> {
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<VertThrust>> {
         let hz = self.props.clone();
-        window.widgets.push(Box::new(self));
+        window.add_widget(Box::new(self));
         hz
     }
 }
@@ -385,7 +1471,7 @@ pub struct RawImageWidget {
 }
 
 impl Widget for RawImageWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, _fonts: &mut FontManager) -> Result<(), Error> {
         let (x, y, w, h) = self.widget.compute_dim(canvas);
         let p = self.props.read().unwrap();
         let img_width = p.width;
@@ -406,21 +1492,27 @@ impl Widget for RawImageWidget {
                     }
                 }
                 drop(rgb);
-            })
-            .unwrap();
+            })?;
+
+        canvas.copy(
+            &self.image_texture,
+            None,
+            Some(sdl2::rect::Rect::new(
+                x - w / 2,
+                y - h / 2,
+                w.max(1) as u32,
+                h.max(1) as u32,
+            )),
+        )?;
+        Ok(())
+    }
 
-        canvas
-            .copy(
-                &self.image_texture,
-                None,
-                Some(sdl2::rect::Rect::new(
-                    x - w / 2,
-                    y - h / 2,
-                    w as u32,
-                    h as u32,
-                )),
-            )
-            .unwrap();
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
     }
 }
 
@@ -431,101 +1523,193 @@ impl RawImageWidget {
         width: u32,
         height: u32,
     ) -> Self {
+        Self::try_new(widget, canvas, width, height).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_new(
+        widget: CommonWidgetProps,
+        canvas: &mut Canvas<SdlWin>,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Error> {
         let texture_creator = canvas.texture_creator();
         let image_texture = texture_creator
             .create_texture_streaming(sdl2::pixels::PixelFormatEnum::RGBA32, width, height)
-            .expect("can't create texture renderer");
-        Self {
+            .map_err(|e| Error::Sdl(format!("can't create image texture: {e}")))?;
+        Ok(Self {
             image_texture,
             widget,
             props: Arc::new(RwLock::new(RawImage {
-                rgb: utils::alloc_vec((width * height * 3).try_into().unwrap()),
+                rgb: utils::alloc_vec((width * height * 3) as usize),
                 width,
                 height,
             })),
-        }
+        })
     }
 
-    pub fn on_window(self, window: &mut Window) -> Arc<RwLock<RawImage>> {
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<RawImage>> {
         let hz = self.props.clone();
-        window.widgets.push(Box::new(self));
+        window.add_widget(Box::new(self));
         hz
     }
 }
 
 pub struct VideoWidget {
     widget: CommonWidgetProps,
-    props: Arc<RwLock<Video>>,
-    image_texture: Texture,
+    // created with the first frame, recreated when the picture size changes
+    image_texture: Option<Texture>,
     inner_decoder: Arc<VideoDecoder>,
+    input_format: InputFormat,
+    freeze_on_loss: bool,
+    keyframe_request: Option<KeyframeRequest>,
+    source_stats: Option<Arc<RwLock<SourceStats>>>,
 }
 
 impl Widget for VideoWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
         let (x, y, w, h) = self.widget.compute_dim(canvas);
-        let p = self.props.read().unwrap();
-        let img_width = p.width;
-        let img_height = p.height;
-        drop(p);
+        let Some((img_width, img_height)) = self.inner_decoder.resolution() else {
+            return Ok(()); // no SPS yet
+        };
 
         let rgb = self.inner_decoder.rgb.read().unwrap();
-        self.image_texture
-            .with_lock(None, |buffer: &mut [u8], pitch: usize| {
-                for y in 0..img_height {
-                    for x in 0..img_width {
-                        let offset = y as usize * pitch + x as usize * 4;
-                        let source_offset = ((y * img_width + x) * 3) as usize;
-                        buffer[offset] = rgb[source_offset];
-                        buffer[offset + 1] = rgb[source_offset + 1];
-                        buffer[offset + 2] = rgb[source_offset + 2];
-                        buffer[offset + 3] = 255;
-                    }
+        // the frame in the new size is not decoded yet
+        if rgb.len() != (img_width * img_height * 3) as usize {
+            return Ok(());
+        }
+        let texture = match self.image_texture.take() {
+            Some(t) if t.query().width == img_width && t.query().height == img_height => t,
+            old => {
+                if let Some(t) = old {
+                    // the texture of the old size is only referenced from here
+                    unsafe { t.destroy() };
                 }
-            })
-            .unwrap();
+                canvas
+                    .texture_creator()
+                    .create_texture_streaming(
+                        sdl2::pixels::PixelFormatEnum::RGBA32,
+                        img_width,
+                        img_height,
+                    )
+                    .map_err(|e| Error::Sdl(format!("can't create video texture: {e}")))?
+            }
+        };
+        let texture = self.image_texture.insert(texture);
+        texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+            for y in 0..img_height {
+                for x in 0..img_width {
+                    let offset = y as usize * pitch + x as usize * 4;
+                    let source_offset = ((y * img_width + x) * 3) as usize;
+                    buffer[offset] = rgb[source_offset];
+                    buffer[offset + 1] = rgb[source_offset + 1];
+                    buffer[offset + 2] = rgb[source_offset + 2];
+                    buffer[offset + 3] = 255;
+                }
+            }
+        })?;
         drop(rgb);
-        canvas
-            .copy(
-                &self.image_texture,
-                None,
-                Some(sdl2::rect::Rect::new(
-                    x - w / 2,
-                    y - h / 2,
-                    w as u32,
-                    h as u32,
-                )),
-            )
-            .unwrap();
+        let area = sdl2::rect::Rect::new(x - w / 2, y - h / 2, w.max(1) as u32, h.max(1) as u32);
+        canvas.copy(texture, None, Some(area))?;
+
+        if self.inner_decoder.is_degraded() {
+            // band across the bottom of the picture
+            let band = Rect::new(
+                area.left(),
+                area.bottom() - area.height() as i32 / 8,
+                area.width(),
+                area.height() / 8,
+            );
+            let bg = color::BLACK.with_alpha(0.6).to_sdl_rgba();
+            let (x2, y2) = ((band.right() - 1) as i16, (band.bottom() - 1) as i16);
+            let _ = canvas.box_(band.left() as i16, band.top() as i16, x2, y2, bg);
+            let style = TextStyle::new((band.height() as f32 * 0.6).max(8.0) as u16)
+                .family(font::SANS)
+                .color(color::RED.clone())
+                .align(text::HAlign::Center, text::VAlign::Middle);
+            text::draw_text(fonts, canvas, &[Span::new("VIDEO DEGRADED")], &style, band);
+        }
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
     }
 }
 
 impl VideoWidget {
-    pub fn new(
-        widget: CommonWidgetProps,
-        canvas: &mut Canvas<SdlWin>,
-        width: u32,
-        height: u32,
-        skip_frames: usize,
-    ) -> Self {
-        let texture_creator = canvas.texture_creator();
-        let image_texture = texture_creator
-            .create_texture_streaming(sdl2::pixels::PixelFormatEnum::RGBA32, width, height)
-            .expect("can't create texture renderer");
-        // let rx = Arc::new(RwLock::new(video_stream));
-        // thread::spawn(move || Self::decode_video(video_stream, width, height));
+    // The picture size is taken from the stream.
+    pub fn new(widget: CommonWidgetProps, skip_frames: usize) -> Self {
         Self {
-            image_texture,
+            image_texture: None,
             widget,
-            props: Arc::new(RwLock::new(Video::new(width, height))),
-            inner_decoder: Arc::new(VideoDecoder::new(width, height, skip_frames)),
+            inner_decoder: Arc::new(VideoDecoder::new(skip_frames)),
+            input_format: InputFormat::AnnexB,
+            freeze_on_loss: false,
+            keyframe_request: None,
+            source_stats: None,
+        }
+    }
+
+    // Format of the received stream, Annex-B by default.
+    pub fn input_format(self, input_format: InputFormat) -> Self {
+        Self {
+            input_format,
+            ..self
+        }
+    }
+
+    // Shows the last good frame after a loss until the next keyframe, the
+    // broken pictures in between are smeared. Pictures before the first
+    // keyframe are not shown either.
+    pub fn freeze_on_loss(self, freeze_on_loss: bool) -> Self {
+        Self {
+            freeze_on_loss,
+            ..self
         }
     }
 
-    pub fn on_window(self, window: &mut Window, rx: Receiver<Vec<u8>>) -> Arc<VideoDecoder> {
+    // Asks the drone for a keyframe when data was lost, called from the
+    // decoding thread.
+    pub fn on_keyframe_request(self, request: impl FnMut() + Send + 'static) -> Self {
+        Self {
+            keyframe_request: Some(Box::new(request)),
+            ..self
+        }
+    }
+
+    // Counters of the `videosource::VideoSource` feeding the widget, its
+    // lost packets degrade the video right away.
+    pub fn source_stats(self, stats: Arc<RwLock<SourceStats>>) -> Self {
+        Self {
+            source_stats: Some(stats),
+            ..self
+        }
+    }
+
+    pub fn on_window(
+        mut self,
+        window: &mut impl WidgetParent,
+        rx: Receiver<Vec<u8>>,
+    ) -> Arc<VideoDecoder> {
         let hz = self.inner_decoder.clone();
         let inner = self.inner_decoder.clone();
-        thread::spawn(move || inner.decode_video(rx));
-        window.widgets.push(Box::new(self));
+        let format = self.input_format.clone();
+        let freeze_on_loss = self.freeze_on_loss;
+        let keyframe_request = self.keyframe_request.take();
+        let source_stats = self.source_stats.take();
+        thread::spawn(move || {
+            let mut vd = VideoStreamDecoder::with_format(inner.skip_frames, &format);
+            vd.set_freeze_on_loss(freeze_on_loss);
+            if let Some(request) = keyframe_request {
+                vd.set_keyframe_request(request);
+            }
+            inner.decode_video(rx, vd, source_stats)
+        });
+        window.add_widget(Box::new(self));
         hz
     }
 }
@@ -537,13 +1721,13 @@ pub struct BatteryStatusWidget {
 }
 
 impl Widget for BatteryStatusWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
         let (x, y, w, h) = self.widget.compute_dim(canvas);
         let p = self.props.read().unwrap();
         let percentage = p.value;
         drop(p);
-        if percentage < 0.1 && self.timer.blink() {
-            return; // do not draw when blinking
+        if percentage < BATTERY_LOW && self.timer.blink() {
+            return Ok(()); // do not draw when blinking
         }
         let cyber_blue = color::CYBER_COOL_BLUE.to_sdl_rgba();
         let red = color::RED.to_sdl_rgba();
@@ -554,7 +1738,7 @@ impl Widget for BatteryStatusWidget {
         let _ = canvas.draw_rect(Rect::new(sx, sy, w as u32, h as u32));
         if percentage >= 0.9 {
             canvas.set_draw_color(cyber_blue);
-        } else if percentage > 0.1 {
+        } else if percentage > BATTERY_LOW {
             canvas.set_draw_color(yellow);
         } else {
             canvas.set_draw_color(red);
@@ -569,7 +1753,16 @@ impl Widget for BatteryStatusWidget {
         ));
         let val = (percentage * 100.0) as i32;
         let text = format!("{val}%");
-        sdl::sdl_text(ttf, canvas, &text, 24, color::WHITE.clone(), x, y);
+        sdl::sdl_text(fonts, canvas, &text, 24, color::WHITE.clone(), x, y);
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
     }
 }
 
@@ -582,9 +1775,9 @@ impl BatteryStatusWidget {
         }
     }
 
-    pub fn on_window(self, window: &mut Window) -> Arc<RwLock<FloatClampedValue>> {
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<FloatClampedValue>> {
         let hz = self.props.clone();
-        window.widgets.push(Box::new(self));
+        window.add_widget(Box::new(self));
         hz
     }
 }
@@ -596,22 +1789,22 @@ pub struct WifiStrengthWidget {
 }
 
 impl Widget for WifiStrengthWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
         let (x, y, w, h) = self.widget.compute_dim(canvas);
-        self.widget.load_textures(canvas);
+        self.widget.load_textures(canvas)?;
 
         let p = self.props.read().unwrap();
         let value = p.value;
         let radius = value * w as f32 * 0.4 * self.timer.range();
         drop(p);
-        if value < 0.45 && self.timer.blink() {
-            return;
+        if value < WIFI_WARNING && self.timer.blink() {
+            return Ok(());
         }
-        sdl::sdl_scale_tex(canvas, &self.widget.textures[0], x, y, w, h);
+        sdl::sdl_scale_tex(canvas, self.widget.texture(0)?, x, y, w, h)?;
         let mut alpha = 1.0;
         let dx = x + (w as f32 * 0.007) as i32;
         let dy = y + (w as f32 * 0.009) as i32;
-        let signal_color = if value < 0.45 {
+        let signal_color = if value < WIFI_WARNING {
             color::RED.clone()
         } else {
             color::YELLOW.clone()
@@ -631,7 +1824,7 @@ impl Widget for WifiStrengthWidget {
         }
         let strength = (value * 100.0) as i32;
         sdl::sdl_text(
-            ttf,
+            fonts,
             canvas,
             &strength.to_string(),
             48,
@@ -639,21 +1832,30 @@ impl Widget for WifiStrengthWidget {
             x,
             y - 2 * h / 5,
         );
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
     }
 }
 
 impl WifiStrengthWidget {
     pub fn new(widget: CommonWidgetProps) -> Self {
         Self {
-            widget: widget.textures(vec!["images/radar-bg.png"]),
+            widget: widget.default_textures(vec!["images/radar-bg.png"]),
             props: Arc::new(RwLock::new(FloatClampedValue { value: 0.0 })),
             timer: utils::GameTimer::new(Duration::from_millis(800)),
         }
     }
 
-    pub fn on_window(self, window: &mut Window) -> Arc<RwLock<FloatClampedValue>> {
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<FloatClampedValue>> {
         let hz = self.props.clone();
-        window.widgets.push(Box::new(self));
+        window.add_widget(Box::new(self));
         hz
     }
 }
@@ -665,9 +1867,9 @@ pub struct LightSignalWidget {
 }
 
 impl Widget for LightSignalWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
         let (x, y, w, h) = self.widget.compute_dim(canvas);
-        self.widget.load_textures(canvas);
+        self.widget.load_textures(canvas)?;
 
         let p = self.props.read().unwrap();
         let last_signal = p.tm;
@@ -686,10 +1888,10 @@ impl Widget for LightSignalWidget {
         if secs_elapsed > 10 {
             green = 0;
             if self.timer.blink() {
-                return;
+                return Ok(());
             }
         }
-        sdl::sdl_scale_tex(canvas, &self.widget.textures[0], x, y, w, h);
+        sdl::sdl_scale_tex(canvas, self.widget.texture(0)?, x, y, w, h)?;
 
         let dx = x - (w as f32 * 0.007) as i32;
         let dy = y - (w as f32 * 0.009) as i32;
@@ -701,7 +1903,7 @@ impl Widget for LightSignalWidget {
             Color::RGBA(red, green, 0, alpha as u8),
         );
         sdl::sdl_text(
-            ttf,
+            fonts,
             canvas,
             &secs_elapsed.to_string(),
             48,
@@ -709,13 +1911,22 @@ impl Widget for LightSignalWidget {
             x,
             y,
         );
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
     }
 }
 
 impl LightSignalWidget {
     pub fn new(widget: CommonWidgetProps) -> Self {
         Self {
-            widget: widget.textures(vec!["images/light-bg.png"]),
+            widget: widget.default_textures(vec!["images/light-bg.png"]),
             props: Arc::new(RwLock::new(LightSignal {
                 tm: utils::now_msecs(),
             })),
@@ -723,9 +1934,9 @@ impl LightSignalWidget {
         }
     }
 
-    pub fn on_window(self, window: &mut Window) -> Arc<RwLock<LightSignal>> {
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<LightSignal>> {
         let hz = self.props.clone();
-        window.widgets.push(Box::new(self));
+        window.add_widget(Box::new(self));
         hz
     }
 }
@@ -734,13 +1945,12 @@ pub struct HorizonWidget {
     widget: CommonWidgetProps,
     props: Arc<RwLock<DroneOrientation>>,
     max_pitch: f32,
-    horizon_color: RgbColor,
 }
 
 impl Widget for HorizonWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, _fonts: &mut FontManager) -> Result<(), Error> {
         let (x, y, w, h) = self.widget.compute_dim(canvas);
-        self.widget.load_textures(canvas);
+        self.widget.load_textures(canvas)?;
 
         let p = self.props.read().unwrap();
         let roll = p.roll;
@@ -764,11 +1974,21 @@ impl Widget for HorizonWidget {
             let x2 = (circle_radius * right_angle.sin()) as i32;
             let y2 = (circle_radius * right_angle.cos()) as i32;
 
-            canvas.set_draw_color(self.horizon_color.to_sdl_rgba());
+            let horizon_color = self.widget.color_or(&color::YELLOW);
+            canvas.set_draw_color(horizon_color.to_sdl_rgba());
             let _ = canvas.draw_line((x + x1 + dx, y - y1 - dy), (x + x2 + dx, y - y2 - dy));
         }
 
-        sdl::sdl_scale_tex(canvas, &self.widget.textures[0], x, y, w, h);
+        sdl::sdl_scale_tex(canvas, self.widget.texture(0)?, x, y, w, h)?;
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
     }
 }
 
@@ -776,8 +1996,9 @@ impl HorizonWidget {
     pub fn new(widget: CommonWidgetProps, max_pitch: f32, horizon_color: RgbColor) -> Self {
         Self {
             max_pitch,
-            horizon_color,
-            widget: widget.textures(vec!["images/horizon-gauge-fg.png"]),
+            widget: widget
+                .default_textures(vec!["images/horizon-gauge-fg.png"])
+                .default_color(horizon_color),
             props: Arc::new(RwLock::new(DroneOrientation {
                 pitch: 0.0,
                 roll: 0.0,
@@ -786,9 +2007,9 @@ impl HorizonWidget {
         }
     }
 
-    pub fn on_window(self, window: &mut Window) -> Arc<RwLock<DroneOrientation>> {
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<DroneOrientation>> {
         let hz = self.props.clone();
-        window.widgets.push(Box::new(self));
+        window.add_widget(Box::new(self));
         hz
     }
 }
@@ -800,20 +2021,22 @@ pub struct ImageCarouselWidget {
 }
 
 impl Widget for ImageCarouselWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, _fonts: &mut FontManager) -> Result<(), Error> {
         let (x, y, w, h) = self.widget.compute_dim(canvas);
         let zw: f32 = self.widget.canvas_width as f32 * 0.7;
 
-        let p = self.props.read().unwrap();
+        let image_dir = self.props.read().unwrap().image_dir.clone();
+        let files = utils::DirectoryReader::new(&image_dir).list();
+
+        let mut p = self.props.write().unwrap();
+        p.set_image_count(files.len());
         let images_no = p.number_of_images;
-        let image_dir = p.image_dir.clone();
         let offset = p.offset;
         let show = p.show;
         drop(p);
 
         let dw = w as usize / images_no;
 
-        let files = utils::DirectoryReader::new(&image_dir).list();
         let mut images = Vec::new();
         let mut zoomed_image = None;
         for i in 0..images_no {
@@ -857,7 +2080,7 @@ impl Widget for ImageCarouselWidget {
             if i < images.len() {
                 let tex = &images[i];
                 let g = tex.texture.read().unwrap();
-                sdl::sdl_render_tex(canvas, &g, x1 + dw as i32 / 2, y);
+                sdl::sdl_render_tex(canvas, &g, x1 + dw as i32 / 2, y)?;
                 drop(g);
                 if show {
                     if let Some(ref zimage) = zoomed_image {
@@ -867,7 +2090,7 @@ impl Widget for ImageCarouselWidget {
                             &g,
                             (self.widget.canvas_width / 2) as i32,
                             (self.widget.canvas_height / 2) as i32,
-                        );
+                        )?;
                         drop(g);
                     }
                 }
@@ -875,6 +2098,77 @@ impl Widget for ImageCarouselWidget {
             canvas.set_draw_color(color::CYBER_COOL_BLUE.to_sdl_rgba());
             let _ = canvas.draw_rect(Rect::new(x1, sy, dw as u32, h as u32));
         }
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
+    }
+
+    fn reload_assets(&mut self) {
+        self.widget.unload_textures();
+        self.texcache.invalidate_modified();
+    }
+
+    // cached textures are scaled to the old widget size
+    fn resize(&mut self, width: u32, height: u32) {
+        self.widget.set_canvas_size(width, height);
+        self.texcache.clear();
+    }
+
+    // Clicking the first image toggles the zoomed view, clicking any other
+    // image scrolls the carousel so that it becomes the first one.
+    fn handle_event(&mut self, event: &Event, ctx: &EventContext) -> EventResult {
+        match event {
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                ..
+            }
+            | Event::FingerDown { .. } => {
+                let (px, _) = ctx.pointer.unwrap_or((ctx.area.left(), 0));
+                let mut p = self.props.write().unwrap();
+                let dw = ctx.area.width() as usize / p.number_of_images.max(1);
+                let clicked = (px - ctx.area.left()).max(0) as usize / dw.max(1);
+                if clicked == 0 {
+                    p.toggle_show();
+                } else {
+                    p.scroll_right(clicked);
+                }
+                EventResult::Consumed
+            }
+            Event::MouseWheel { y, .. } if *y != 0 => {
+                let mut p = self.props.write().unwrap();
+                if *y > 0 {
+                    p.turn_left();
+                } else {
+                    p.turn_right();
+                }
+                EventResult::Consumed
+            }
+            _ => EventResult::Propagate,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    // Left/right scroll the images, activate toggles the zoomed view and
+    // back leaves it.
+    fn focus_action(&mut self, action: FocusAction) -> EventResult {
+        let mut p = self.props.write().unwrap();
+        match action {
+            FocusAction::Adjust(Direction::Left) => p.turn_left(),
+            FocusAction::Adjust(Direction::Right) => p.turn_right(),
+            FocusAction::Activate => p.toggle_show(),
+            FocusAction::Back if p.show => p.toggle_show(),
+            _ => return EventResult::Propagate,
+        }
+        EventResult::Consumed
     }
 }
 
@@ -886,15 +2180,16 @@ impl ImageCarouselWidget {
                 image_dir: image_dir.to_owned(),
                 number_of_images,
                 offset: 0,
+                image_count: 0,
                 show: false,
             })),
             texcache: TextureCache::new(),
         }
     }
 
-    pub fn on_window(self, window: &mut Window) -> Arc<RwLock<ImageCarousel>> {
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<ImageCarousel>> {
         let hz = self.props.clone();
-        window.widgets.push(Box::new(self));
+        window.add_widget(Box::new(self));
         hz
     }
 }
@@ -906,151 +2201,1249 @@ pub struct DroneYawWidget {
 }
 
 impl Widget for DroneYawWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
+        let (x, y, w, h) = self.widget.compute_dim(canvas);
+        let p = self.props.read().unwrap();
+        let angle = p.value;
+        drop(p);
+
+        let bg = self.texcache.load_texture(
+            canvas,
+            self.widget.texture_name(0)?,
+            w as u32,
+            h as u32,
+            None,
+        )?;
+
+        let fg = self.texcache.load_texture(
+            canvas,
+            self.widget.texture_name(1)?,
+            w as u32 * 4 / 5, // somewhat smaller than the background
+            h as u32 * 4 / 5,
+            None,
+        )?;
+
+        bg.render(canvas, x, y)?;
+        fg.render_rot(canvas, x, y, angle)?;
+
+        let text = format!("{:.1}{}", angle, 176 as char);
+        sdl::sdl_text(fonts, canvas, &text, 24, color::BLACK.clone(), x, y);
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
+    }
+
+    fn reload_assets(&mut self) {
+        self.widget.unload_textures();
+        self.texcache.invalidate_modified();
+    }
+
+    // cached textures are scaled to the old widget size
+    fn resize(&mut self, width: u32, height: u32) {
+        self.widget.set_canvas_size(width, height);
+        self.texcache.clear();
+    }
+}
+
+impl DroneYawWidget {
+    pub fn new(widget: CommonWidgetProps) -> Self {
+        Self {
+            widget: widget.default_textures(vec!["images/yaw-bg.png", "images/yaw-fg.png"]),
+            props: Arc::new(RwLock::new(FloatGenericValue { value: 0.0 })),
+            texcache: TextureCache::new(),
+        }
+    }
+
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<FloatGenericValue>> {
+        let hz = self.props.clone();
+        window.add_widget(Box::new(self));
+        hz
+    }
+}
+
+pub struct FlightLogWidget {
+    widget: CommonWidgetProps,
+    props: Arc<RwLock<FlightLog>>,
+    texcache: TextureCache,
+}
+
+impl Widget for FlightLogWidget {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, _fonts: &mut FontManager) -> Result<(), Error> {
         let (x, y, w, h) = self.widget.compute_dim(canvas);
+
+        let bg = self.texcache.load_texture(
+            canvas,
+            self.widget.texture_name(0)?,
+            w as u32,
+            h as u32,
+            None,
+        )?;
+
+        bg.render(canvas, x, y)?;
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
+    }
+
+    fn reload_assets(&mut self) {
+        self.widget.unload_textures();
+        self.texcache.invalidate_modified();
+    }
+
+    // cached textures are scaled to the old widget size
+    fn resize(&mut self, width: u32, height: u32) {
+        self.widget.set_canvas_size(width, height);
+        self.texcache.clear();
+    }
+}
+
+impl FlightLogWidget {
+    pub fn new(widget: CommonWidgetProps) -> Self {
+        Self {
+            widget: widget.default_textures(vec!["images/radius-bg.png"]),
+            props: Arc::new(RwLock::new(FlightLog {})),
+            texcache: TextureCache::new(),
+        }
+    }
+
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<FlightLog>> {
+        let hz = self.props.clone();
+        window.add_widget(Box::new(self));
+        hz
+    }
+}
+
+type ClickCallback = Box<dyn FnMut(&ButtonState)>;
+
+// Pointer and focus handling shared by the button, toggle and checkbox.
+struct Clickable {
+    props: Arc<RwLock<ButtonState>>,
+    // toggle and checkbox flip `checked` on click
+    toggles: bool,
+    on_click: Option<ClickCallback>,
+}
+
+impl Clickable {
+    fn new(label: &str, toggles: bool) -> Self {
+        Self {
+            props: Arc::new(RwLock::new(ButtonState {
+                label: label.to_owned(),
+                enabled: true,
+                checked: false,
+                pressed: false,
+                hover: false,
+                clicks: 0,
+            })),
+            toggles,
+            on_click: None,
+        }
+    }
+
+    fn click(&mut self) {
+        let mut p = self.props.write().unwrap();
+        if self.toggles {
+            p.checked = !p.checked;
+        }
+        p.clicks += 1;
+        drop(p);
+        if let Some(ref mut on_click) = self.on_click {
+            // the callback may read the state through the handle too
+            let p = self.props.read().unwrap();
+            on_click(&p);
+        }
+    }
+
+    // Press inside the widget and release inside it is a click, releasing
+    // outside cancels it.
+    fn handle_event(&mut self, event: &Event, ctx: &EventContext) -> EventResult {
+        if !self.props.read().unwrap().enabled {
+            return EventResult::Propagate;
+        }
+        let inside = ctx
+            .pointer
+            .map(|(x, y)| ctx.area.contains_point(Point::new(x, y)))
+            .unwrap_or(false);
+        match event {
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                ..
+            }
+            | Event::FingerDown { .. } => {
+                self.props.write().unwrap().pressed = true;
+                EventResult::Consumed
+            }
+            Event::MouseButtonUp {
+                mouse_btn: MouseButton::Left,
+                ..
+            }
+            | Event::FingerUp { .. } => {
+                let mut p = self.props.write().unwrap();
+                if !p.pressed {
+                    return EventResult::Propagate;
+                }
+                p.pressed = false;
+                drop(p);
+                if inside {
+                    self.click();
+                }
+                EventResult::Consumed
+            }
+            _ => EventResult::Propagate,
+        }
+    }
+
+    fn hover(&mut self, area: Rect, pointer: (i32, i32)) {
+        let inside = area.contains_point(Point::new(pointer.0, pointer.1));
+        self.props.write().unwrap().hover = inside;
+    }
+
+    fn focusable(&self) -> bool {
+        self.props.read().unwrap().enabled
+    }
+
+    fn focus_action(&mut self, action: FocusAction) -> EventResult {
+        if action != FocusAction::Activate || !self.focusable() {
+            return EventResult::Propagate;
+        }
+        self.click();
+        EventResult::Consumed
+    }
+
+    // Fill color for the current state.
+    fn color(&self, base: &RgbColor) -> RgbColor {
+        let p = self.props.read().unwrap();
+        if !p.enabled {
+            color::GREY_50.clone()
+        } else if p.pressed {
+            base.mul(0.7).with_alpha(1.0)
+        } else if p.hover {
+            base.blend_normal(color::WHITE.with_alpha(0.25))
+        } else {
+            base.clone()
+        }
+    }
+
+    // Texture for the current state: normal, pressed or checked, disabled.
+    // Missing ones fall back to the first texture.
+    fn texture_idx(&self, textures: usize) -> usize {
+        let p = self.props.read().unwrap();
+        let idx = if !p.enabled {
+            2
+        } else if p.pressed || p.checked {
+            1
+        } else {
+            0
+        };
+        if idx < textures {
+            idx
+        } else {
+            0
+        }
+    }
+}
+
+fn draw_label(
+    fonts: &mut FontManager,
+    canvas: &mut Canvas<SdlWin>,
+    label: &str,
+    halign: text::HAlign,
+    area: Rect,
+    enabled: bool,
+) {
+    if label.is_empty() {
+        return;
+    }
+    let size = (area.height() as f32 * 0.45).max(8.0) as u16;
+    let color = if enabled {
+        color::WHITE.clone()
+    } else {
+        color::GREY_80.clone()
+    };
+    let style = TextStyle::new(size)
+        .family(font::SANS)
+        .color(color)
+        .align(halign, text::VAlign::Middle);
+    text::draw_text(fonts, canvas, &[Span::new(label)], &style, area);
+}
+
+// Push button, e.g. take-off or land:
+//
+// ```
+// let take_off = ButtonWidget::new(props, "TAKE OFF")
+//     .on_click(|_| tracing::info!("take off"))
+//     .on_window(&mut win);
+// if take_off.write().unwrap().take_clicks() > 0 { ... }
+// ```
+//
+// Drawn as a rounded box in the widget color, or with the textures for the
+// normal, pressed and disabled state.
+pub struct ButtonWidget {
+    widget: CommonWidgetProps,
+    clickable: Clickable,
+}
+
+impl ButtonWidget {
+    pub fn new(widget: CommonWidgetProps, label: &str) -> Self {
+        Self {
+            widget,
+            clickable: Clickable::new(label, false),
+        }
+    }
+
+    // Called after every click with the new state.
+    pub fn on_click(mut self, f: impl FnMut(&ButtonState) + 'static) -> Self {
+        self.clickable.on_click = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<ButtonState>> {
+        let hz = self.clickable.props.clone();
+        window.add_widget(Box::new(self));
+        hz
+    }
+}
+
+impl Widget for ButtonWidget {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
+        let (x, y, w, h) = self.widget.compute_dim(canvas);
+        let area = Rect::from_center(Point::new(x, y), w.max(1) as u32, h.max(1) as u32);
+        if !self.widget.texture_names.is_empty() {
+            self.widget.load_textures(canvas)?;
+            let idx = self.clickable.texture_idx(self.widget.texture_names.len());
+            sdl::sdl_scale_tex(canvas, self.widget.texture(idx)?, x, y, w, h)?;
+        } else {
+            let fill = self
+                .clickable
+                .color(&self.widget.color_or(&color::CYBER_COOL_BLUE));
+            let radius = (area.height() / 5) as i16;
+            let (x1, y1) = (area.left() as i16, area.top() as i16);
+            let (x2, y2) = ((area.right() - 1) as i16, (area.bottom() - 1) as i16);
+            let _ = canvas.rounded_box(x1, y1, x2, y2, radius, fill.to_sdl_rgba());
+            let _ = canvas.rounded_rectangle(x1, y1, x2, y2, radius, color::WHITE.to_sdl_rgba());
+        }
+        let p = self.clickable.props.read().unwrap();
+        let (label, enabled) = (p.label.clone(), p.enabled);
+        drop(p);
+        draw_label(fonts, canvas, &label, text::HAlign::Center, area, enabled);
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
+    }
+
+    fn handle_event(&mut self, event: &Event, ctx: &EventContext) -> EventResult {
+        self.clickable.handle_event(event, ctx)
+    }
+
+    fn hover(&mut self, pointer: (i32, i32)) {
+        self.clickable.hover(self.widget.area(), pointer);
+    }
+
+    fn focusable(&self) -> bool {
+        self.clickable.focusable()
+    }
+
+    fn focus_action(&mut self, action: FocusAction) -> EventResult {
+        self.clickable.focus_action(action)
+    }
+}
+
+// On/off switch with the label on the right, e.g. video recording. The
+// state handle tells whether it is on, see `ButtonState::is_checked`.
+pub struct ToggleWidget {
+    widget: CommonWidgetProps,
+    clickable: Clickable,
+}
+
+impl ToggleWidget {
+    pub fn new(widget: CommonWidgetProps, label: &str) -> Self {
+        Self {
+            widget,
+            clickable: Clickable::new(label, true),
+        }
+    }
+
+    pub fn on_click(mut self, f: impl FnMut(&ButtonState) + 'static) -> Self {
+        self.clickable.on_click = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<ButtonState>> {
+        let hz = self.clickable.props.clone();
+        window.add_widget(Box::new(self));
+        hz
+    }
+}
+
+impl Widget for ToggleWidget {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
+        let (x, y, w, h) = self.widget.compute_dim(canvas);
+        let area = Rect::from_center(Point::new(x, y), w.max(1) as u32, h.max(1) as u32);
+        let p = self.clickable.props.read().unwrap();
+        let (label, enabled, checked) = (p.label.clone(), p.enabled, p.checked);
+        drop(p);
+
+        let track_w = (2 * area.height()).min(area.width()) as i32;
+        let track = Rect::new(area.left(), area.top(), track_w as u32, area.height());
+        if !self.widget.texture_names.is_empty() {
+            self.widget.load_textures(canvas)?;
+            let idx = self.clickable.texture_idx(self.widget.texture_names.len());
+            let center = track.center();
+            sdl::sdl_scale_tex(
+                canvas,
+                self.widget.texture(idx)?,
+                center.x(),
+                center.y(),
+                track_w,
+                h,
+            )?;
+        } else {
+            let on = self.clickable.color(&self.widget.color_or(&color::GREEN));
+            let fill = if checked { on } else { color::GREY_20.clone() };
+            let radius = (h / 2) as i16;
+            let (x1, y1) = (track.left() as i16, track.top() as i16);
+            let (x2, y2) = ((track.right() - 1) as i16, (track.bottom() - 1) as i16);
+            let _ = canvas.rounded_box(x1, y1, x2, y2, radius, fill.to_sdl_rgba());
+            let _ = canvas.rounded_rectangle(x1, y1, x2, y2, radius, color::WHITE.to_sdl_rgba());
+            let knob_x = if checked {
+                track.right() - h / 2
+            } else {
+                track.left() + h / 2
+            };
+            let knob = if enabled {
+                color::WHITE.clone()
+            } else {
+                color::GREY_80.clone()
+            };
+            let _ = canvas.filled_circle(
+                knob_x as i16,
+                track.center().y() as i16,
+                (h / 2 - h / 8).max(1) as i16,
+                knob.to_sdl_rgba(),
+            );
+        }
+        let gap = h / 3;
+        let label_area = Rect::new(
+            track.right() + gap,
+            area.top(),
+            (w - track_w - gap).max(1) as u32,
+            area.height(),
+        );
+        draw_label(
+            fonts,
+            canvas,
+            &label,
+            text::HAlign::Left,
+            label_area,
+            enabled,
+        );
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
+    }
+
+    fn handle_event(&mut self, event: &Event, ctx: &EventContext) -> EventResult {
+        self.clickable.handle_event(event, ctx)
+    }
+
+    fn hover(&mut self, pointer: (i32, i32)) {
+        self.clickable.hover(self.widget.area(), pointer);
+    }
+
+    fn focusable(&self) -> bool {
+        self.clickable.focusable()
+    }
+
+    fn focus_action(&mut self, action: FocusAction) -> EventResult {
+        self.clickable.focus_action(action)
+    }
+}
+
+// Check box with the label on the right.
+pub struct CheckboxWidget {
+    widget: CommonWidgetProps,
+    clickable: Clickable,
+}
+
+impl CheckboxWidget {
+    pub fn new(widget: CommonWidgetProps, label: &str) -> Self {
+        Self {
+            widget,
+            clickable: Clickable::new(label, true),
+        }
+    }
+
+    pub fn on_click(mut self, f: impl FnMut(&ButtonState) + 'static) -> Self {
+        self.clickable.on_click = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<ButtonState>> {
+        let hz = self.clickable.props.clone();
+        window.add_widget(Box::new(self));
+        hz
+    }
+}
+
+impl Widget for CheckboxWidget {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
+        let (x, y, w, h) = self.widget.compute_dim(canvas);
+        let area = Rect::from_center(Point::new(x, y), w.max(1) as u32, h.max(1) as u32);
+        let p = self.clickable.props.read().unwrap();
+        let (label, enabled, checked) = (p.label.clone(), p.enabled, p.checked);
+        drop(p);
+
+        let side = area.height().min(area.width());
+        let bx = Rect::new(area.left(), area.top(), side, side);
+        if !self.widget.texture_names.is_empty() {
+            self.widget.load_textures(canvas)?;
+            let idx = self.clickable.texture_idx(self.widget.texture_names.len());
+            let center = bx.center();
+            let side = side as i32;
+            sdl::sdl_scale_tex(
+                canvas,
+                self.widget.texture(idx)?,
+                center.x(),
+                center.y(),
+                side,
+                side,
+            )?;
+        } else {
+            let c = self.clickable.color(&self.widget.color_or(&color::WHITE));
+            canvas.set_draw_color(c.to_sdl_rgba());
+            let border = (side / 12).max(1) as i32;
+            for i in 0..border {
+                let _ = canvas.draw_rect(Rect::new(
+                    bx.left() + i,
+                    bx.top() + i,
+                    side - 2 * i as u32,
+                    side - 2 * i as u32,
+                ));
+            }
+            if checked {
+                let s = side as i32;
+                let (l, t) = (bx.left(), bx.top());
+                let width = (border * 2).clamp(1, 255) as u8;
+                let color = c.to_sdl_rgba();
+                let _ = canvas.thick_line(
+                    (l + s / 5) as i16,
+                    (t + s / 2) as i16,
+                    (l + s * 2 / 5) as i16,
+                    (t + s * 3 / 4) as i16,
+                    width,
+                    color,
+                );
+                let _ = canvas.thick_line(
+                    (l + s * 2 / 5) as i16,
+                    (t + s * 3 / 4) as i16,
+                    (l + s * 4 / 5) as i16,
+                    (t + s / 4) as i16,
+                    width,
+                    color,
+                );
+            }
+        }
+        let gap = side as i32 / 3;
+        let label_area = Rect::new(
+            bx.right() + gap,
+            area.top(),
+            (w - side as i32 - gap).max(1) as u32,
+            area.height(),
+        );
+        draw_label(
+            fonts,
+            canvas,
+            &label,
+            text::HAlign::Left,
+            label_area,
+            enabled,
+        );
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
+    }
+
+    fn handle_event(&mut self, event: &Event, ctx: &EventContext) -> EventResult {
+        self.clickable.handle_event(event, ctx)
+    }
+
+    fn hover(&mut self, pointer: (i32, i32)) {
+        self.clickable.hover(self.widget.area(), pointer);
+    }
+
+    fn focusable(&self) -> bool {
+        self.clickable.focusable()
+    }
+
+    fn focus_action(&mut self, action: FocusAction) -> EventResult {
+        self.clickable.focus_action(action)
+    }
+}
+
+type TextCallback = Box<dyn FnMut(&str)>;
+
+// Single line text input, e.g. the WiFi SSID or the mission name:
+//
+// ```
+// let ssid = TextInputWidget::new(props, "SSID")
+//     .max_len(32)
+//     .on_submit(|text| tracing::info!("ssid {text}"))
+//     .on_window(&mut win);
+// if let Some(text) = ssid.write().unwrap().take_submitted() { ... }
+// ```
+//
+// A click or touch starts the editing, the text comes from the SDL text
+// input so IME composition works. Return submits, Escape or a press outside
+// ends the editing. Shift with the arrows, Home and End selects, Ctrl+A/C/X/V
+// work with the clipboard.
+//
+// With `on_screen_keyboard` a touch, or the A button on the focused input,
+// opens a keyboard at the bottom of the window. Its keys are touched, or
+// navigated with the D-pad, A types, X deletes and B closes it. Add the
+// input after the other widgets, so the keyboard is drawn on top.
+pub struct TextInputWidget {
+    widget: CommonWidgetProps,
+    props: Arc<RwLock<TextInputState>>,
+    on_screen_keyboard: bool,
+    on_change: Option<TextCallback>,
+    on_submit: Option<TextCallback>,
+    // taken from the canvas on the first draw, controls the text input and
+    // the clipboard
+    video: Option<VideoSubsystem>,
+    // long text is scrolled to keep the cursor visible, in pixels
+    scroll: i32,
+    timer: utils::GameTimer,
+}
+
+impl TextInputWidget {
+    pub fn new(widget: CommonWidgetProps, placeholder: &str) -> Self {
+        Self {
+            widget,
+            props: Arc::new(RwLock::new(TextInputState {
+                edit: TextEdit::default(),
+                placeholder: placeholder.to_owned(),
+                editing: false,
+                keyboard: None,
+                changed: false,
+                submitted: None,
+            })),
+            on_screen_keyboard: false,
+            on_change: None,
+            on_submit: None,
+            video: None,
+            scroll: 0,
+            timer: utils::GameTimer::new(Duration::from_millis(500)),
+        }
+    }
+
+    pub fn max_len(self, max_len: usize) -> Self {
+        self.props.write().unwrap().edit.set_max_len(Some(max_len));
+        self
+    }
+
+    pub fn on_screen_keyboard(self, on_screen_keyboard: bool) -> Self {
+        Self {
+            on_screen_keyboard,
+            ..self
+        }
+    }
+
+    // Called with the text after every edit.
+    pub fn on_change(self, f: impl FnMut(&str) + 'static) -> Self {
+        Self {
+            on_change: Some(Box::new(f)),
+            ..self
+        }
+    }
+
+    // Called with the text when submitted by Return or DONE.
+    pub fn on_submit(self, f: impl FnMut(&str) + 'static) -> Self {
+        Self {
+            on_submit: Some(Box::new(f)),
+            ..self
+        }
+    }
+
+    pub fn on_window(self, window: &mut impl WidgetParent) -> Arc<RwLock<TextInputState>> {
+        let hz = self.props.clone();
+        window.add_widget(Box::new(self));
+        hz
+    }
+
+    fn begin(&mut self, area: Rect, keyboard: bool) {
+        let mut p = self.props.write().unwrap();
+        if keyboard && self.on_screen_keyboard && p.keyboard.is_none() {
+            p.keyboard = Some(Keyboard::default());
+        }
+        if p.editing {
+            return;
+        }
+        p.editing = true;
+        drop(p);
+        if let Some(ref video) = self.video {
+            let text_input = video.text_input();
+            // IME candidates are shown next to the area
+            text_input.set_rect(area);
+            text_input.start();
+        }
+    }
+
+    fn end(&mut self) {
+        let mut p = self.props.write().unwrap();
+        if !p.editing {
+            return;
+        }
+        p.editing = false;
+        p.keyboard = None;
+        p.edit.set_composition("", 0);
+        drop(p);
+        if let Some(ref video) = self.video {
+            video.text_input().stop();
+        }
+    }
+
+    // Applies the edit, notifies when the text changed.
+    fn edit<T>(&mut self, f: impl FnOnce(&mut TextEdit) -> T) -> T {
+        let mut p = self.props.write().unwrap();
+        let before = p.edit.text().to_owned();
+        let r = f(&mut p.edit);
+        if p.edit.text() == before {
+            return r;
+        }
+        p.changed = true;
+        let text = p.edit.text().to_owned();
+        drop(p);
+        if let Some(ref mut on_change) = self.on_change {
+            on_change(&text);
+        }
+        r
+    }
+
+    fn submit(&mut self) {
+        let text = self.props.read().unwrap().edit.text().to_owned();
+        self.props.write().unwrap().submitted = Some(text.clone());
+        self.end();
+        if let Some(ref mut on_submit) = self.on_submit {
+            on_submit(&text);
+        }
+    }
+
+    fn copy(&self, text: &str) {
+        if let Some(ref video) = self.video {
+            if let Err(e) = video.clipboard().set_clipboard_text(text) {
+                tracing::error!("can't copy to clipboard: {e}");
+            }
+        }
+    }
+
+    fn paste(&mut self) {
+        let text = match self.video.as_ref().map(|v| v.clipboard().clipboard_text()) {
+            Some(Ok(text)) => text,
+            Some(Err(e)) => {
+                tracing::error!("can't paste from clipboard: {e}");
+                return;
+            }
+            None => return,
+        };
+        self.edit(|e| e.insert(&text));
+    }
+
+    fn key_down(&mut self, keycode: Keycode, keymod: Mod) {
+        // keys belong to the IME while composing
+        if !self.props.read().unwrap().edit.composition().is_empty() {
+            return;
+        }
+        let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+        let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD | Mod::LGUIMOD | Mod::RGUIMOD);
+        match keycode {
+            Keycode::LEFT => self.edit(|e| e.left(shift)),
+            Keycode::RIGHT => self.edit(|e| e.right(shift)),
+            Keycode::HOME => self.edit(|e| e.home(shift)),
+            Keycode::END => self.edit(|e| e.end(shift)),
+            Keycode::BACKSPACE => self.edit(TextEdit::backspace),
+            Keycode::DELETE => self.edit(TextEdit::delete),
+            Keycode::RETURN | Keycode::KP_ENTER => self.submit(),
+            Keycode::ESCAPE => self.end(),
+            Keycode::A if ctrl => self.edit(TextEdit::select_all),
+            Keycode::C if ctrl => {
+                let selected = self
+                    .props
+                    .read()
+                    .unwrap()
+                    .edit
+                    .selected_text()
+                    .map(str::to_owned);
+                if let Some(text) = selected {
+                    self.copy(&text);
+                }
+            }
+            Keycode::X if ctrl => {
+                if let Some(text) = self.edit(TextEdit::cut) {
+                    self.copy(&text);
+                }
+            }
+            Keycode::V if ctrl => self.paste(),
+            _ => {}
+        }
+    }
+
+    fn keyboard_area(&self) -> Rect {
+        keyboard_area(self.widget.canvas_width, self.widget.canvas_height)
+    }
+
+    // Types the key under the pointer while the on-screen keyboard is open.
+    // Presses on the keyboard between the keys do nothing.
+    fn press_key(&mut self, point: Point) -> Option<EventResult> {
+        let area = self.keyboard_area();
+        if !area.contains_point(point) {
+            return None;
+        }
+        let mut p = self.props.write().unwrap();
+        let keyboard = p.keyboard.as_mut()?;
+        if let Some((row, col)) = key_at(keyboard, area, point) {
+            keyboard.select(row, col);
+            drop(p);
+            self.keyboard_action(FocusAction::Activate);
+        }
+        Some(EventResult::Consumed)
+    }
+
+    // D-pad and buttons while the on-screen keyboard is open.
+    fn keyboard_action(&mut self, action: FocusAction) -> EventResult {
+        let mut p = self.props.write().unwrap();
+        let Some(ref mut keyboard) = p.keyboard else {
+            return EventResult::Propagate;
+        };
+        match action {
+            FocusAction::Adjust(dir) => keyboard.navigate(dir),
+            FocusAction::Activate => {
+                let key = keyboard.press();
+                drop(p);
+                match key {
+                    Key::Char(c) => self.edit(|e| e.insert(&c.to_string())),
+                    Key::Space => self.edit(|e| e.insert(" ")),
+                    Key::Backspace => self.edit(TextEdit::backspace),
+                    Key::Left => self.edit(|e| e.left(false)),
+                    Key::Right => self.edit(|e| e.right(false)),
+                    Key::Done => self.submit(),
+                    Key::Shift => {}
+                }
+            }
+            FocusAction::Back => {
+                drop(p);
+                self.end();
+            }
+        }
+        EventResult::Consumed
+    }
+}
+
+impl Widget for TextInputWidget {
+    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, fonts: &mut FontManager) -> Result<(), Error> {
+        if self.video.is_none() {
+            self.video = Some(canvas.window().subsystem().clone());
+        }
+        let (x, y, w, h) = self.widget.compute_dim(canvas);
+        let area = Rect::from_center(Point::new(x, y), w.max(1) as u32, h.max(1) as u32);
+        let p = self.props.read().unwrap();
+        let (edit, editing, keyboard) = (p.edit.clone(), p.editing, p.keyboard.clone());
+        let placeholder = p.placeholder.clone();
+        drop(p);
+
+        let accent = self.widget.color_or(&color::CYBER_COOL_BLUE);
+        let radius = (area.height() / 5) as i16;
+        let (x1, y1) = (area.left() as i16, area.top() as i16);
+        let (x2, y2) = ((area.right() - 1) as i16, (area.bottom() - 1) as i16);
+        let border = if editing { &accent } else { &*color::GREY_50 };
+        let _ = canvas.rounded_box(x1, y1, x2, y2, radius, color::GREY_20.to_sdl_rgba());
+        let _ = canvas.rounded_rectangle(x1, y1, x2, y2, radius, border.to_sdl_rgba());
+
+        let inner = container::inset(area, area.height() as i32 / 4);
+        let style = TextStyle::new((inner.height() as f32 * 0.9).max(8.0) as u16)
+            .family(font::SANS)
+            .align(text::HAlign::Left, text::VAlign::Middle);
+        if edit.text().is_empty() && edit.composition().is_empty() {
+            self.scroll = 0;
+            if !editing {
+                let style = style.clone().color(color::GREY_50.clone());
+                text::draw_text(fonts, canvas, &[Span::new(&placeholder)], &style, inner);
+            }
+        }
+
+        // the composition is shown at the cursor until the IME commits it
+        let before = edit.before_cursor();
+        let after = &edit.text()[before.len()..];
+        let shown = format!("{before}{}{after}", edit.composition());
+        let font = style.font();
+        let mut measure = |s: &str| {
+            fonts
+                .font(&font, s)
+                .ok()
+                .and_then(|f| f.size_of(s).ok())
+                .map(|(w, _)| w as i32)
+                .unwrap_or(0)
+        };
+        let composition = (
+            measure(before),
+            measure(&format!("{before}{}", edit.composition())),
+        );
+        let cursor = measure(&format!("{before}{}", edit.composition_before_cursor()));
+        let selection = edit
+            .selection()
+            .map(|(start, end)| (measure(edit.prefix(start)), measure(edit.prefix(end))));
+        let width = inner.width() as i32;
+        if cursor - self.scroll > width {
+            self.scroll = cursor - width;
+        } else if cursor < self.scroll {
+            self.scroll = cursor;
+        }
+
+        canvas.set_clip_rect(inner);
+        let left = inner.left() - self.scroll;
+        let (top, bottom) = (inner.top() as i16, (inner.bottom() - 1) as i16);
+        if let Some((start, end)) = selection {
+            let c = accent.with_alpha(0.5).to_sdl_rgba();
+            let _ = canvas.box_((left + start) as i16, top, (left + end) as i16, bottom, c);
+        }
+        if !shown.is_empty() {
+            let texture = fonts.text(canvas, &shown, &font, &color::WHITE)?;
+            let q = texture.query();
+            let dst = Rect::new(
+                left,
+                inner.center().y() - q.height as i32 / 2,
+                q.width,
+                q.height,
+            );
+            canvas.copy(texture, None, dst)?;
+        }
+        if composition.1 > composition.0 {
+            let (cx1, cx2) = ((left + composition.0) as i16, (left + composition.1) as i16);
+            let _ = canvas.hline(cx1, cx2, bottom, color::WHITE.to_sdl_rgba());
+        }
+        if editing && !self.timer.blink() {
+            let _ = canvas.vline((left + cursor) as i16, top, bottom, accent.to_sdl_rgba());
+        }
+        canvas.set_clip_rect(None);
+
+        if let Some(keyboard) = keyboard {
+            draw_keyboard(canvas, fonts, &keyboard, self.keyboard_area(), &accent);
+        }
+        Ok(())
+    }
+
+    fn common(&self) -> &CommonWidgetProps {
+        &self.widget
+    }
+
+    fn common_mut(&mut self) -> &mut CommonWidgetProps {
+        &mut self.widget
+    }
+
+    fn handle_event(&mut self, event: &Event, ctx: &EventContext) -> EventResult {
         let p = self.props.read().unwrap();
-        let angle = p.value;
+        let (editing, keyboard) = (p.editing, p.keyboard.is_some());
         drop(p);
+        if let (true, Some((px, py))) = (is_pointer_press(event), ctx.pointer) {
+            if let Some(result) = self.press_key(Point::new(px, py)) {
+                return result;
+            }
+        }
+        match event {
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                ..
+            } => {
+                self.begin(ctx.area, false);
+                EventResult::Consumed
+            }
+            // no physical keyboard on touch screens
+            Event::FingerDown { .. } => {
+                self.begin(ctx.area, true);
+                EventResult::Consumed
+            }
+            Event::MouseButtonUp { .. } | Event::FingerUp { .. } if ctx.pointer.is_some() => {
+                EventResult::Consumed
+            }
+            // the focus navigation may be off, the keyboard handles the pad itself
+            Event::ControllerButtonDown { button, .. } if keyboard => match button {
+                Button::X => {
+                    self.edit(TextEdit::backspace);
+                    EventResult::Consumed
+                }
+                Button::Start => {
+                    self.submit();
+                    EventResult::Consumed
+                }
+                _ => match focus::action_of(event) {
+                    Some(action) => self.keyboard_action(action),
+                    None => EventResult::Propagate,
+                },
+            },
+            Event::TextInput { text, .. } if editing => {
+                self.edit(|e| {
+                    e.set_composition("", 0);
+                    e.insert(text);
+                });
+                EventResult::Consumed
+            }
+            Event::TextEditing { text, start, .. } if editing => {
+                let cursor = (*start).max(0) as usize;
+                self.props
+                    .write()
+                    .unwrap()
+                    .edit
+                    .set_composition(text, cursor);
+                EventResult::Consumed
+            }
+            Event::KeyDown {
+                keycode: Some(keycode),
+                keymod,
+                ..
+            } if editing => {
+                self.key_down(*keycode, *keymod);
+                EventResult::Consumed
+            }
+            // the app doesn't see the keys typed into the input
+            Event::KeyDown { .. } | Event::KeyUp { .. } if editing => EventResult::Consumed,
+            _ => EventResult::Propagate,
+        }
+    }
 
-        let bg = self
-            .texcache
-            .load_texture(
-                canvas,
-                "images/yaw-bg.png".to_owned(),
-                w as u32,
-                h as u32,
-                None,
-            )
-            .expect("can't load yaw bg texture");
-
-        let fg = self
-            .texcache
-            .load_texture(
-                canvas,
-                "images/yaw-fg.png".to_owned(),
-                w as u32 * 4 / 5, // somewhat smaller than the background
-                h as u32 * 4 / 5,
-                None,
-            )
-            .expect("can't load yaw bg texture");
-
-        bg.render(canvas, x, y);
-        fg.render_rot(canvas, x, y, angle);
+    fn contains_point(&self, point: Point) -> bool {
+        self.widget.area().contains_point(point)
+            || self.props.read().unwrap().keyboard.is_some()
+                && self.keyboard_area().contains_point(point)
+    }
 
-        let text = format!("{:.1}{}", angle, 176 as char);
-        sdl::sdl_text(ttf, canvas, &text, 24, color::BLACK.clone(), x, y);
+    fn focusable(&self) -> bool {
+        true
     }
-}
 
-impl DroneYawWidget {
-    pub fn new(widget: CommonWidgetProps) -> Self {
-        Self {
-            widget,
-            props: Arc::new(RwLock::new(FloatGenericValue { value: 0.0 })),
-            texcache: TextureCache::new(),
+    fn focus_action(&mut self, action: FocusAction) -> EventResult {
+        if self.props.read().unwrap().keyboard.is_some() {
+            return self.keyboard_action(action);
+        }
+        match action {
+            FocusAction::Activate => {
+                self.begin(self.widget.area(), true);
+                EventResult::Consumed
+            }
+            FocusAction::Back if self.captures_keyboard() => {
+                self.end();
+                EventResult::Consumed
+            }
+            _ => EventResult::Propagate,
         }
     }
 
-    pub fn on_window(self, window: &mut Window) -> Arc<RwLock<FloatGenericValue>> {
-        let hz = self.props.clone();
-        window.widgets.push(Box::new(self));
-        hz
+    fn captures_keyboard(&self) -> bool {
+        self.props.read().unwrap().editing
     }
-}
 
-pub struct FlightLogWidget {
-    widget: CommonWidgetProps,
-    props: Arc<RwLock<FlightLog>>,
-    texcache: TextureCache,
+    fn release_keyboard(&mut self) {
+        self.end();
+    }
 }
 
-impl Widget for FlightLogWidget {
-    fn draw(&mut self, canvas: &mut Canvas<SdlWin>, ttf: &mut Sdl2TtfContext) {
-        let (x, y, w, h) = self.widget.compute_dim(canvas);
+// On-screen keyboard over the bottom part of the window.
+fn keyboard_area(width: u32, height: u32) -> Rect {
+    Rect::from_center(
+        Point::new(width as i32 / 2, height as i32 * 4 / 5),
+        width * 3 / 5,
+        height * 3 / 10,
+    )
+}
 
-        let bg = self
-            .texcache
-            .load_texture(
-                canvas,
-                "images/radius-bg.png".to_owned(),
-                w as u32,
-                h as u32,
-                None,
-            )
-            .expect("can't load yaw bg texture");
+// Key cells of the keyboard in the area, row by row.
+fn key_cells(keyboard: &Keyboard, area: Rect) -> Vec<Vec<(Key, Rect)>> {
+    let rows = keyboard.rows();
+    let row_height = area.height() / rows.len() as u32;
+    let spacing = (row_height / 10) as i32;
+    rows.iter()
+        .enumerate()
+        .map(|(r, keys)| {
+            let row = Rect::new(
+                area.left(),
+                area.top() + (r as u32 * row_height) as i32,
+                area.width(),
+                row_height,
+            );
+            let cells = container::stack_rects(
+                row,
+                keys.len(),
+                container::Orientation::Horizontal,
+                spacing,
+                spacing,
+            );
+            keys.iter().copied().zip(cells).collect()
+        })
+        .collect()
+}
 
-        bg.render(canvas, x, y);
-    }
+// Row and column of the key under the point.
+fn key_at(keyboard: &Keyboard, area: Rect, point: Point) -> Option<(usize, usize)> {
+    key_cells(keyboard, area)
+        .iter()
+        .enumerate()
+        .find_map(|(r, keys)| {
+            keys.iter()
+                .position(|(_, cell)| cell.contains_point(point))
+                .map(|c| (r, c))
+        })
 }
 
-impl FlightLogWidget {
-    pub fn new(widget: CommonWidgetProps) -> Self {
-        Self {
-            widget,
-            props: Arc::new(RwLock::new(FlightLog {})),
-            texcache: TextureCache::new(),
+fn draw_keyboard(
+    canvas: &mut Canvas<SdlWin>,
+    fonts: &mut FontManager,
+    keyboard: &Keyboard,
+    area: Rect,
+    accent: &RgbColor,
+) {
+    let bg = color::GREY_20.with_alpha(0.9).to_sdl_rgba();
+    let (x2, y2) = ((area.right() - 1) as i16, (area.bottom() - 1) as i16);
+    let _ = canvas.box_(area.left() as i16, area.top() as i16, x2, y2, bg);
+
+    for (r, keys) in key_cells(keyboard, area).iter().enumerate() {
+        for (c, (key, cell)) in keys.iter().enumerate() {
+            let cell = *cell;
+            let fill = if keyboard.selected() == (r, c) {
+                accent
+            } else if *key == Key::Shift && keyboard.is_shift() {
+                &*color::GREY_80
+            } else {
+                &*color::GREY_50
+            };
+            let radius = (cell.height() / 5) as i16;
+            let (x1, y1) = (cell.left() as i16, cell.top() as i16);
+            let (x2, y2) = ((cell.right() - 1) as i16, (cell.bottom() - 1) as i16);
+            let _ = canvas.rounded_box(x1, y1, x2, y2, radius, fill.to_sdl_rgba());
+            let style = TextStyle::new((cell.height() as f32 * 0.5).max(8.0) as u16)
+                .family(font::SANS)
+                .color(color::WHITE.clone());
+            text::draw_text(fonts, canvas, &[Span::new(&key.label())], &style, cell);
         }
     }
-
-    pub fn on_window(self, window: &mut Window) -> Arc<RwLock<FlightLog>> {
-        let hz = self.props.clone();
-        window.widgets.push(Box::new(self));
-        hz
-    }
 }
 
 pub struct Text {
-    value: String,
+    spans: Vec<Span>,
 }
 
 impl Text {
     pub fn set(&mut self, value: String) {
-        self.value = value;
+        self.spans = vec![Span::new(&value)];
+    }
+
+    // Text parts with their own colors, only used with `TextWidget::style`.
+    pub fn set_spans(&mut self, spans: Vec<Span>) {
+        self.spans = spans;
     }
 }
 
+// Decoder shared by the video widget and its decoding thread.
 pub struct VideoDecoder {
+    // last frame, 3 bytes per pixel
     pub rgb: Arc<RwLock<Vec<u8>>>,
     pub skip_frames: usize,
-}
-
-pub struct Video {
-    width: u32,
-    height: u32,
-}
-
-impl Video {
-    fn new(width: u32, height: u32) -> Self {
-        Self { width, height }
-    }
+    info: RwLock<Option<StreamInfo>>,
+    degraded: RwLock<bool>,
 }
 
 impl VideoDecoder {
-    fn new(width: u32, height: u32, skip_frames: usize) -> Self {
+    fn new(skip_frames: usize) -> Self {
         Self {
-            rgb: Arc::new(RwLock::new(utils::alloc_vec((width * height * 3) as usize))),
+            rgb: Arc::new(RwLock::new(Vec::new())),
             skip_frames,
+            info: RwLock::new(None),
+            degraded: RwLock::new(false),
+        }
+    }
+
+    // True after a loss until the next keyframe arrives.
+    pub fn is_degraded(&self) -> bool {
+        *self.degraded.read().unwrap()
+    }
+
+    // Picture size, profile and level of the stream, none until the first
+    // SPS was received.
+    pub fn stream_info(&self) -> Option<StreamInfo> {
+        *self.info.read().unwrap()
+    }
+
+    pub fn resolution(&self) -> Option<(u32, u32)> {
+        self.stream_info().map(|i| (i.width, i.height))
+    }
+
+    fn decode_all(&self, vd: &mut VideoStreamDecoder) {
+        while vd.decode_images(&self.rgb) == StreamAction::CallNext {}
+        let info = vd.stream_info();
+        if *self.info.read().unwrap() != info {
+            *self.info.write().unwrap() = info;
+        }
+        let degraded = vd.is_degraded();
+        if *self.degraded.read().unwrap() != degraded {
+            *self.degraded.write().unwrap() = degraded;
         }
     }
 
-    fn decode_video(&self, rx: Receiver<Vec<u8>>) {
-        let mut vd = VideoStreamDecoder::new(self.skip_frames);
+    fn decode_video(
+        &self,
+        rx: Receiver<Vec<u8>>,
+        mut vd: VideoStreamDecoder,
+        source_stats: Option<Arc<RwLock<SourceStats>>>,
+    ) {
+        let mut source_lost = 0;
         loop {
             let stream = rx.recv();
             if stream.is_err() {
-                tracing::error!("error decoding stream: {}", stream.err().unwrap());
-                thread::sleep(Duration::from_millis(500));
-                continue;
+                // the sender is gone, decode what is left
+                tracing::info!("video stream ended");
+                vd.end_stream();
+                self.decode_all(&mut vd);
+                return;
             }
             let mut stream = stream.unwrap();
             // tracing::info!("stream.len={}", stream.len());
-            vd.send_stream(&mut stream);
-            loop {
-                let r = vd.decode_images(&self.rgb);
-                if r != StreamAction::CallNext {
-                    break;
+            if let Some(stats) = source_stats.as_ref() {
+                // the gap is in front of the received data
+                let stats = *stats.read().unwrap();
+                let lost = stats.lost + stats.dropped_units;
+                if lost > source_lost {
+                    vd.report_loss();
                 }
+                source_lost = lost;
             }
+            vd.send_stream(&mut stream);
+            self.decode_all(&mut vd);
         }
     }
 }
@@ -1061,12 +3454,24 @@ pub struct ImageCarousel {
     image_dir: String,
     number_of_images: usize,
     offset: usize,
+    // images found in the directory on the last draw
+    image_count: usize,
     show: bool,
 }
 
 impl ImageCarousel {
     pub fn turn_right(&mut self) {
-        self.offset += 1;
+        self.scroll_right(1);
+    }
+
+    // The last image stays visible as the first one.
+    fn scroll_right(&mut self, images: usize) {
+        self.offset = (self.offset + images).min(self.image_count.saturating_sub(1));
+    }
+
+    fn set_image_count(&mut self, image_count: usize) {
+        self.image_count = image_count;
+        self.offset = self.offset.min(image_count.saturating_sub(1));
     }
 
     pub fn turn_left(&mut self) {
@@ -1159,6 +3564,10 @@ impl GamepadStick {
         self.horiz = p.0;
         self.vert = p.1;
     }
+
+    pub fn get(&self) -> (f32, f32) {
+        (self.horiz, self.vert)
+    }
 }
 
 pub struct HorizSlider {
@@ -1166,25 +3575,25 @@ pub struct HorizSlider {
     max_value: f32,
     value: f32,
     steps: f32,
+    // moved by the user since the last `take_changed`
+    changed: bool,
 }
 
 impl HorizSlider {
+    // Size of one step, without steps 1/100 of the range.
+    fn step(&self) -> f32 {
+        let steps = if self.steps > 0.0 { self.steps } else { 100.0 };
+        (self.max_value - self.min_value) / steps
+    }
+
     pub fn inc(&mut self) {
-        if self.value < self.max_value {
-            self.value += (self.max_value - self.min_value) / self.steps;
-        }
-        if self.value > self.max_value {
-            self.value = self.max_value;
-        }
+        let v = self.snap(self.value + self.step());
+        self.set(v);
     }
 
     pub fn dec(&mut self) {
-        if self.value > self.min_value {
-            self.value -= (self.max_value - self.min_value) / self.steps;
-        }
-        if self.value < self.min_value {
-            self.value = self.min_value;
-        }
+        let v = self.snap(self.value - self.step());
+        self.set(v);
     }
 
     pub fn set(&mut self, v: f32) {
@@ -1194,6 +3603,45 @@ impl HorizSlider {
     pub fn get(&self) -> f32 {
         self.value
     }
+
+    pub fn min(&self) -> f32 {
+        self.min_value
+    }
+
+    pub fn max(&self) -> f32 {
+        self.max_value
+    }
+
+    // Nearest value on a step within the range.
+    pub fn snap(&self, v: f32) -> f32 {
+        let v = utils::clamp_range(v, self.min_value, self.max_value);
+        if self.steps <= 0.0 {
+            return v;
+        }
+        let step = self.step();
+        let snapped = self.min_value + ((v - self.min_value) / step).round() * step;
+        utils::clamp_range(snapped, self.min_value, self.max_value)
+    }
+
+    // Position of the value in the range, 0 at min and 1 at max.
+    pub fn fraction(&self) -> f32 {
+        let range = self.max_value - self.min_value;
+        if range == 0.0 {
+            return 0.0;
+        }
+        utils::clamp((self.value - self.min_value) / range)
+    }
+
+    // Sets the value at the position, snapped to the steps.
+    pub fn set_fraction(&mut self, f: f32) {
+        let v = self.min_value + utils::clamp(f) * (self.max_value - self.min_value);
+        self.value = self.snap(v);
+    }
+
+    // New value when the user moved the slider since the last call.
+    pub fn take_changed(&mut self) -> Option<f32> {
+        std::mem::take(&mut self.changed).then_some(self.value)
+    }
 }
 
 pub struct VertThrust {
@@ -1229,3 +3677,656 @@ impl VertThrust {
         self.color2 = color2;
     }
 }
+
+// State of `ButtonWidget`, `ToggleWidget` and `CheckboxWidget`.
+pub struct ButtonState {
+    label: String,
+    enabled: bool,
+    checked: bool,
+    pressed: bool,
+    hover: bool,
+    clicks: u32,
+}
+
+impl ButtonState {
+    pub fn set_label(&mut self, label: &str) {
+        self.label = label.to_owned();
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    // Disabled widgets are grayed out and ignore the input.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.pressed = false;
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked;
+    }
+
+    // Toggle is on or the check box is checked.
+    pub fn is_checked(&self) -> bool {
+        self.checked
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    pub fn is_hover(&self) -> bool {
+        self.hover
+    }
+
+    // Number of clicks since the last call, for polling in the main loop.
+    pub fn take_clicks(&mut self) -> u32 {
+        std::mem::take(&mut self.clicks)
+    }
+}
+
+// State of `TextInputWidget`.
+pub struct TextInputState {
+    edit: TextEdit,
+    placeholder: String,
+    editing: bool,
+    // open on-screen keyboard
+    keyboard: Option<Keyboard>,
+    changed: bool,
+    submitted: Option<String>,
+}
+
+impl TextInputState {
+    pub fn text(&self) -> &str {
+        self.edit.text()
+    }
+
+    // Replaces the text, no change notification is sent.
+    pub fn set_text(&mut self, text: &str) {
+        self.edit.set_text(text);
+    }
+
+    pub fn placeholder(&self) -> &str {
+        &self.placeholder
+    }
+
+    pub fn set_placeholder(&mut self, placeholder: &str) {
+        self.placeholder = placeholder.to_owned();
+    }
+
+    pub fn selected_text(&self) -> Option<&str> {
+        self.edit.selected_text()
+    }
+
+    pub fn is_editing(&self) -> bool {
+        self.editing
+    }
+
+    pub fn is_keyboard_open(&self) -> bool {
+        self.keyboard.is_some()
+    }
+
+    // Text when edited by the user since the last call.
+    pub fn take_changed(&mut self) -> Option<String> {
+        std::mem::take(&mut self.changed).then(|| self.edit.text().to_owned())
+    }
+
+    // Text submitted since the last call.
+    pub fn take_submitted(&mut self) -> Option<String> {
+        self.submitted.take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cell::Cell,
+        rc::Rc,
+        time::{Duration, Instant},
+    };
+
+    use sdl2::{
+        event::Event,
+        mouse::{MouseButton, MouseWheelDirection},
+        rect::Rect,
+        render::Canvas,
+        video::Window as SdlWin,
+    };
+
+    use super::{
+        dispatch_to_widgets, focus_target_at, focus_targets, key_cells, keyboard_area,
+        pointer_position, record_draw_result, widget_at, Clickable, CommonWidgetProps,
+        EventContext, EventResult, FocusAction, HorizSlider, HorizSliderWidget,
+        ImageCarouselWidget, TextInputWidget, Widget, WidgetParent, TEXTURE_RETRY_INTERVAL,
+    };
+    use crate::{
+        container::ContainerWidget,
+        error::Error,
+        font::FontManager,
+        replay::{Player, Recorder},
+        textedit::Keyboard,
+    };
+
+    // Widget with a fixed area, the canvas is not needed. Counts the events
+    // sent to it and consumes them unless it is passing them on.
+    struct Dummy {
+        widget: CommonWidgetProps,
+        focusable: bool,
+        consume: bool,
+        handled: Rc<Cell<u32>>,
+    }
+
+    impl Widget for Dummy {
+        fn draw(&mut self, _: &mut Canvas<SdlWin>, _: &mut FontManager) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn common(&self) -> &CommonWidgetProps {
+            &self.widget
+        }
+
+        fn common_mut(&mut self) -> &mut CommonWidgetProps {
+            &mut self.widget
+        }
+
+        fn focusable(&self) -> bool {
+            self.focusable
+        }
+
+        fn handle_event(&mut self, _: &Event, _: &EventContext) -> EventResult {
+            self.handled.set(self.handled.get() + 1);
+            if self.consume {
+                EventResult::Consumed
+            } else {
+                EventResult::Propagate
+            }
+        }
+    }
+
+    fn props(area: Rect) -> CommonWidgetProps {
+        CommonWidgetProps {
+            canvas_width: 800,
+            canvas_height: 600,
+            x: 0.0,
+            y: 0.0,
+            w: 0.0,
+            h: 0.0,
+            square: false,
+            anchor: None,
+            margin: (0.0, 0.0),
+            area_override: Some(area),
+            texture_names: Vec::new(),
+            textures: Vec::new(),
+            color: None,
+            draw_error: None,
+            texture_error: None,
+        }
+    }
+
+    fn dummy(area: Rect, focusable: bool) -> Box<dyn Widget> {
+        Box::new(Dummy {
+            widget: props(area),
+            focusable,
+            consume: true,
+            handled: Rc::new(Cell::new(0)),
+        })
+    }
+
+    fn counter(area: Rect) -> (Box<dyn Widget>, Rc<Cell<u32>>) {
+        let handled = Rc::new(Cell::new(0));
+        let widget = Box::new(Dummy {
+            widget: props(area),
+            focusable: false,
+            consume: true,
+            handled: handled.clone(),
+        });
+        (widget, handled)
+    }
+
+    fn passing(area: Rect) -> (Box<dyn Widget>, Rc<Cell<u32>>) {
+        let handled = Rc::new(Cell::new(0));
+        let widget = Box::new(Dummy {
+            widget: props(area),
+            focusable: false,
+            consume: false,
+            handled: handled.clone(),
+        });
+        (widget, handled)
+    }
+
+    fn slider(min_value: f32, max_value: f32, steps: f32) -> HorizSlider {
+        HorizSlider {
+            min_value,
+            max_value,
+            value: min_value,
+            steps,
+            changed: false,
+        }
+    }
+
+    #[test]
+    fn slider_maps_range_and_snaps() {
+        let mut s = slider(0.5, 2.0, 6.0);
+        assert_eq!(0.0, s.fraction());
+        s.set(2.0);
+        assert_eq!(1.0, s.fraction());
+        s.set(1.25);
+        assert_eq!(0.5, s.fraction());
+        // 0.4 of the range is between the 2nd and 3rd step, closer to the 2nd
+        s.set_fraction(0.4);
+        assert!((s.get() - 1.0).abs() < 1e-6);
+        s.inc();
+        assert!((s.get() - 1.25).abs() < 1e-6);
+        s.set(1.9);
+        s.inc();
+        assert_eq!(2.0, s.get());
+        assert_eq!(None, s.take_changed());
+
+        let mut s = slider(-1.0, 1.0, 0.0);
+        s.set_fraction(0.3);
+        assert!((s.get() + 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn failed_draw_is_logged_once_and_retried_after_backoff() {
+        let mut widget = dummy(Rect::new(0, 0, 100, 50), false);
+        let missing = Error::Asset("missing.png".to_owned());
+        assert!(record_draw_result(
+            widget.common_mut(),
+            Err(missing.clone())
+        ));
+        assert!(!record_draw_result(
+            widget.common_mut(),
+            Err(missing.clone())
+        ));
+        assert_eq!(
+            Some(missing.to_string()),
+            widget.common().draw_error.clone()
+        );
+        // a different error is logged again, recovery resets the state
+        assert!(record_draw_result(
+            widget.common_mut(),
+            Err(Error::Asset("broken.png".to_owned()))
+        ));
+        assert!(!record_draw_result(widget.common_mut(), Ok(())));
+        assert_eq!(None, widget.common().draw_error);
+        assert!(record_draw_result(
+            widget.common_mut(),
+            Err(missing.clone())
+        ));
+
+        // the texture load is retried only after the backoff or an asset reload
+        let failed = Instant::now();
+        widget.common_mut().texture_error = Some((missing.clone(), failed));
+        let pending = |w: &dyn Widget, at| w.common().pending_texture_error(at);
+        assert_eq!(Some(missing.clone()), pending(widget.as_ref(), failed));
+        assert_eq!(
+            Some(missing.clone()),
+            pending(widget.as_ref(), failed + Duration::from_secs(1))
+        );
+        assert_eq!(
+            None,
+            pending(widget.as_ref(), failed + TEXTURE_RETRY_INTERVAL)
+        );
+        widget.reload_assets();
+        assert_eq!(None, pending(widget.as_ref(), failed));
+    }
+
+    #[test]
+    fn focus_reaches_container_children() {
+        let button = Rect::new(0, 0, 100, 50);
+        let nested = Rect::new(300, 10, 100, 50);
+        let mut panel = ContainerWidget::panel(props(Rect::new(200, 0, 400, 200)));
+        panel.add_widget(dummy(Rect::new(300, 100, 100, 50), false));
+        panel.add_widget(dummy(nested, true));
+        let mut widgets: Vec<Box<dyn Widget>> = vec![dummy(button, true), Box::new(panel)];
+
+        assert_eq!(
+            vec![(vec![0], button), (vec![1, 1], nested)],
+            focus_targets(&widgets)
+        );
+        let child = widget_at(&mut widgets, &[1, 1]).unwrap();
+        assert_eq!(nested, child.common().area());
+        assert!(widget_at(&mut widgets, &[1, 2]).is_none());
+
+        // a press on the container focuses the child under the pointer
+        assert_eq!(Some(vec![1, 1]), focus_target_at(&widgets, 1, (350, 30)));
+        assert_eq!(None, focus_target_at(&widgets, 1, (350, 120)));
+    }
+
+    #[test]
+    fn replayed_wheel_hits_recorded_widget() {
+        // HiDPI window, the drawable has twice the window size
+        let (window_size, drawable_size) = ((400, 300), (800, 600));
+        let (left, left_wheel) = counter(Rect::new(0, 0, 400, 600));
+        let (right, right_wheel) = counter(Rect::new(400, 0, 400, 600));
+        let mut widgets = vec![left, right];
+
+        // scrolled over the right widget
+        let path = std::env::temp_dir().join(format!("sdl-ui-wheel-{}.rec", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut recorder = Recorder::new(path).unwrap();
+        recorder.record(
+            1,
+            &Event::MouseWheel {
+                timestamp: 0,
+                window_id: 0,
+                which: 0,
+                x: 0,
+                y: 1,
+                direction: MouseWheelDirection::Normal,
+                precise_x: 0.0,
+                precise_y: 1.0,
+                mouse_x: 300,
+                mouse_y: 150,
+            },
+        );
+        drop(recorder);
+
+        // the cursor is over the left widget while replaying, the pointer
+        // position comes from the recorded event only
+        let mut player = Player::new(path).unwrap();
+        let events = player.take(1, None);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(1, events.len());
+        let pointer = pointer_position(&events[0], window_size, drawable_size);
+        assert_eq!(Some((600, 300)), pointer);
+        let result = dispatch_to_widgets(&mut widgets, &mut None, &events[0], pointer);
+        assert_eq!(EventResult::Consumed, result);
+        assert_eq!((0, 1), (left_wheel.get(), right_wheel.get()));
+    }
+
+    fn touch(down: bool, (x, y): (i32, i32)) -> Event {
+        // touch coordinates are normalized to the 800x600 canvas
+        let (x, y) = (x as f32 / 800.0, y as f32 / 600.0);
+        if down {
+            Event::FingerDown {
+                timestamp: 0,
+                touch_id: 0,
+                finger_id: 0,
+                x,
+                y,
+                dx: 0.0,
+                dy: 0.0,
+                pressure: 1.0,
+            }
+        } else {
+            Event::FingerUp {
+                timestamp: 0,
+                touch_id: 0,
+                finger_id: 0,
+                x,
+                y,
+                dx: 0.0,
+                dy: 0.0,
+                pressure: 0.0,
+            }
+        }
+    }
+
+    #[test]
+    fn pointer_grab_and_propagation() {
+        let (below, below_handled) = counter(Rect::new(0, 0, 400, 400));
+        let (above, above_handled) = counter(Rect::new(200, 200, 400, 400));
+        let mut widgets = vec![below, above];
+        let mut grab = None;
+        let mut send = |widgets: &mut Vec<Box<dyn Widget>>, down: bool, p: (i32, i32)| {
+            dispatch_to_widgets(widgets, &mut grab, &touch(down, p), Some(p))
+        };
+
+        // the press in the overlap goes to the topmost widget only
+        assert_eq!(EventResult::Consumed, send(&mut widgets, true, (300, 300)));
+        assert_eq!((0, 1), (below_handled.get(), above_handled.get()));
+        // released outside of both, still delivered to the pressed widget
+        assert_eq!(EventResult::Consumed, send(&mut widgets, false, (700, 100)));
+        assert_eq!((0, 2), (below_handled.get(), above_handled.get()));
+        // the grab ended with the release
+        assert_eq!(
+            EventResult::Propagate,
+            send(&mut widgets, false, (700, 100))
+        );
+        assert_eq!((0, 2), (below_handled.get(), above_handled.get()));
+
+        // the widget on top passes the press on to the one below
+        let (above, above_handled) = passing(Rect::new(200, 200, 400, 400));
+        widgets[1] = above;
+        assert_eq!(EventResult::Consumed, send(&mut widgets, true, (300, 300)));
+        assert_eq!((1, 1), (below_handled.get(), above_handled.get()));
+        assert_eq!(EventResult::Consumed, send(&mut widgets, false, (700, 100)));
+        assert_eq!((2, 1), (below_handled.get(), above_handled.get()));
+        // outside of the widget below nobody wants it
+        assert_eq!(EventResult::Propagate, send(&mut widgets, true, (500, 500)));
+        assert_eq!((2, 2), (below_handled.get(), above_handled.get()));
+    }
+
+    fn wheel(x: i32, y: i32) -> Event {
+        Event::MouseWheel {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            x,
+            y,
+            direction: MouseWheelDirection::Normal,
+            precise_x: x as f32,
+            precise_y: y as f32,
+            mouse_x: 50,
+            mouse_y: 50,
+        }
+    }
+
+    #[test]
+    fn slider_ignores_horizontal_wheel() {
+        let area = Rect::new(0, 0, 200, 20);
+        let mut widget = HorizSliderWidget::new(props(area), 0.0, 1.0, 4.0);
+        let slider = widget.props.clone();
+        let ctx = EventContext {
+            area,
+            pointer: Some((50, 10)),
+        };
+        assert_eq!(
+            EventResult::Propagate,
+            widget.handle_event(&wheel(1, 0), &ctx)
+        );
+        assert_eq!(0.0, slider.read().unwrap().get());
+        assert_eq!(
+            EventResult::Consumed,
+            widget.handle_event(&wheel(0, 1), &ctx)
+        );
+        assert_eq!(0.25, slider.read().unwrap().get());
+    }
+
+    #[test]
+    fn carousel_stops_at_last_image() {
+        let area = Rect::new(0, 0, 300, 100);
+        let mut widget = ImageCarouselWidget::new(props(area), "images", 3);
+        let carousel = widget.props.clone();
+        carousel.write().unwrap().set_image_count(4);
+        let ctx = EventContext {
+            area,
+            pointer: Some((250, 50)),
+        };
+        // clicking the third image scrolls by two
+        widget.handle_event(&touch(true, (250, 50)), &ctx);
+        assert_eq!(2, carousel.read().unwrap().offset);
+        widget.handle_event(&touch(true, (250, 50)), &ctx);
+        assert_eq!(3, carousel.read().unwrap().offset);
+        carousel.write().unwrap().turn_right();
+        assert_eq!(3, carousel.read().unwrap().offset);
+        // images removed from the directory
+        carousel.write().unwrap().set_image_count(2);
+        assert_eq!(1, carousel.read().unwrap().offset);
+    }
+
+    #[test]
+    fn touch_types_on_screen_keyboard() {
+        let input = TextInputWidget::new(props(Rect::new(300, 100, 200, 40)), "SSID")
+            .on_screen_keyboard(true);
+        let state = input.props.clone();
+        // the widget below the keyboard must not get the touches
+        let (below, below_handled) = counter(Rect::new(0, 300, 800, 300));
+        let mut widgets: Vec<Box<dyn Widget>> = vec![below, Box::new(input)];
+        let mut grab = None;
+        let mut tap = |widgets: &mut Vec<Box<dyn Widget>>, p: (i32, i32)| {
+            let down = dispatch_to_widgets(widgets, &mut grab, &touch(true, p), Some(p));
+            dispatch_to_widgets(widgets, &mut grab, &touch(false, p), Some(p));
+            down
+        };
+
+        assert_eq!(EventResult::Consumed, tap(&mut widgets, (400, 120)));
+        assert!(state.read().unwrap().is_keyboard_open());
+
+        let keys = key_cells(&Keyboard::default(), keyboard_area(800, 600));
+        let q = keys[1][0].1.center();
+        assert_eq!(EventResult::Consumed, tap(&mut widgets, (q.x(), q.y())));
+        assert!(state.read().unwrap().is_editing());
+        assert_eq!("q", state.read().unwrap().text());
+
+        let done = keys[4][5].1.center();
+        tap(&mut widgets, (done.x(), done.y()));
+        assert!(!state.read().unwrap().is_editing());
+        assert_eq!(
+            Some("q".to_owned()),
+            state.write().unwrap().take_submitted()
+        );
+        assert_eq!(0, below_handled.get());
+    }
+
+    fn mouse(down: bool, (x, y): (i32, i32)) -> Event {
+        if down {
+            Event::MouseButtonDown {
+                timestamp: 0,
+                window_id: 0,
+                which: 0,
+                mouse_btn: MouseButton::Left,
+                clicks: 1,
+                x,
+                y,
+            }
+        } else {
+            Event::MouseButtonUp {
+                timestamp: 0,
+                window_id: 0,
+                which: 0,
+                mouse_btn: MouseButton::Left,
+                clicks: 1,
+                x,
+                y,
+            }
+        }
+    }
+
+    // Sends the event to the clickable of a 100x50 button at the origin.
+    fn send(c: &mut Clickable, event: &Event, pointer: (i32, i32)) -> EventResult {
+        let ctx = EventContext {
+            area: Rect::new(0, 0, 100, 50),
+            pointer: Some(pointer),
+        };
+        c.handle_event(event, &ctx)
+    }
+
+    #[test]
+    fn click_inside() {
+        let mut c = Clickable::new("TAKE OFF", false);
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        c.on_click = Some(Box::new(move |state| {
+            assert_eq!("TAKE OFF", state.label());
+            counter.set(counter.get() + 1);
+        }));
+        let state = c.props.clone();
+
+        assert_eq!(
+            EventResult::Consumed,
+            send(&mut c, &mouse(true, (10, 10)), (10, 10))
+        );
+        assert!(state.read().unwrap().is_pressed());
+        assert_eq!(
+            EventResult::Consumed,
+            send(&mut c, &mouse(false, (90, 40)), (90, 40))
+        );
+        assert!(!state.read().unwrap().is_pressed());
+        // touch works the same way
+        send(&mut c, &touch(true, (50, 25)), (50, 25));
+        send(&mut c, &touch(false, (50, 25)), (50, 25));
+
+        assert_eq!(2, calls.get());
+        assert_eq!(2, state.write().unwrap().take_clicks());
+        assert_eq!(0, state.write().unwrap().take_clicks());
+        assert!(!state.read().unwrap().is_checked());
+    }
+
+    #[test]
+    fn release_outside_cancels() {
+        let mut c = Clickable::new("LAND", false);
+        let state = c.props.clone();
+        send(&mut c, &mouse(true, (10, 10)), (10, 10));
+        // dragged off the button before releasing
+        assert_eq!(
+            EventResult::Consumed,
+            send(&mut c, &mouse(false, (150, 10)), (150, 10))
+        );
+        assert!(!state.read().unwrap().is_pressed());
+        assert_eq!(0, state.write().unwrap().take_clicks());
+        // release without a press belongs to someone else
+        assert_eq!(
+            EventResult::Propagate,
+            send(&mut c, &mouse(false, (10, 10)), (10, 10))
+        );
+        assert_eq!(0, state.write().unwrap().take_clicks());
+    }
+
+    #[test]
+    fn disabled_ignores_input() {
+        let mut c = Clickable::new("ARM", false);
+        let state = c.props.clone();
+        send(&mut c, &mouse(true, (10, 10)), (10, 10));
+        // disabling while pressed drops the press
+        state.write().unwrap().set_enabled(false);
+        assert!(!state.read().unwrap().is_pressed());
+        assert_eq!(
+            EventResult::Propagate,
+            send(&mut c, &mouse(false, (10, 10)), (10, 10))
+        );
+        assert_eq!(
+            EventResult::Propagate,
+            send(&mut c, &mouse(true, (10, 10)), (10, 10))
+        );
+        assert!(!c.focusable());
+        assert_eq!(
+            EventResult::Propagate,
+            c.focus_action(FocusAction::Activate)
+        );
+        assert_eq!(0, state.write().unwrap().take_clicks());
+
+        state.write().unwrap().set_enabled(true);
+        assert!(c.focusable());
+        assert_eq!(EventResult::Consumed, c.focus_action(FocusAction::Activate));
+        assert_eq!(1, state.write().unwrap().take_clicks());
+    }
+
+    #[test]
+    fn toggle_flips_checked() {
+        let mut c = Clickable::new("LIGHTS", true);
+        let state = c.props.clone();
+        send(&mut c, &mouse(true, (10, 10)), (10, 10));
+        send(&mut c, &mouse(false, (10, 10)), (10, 10));
+        assert!(state.read().unwrap().is_checked());
+        // activated by the pad
+        assert_eq!(EventResult::Consumed, c.focus_action(FocusAction::Activate));
+        assert!(!state.read().unwrap().is_checked());
+        // a cancelled press doesn't flip it
+        send(&mut c, &mouse(true, (10, 10)), (10, 10));
+        send(&mut c, &mouse(false, (150, 10)), (150, 10));
+        assert!(!state.read().unwrap().is_checked());
+        assert_eq!(2, state.write().unwrap().take_clicks());
+        // other actions are left to the focus navigation
+        assert_eq!(EventResult::Propagate, c.focus_action(FocusAction::Back));
+    }
+}
@@ -0,0 +1,47 @@
+use std::fmt;
+
+// Error type shared by the whole crate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    // SDL call failed, e.g. window or texture creation
+    Sdl(String),
+    // image could not be found or decoded
+    Asset(String),
+    Font(String),
+    Gamepad(String),
+    // invalid input mapping
+    Input(String),
+    Layout(String),
+    // broken input recording
+    Replay(String),
+    // malformed video stream or decoder configuration
+    Video(String),
+    Io(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Sdl(e) => write!(f, "sdl: {e}"),
+            Error::Asset(e) => write!(f, "asset: {e}"),
+            Error::Font(e) => write!(f, "font: {e}"),
+            Error::Gamepad(e) => write!(f, "gamepad: {e}"),
+            Error::Input(e) => write!(f, "input: {e}"),
+            Error::Layout(e) => write!(f, "layout: {e}"),
+            Error::Replay(e) => write!(f, "replay: {e}"),
+            Error::Video(e) => write!(f, "video: {e}"),
+            Error::Io(e) => write!(f, "io: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// SDL reports its errors as strings.
+impl From<String> for Error {
+    fn from(e: String) -> Self {
+        Error::Sdl(e)
+    }
+}
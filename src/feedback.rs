@@ -0,0 +1,307 @@
+use std::time::{Duration, Instant};
+
+use sdl2::controller::GameController;
+
+// Part of the pattern: rumble of both motors (0..65535) and optional LED
+// color, held for the duration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pulse {
+    pub low: u16,
+    pub high: u16,
+    pub led: Option<(u8, u8, u8)>,
+    pub duration: Duration,
+}
+
+// Sequence of pulses played on the active gamepad:
+//
+// ```
+// let pattern = Pattern::new()
+//     .pulse(0x4000, 0xc000, 150)
+//     .pause(100)
+//     .pulse(0x4000, 0xc000, 150)
+//     .led(255, 0, 0);
+// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Pattern {
+    pulses: Vec<Pulse>,
+}
+
+impl Pattern {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pulse(mut self, low: u16, high: u16, ms: u64) -> Self {
+        self.pulses.push(Pulse {
+            low,
+            high,
+            led: None,
+            duration: Duration::from_millis(ms),
+        });
+        self
+    }
+
+    pub fn pause(self, ms: u64) -> Self {
+        self.pulse(0, 0, ms)
+    }
+
+    // Colors the LED during all pulses of the pattern.
+    pub fn led(mut self, r: u8, g: u8, b: u8) -> Self {
+        for pulse in self.pulses.iter_mut() {
+            pulse.led = Some((r, g, b));
+        }
+        self
+    }
+
+    // Three short strong pulses, red LED.
+    pub fn low_battery() -> Self {
+        Self::new()
+            .pulse(0xffff, 0xffff, 150)
+            .pause(100)
+            .pulse(0xffff, 0xffff, 150)
+            .pause(100)
+            .pulse(0xffff, 0xffff, 150)
+            .led(255, 0, 0)
+    }
+
+    // Long weak pulse, yellow LED.
+    pub fn weak_signal() -> Self {
+        Self::new().pulse(0x6000, 0x2000, 400).led(255, 200, 0)
+    }
+
+    pub fn pulses(&self) -> &[Pulse] {
+        &self.pulses
+    }
+}
+
+// Output of the feedback, implemented by `GameController`. Methods return
+// false when the device doesn't support the effect.
+pub trait FeedbackDevice {
+    fn rumble(&mut self, low: u16, high: u16, duration: Duration) -> bool;
+
+    fn led(&mut self, r: u8, g: u8, b: u8) -> bool;
+}
+
+impl FeedbackDevice for GameController {
+    fn rumble(&mut self, low: u16, high: u16, duration: Duration) -> bool {
+        self.has_rumble()
+            && self
+                .set_rumble(low, high, duration.as_millis() as u32)
+                .is_ok()
+    }
+
+    fn led(&mut self, r: u8, g: u8, b: u8) -> bool {
+        self.has_led() && self.set_led(r, g, b).is_ok()
+    }
+}
+
+// Fires the pattern when the watched value drops below the threshold. It is
+// armed again once the value rises above the threshold plus the hysteresis,
+// so a noisy value doesn't fire repeatedly.
+struct Trigger {
+    value: Box<dyn Fn() -> f32>,
+    threshold: f32,
+    hysteresis: f32,
+    pattern: Pattern,
+    armed: bool,
+}
+
+struct Playing {
+    pattern: Pattern,
+    pulse: usize,
+    started: Instant,
+}
+
+// Feedback tied to the UI state, e.g. rumble when the battery runs low:
+//
+// ```
+// let battery = BatteryStatusWidget::new(props).on_window(&mut win);
+// let b = battery.clone();
+// win.feedback.below(
+//     move || b.read().unwrap().get(),
+//     desktop::BATTERY_LOW,
+//     Pattern::low_battery(),
+// );
+// ```
+//
+// The window plays the patterns on the active gamepad in `Window::draw`.
+// Without a gamepad, or when it lacks rumble or LED, nothing happens.
+pub struct Feedback {
+    triggers: Vec<Trigger>,
+    queue: Vec<Pattern>,
+    playing: Option<Playing>,
+    // LED color restored after a pattern changed it
+    idle_led: Option<(u8, u8, u8)>,
+    enabled: bool,
+}
+
+impl Default for Feedback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Feedback {
+    pub fn new() -> Self {
+        Self {
+            triggers: Vec::new(),
+            queue: Vec::new(),
+            playing: None,
+            idle_led: None,
+            enabled: true,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.queue.clear();
+            self.playing = None;
+        }
+    }
+
+    pub fn set_idle_led(&mut self, color: Option<(u8, u8, u8)>) {
+        self.idle_led = color;
+    }
+
+    // Plays the pattern when the value drops below the threshold.
+    pub fn below(&mut self, value: impl Fn() -> f32 + 'static, threshold: f32, pattern: Pattern) {
+        self.triggers.push(Trigger {
+            value: Box::new(value),
+            threshold,
+            hysteresis: 0.05,
+            pattern,
+            armed: true,
+        });
+    }
+
+    // Queues the pattern, it starts after the already queued ones.
+    pub fn play(&mut self, pattern: Pattern) {
+        if self.enabled {
+            self.queue.push(pattern);
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing.is_some() || !self.queue.is_empty()
+    }
+
+    pub fn update(&mut self, device: Option<&mut dyn FeedbackDevice>) {
+        self.update_at(device, Instant::now());
+    }
+
+    // Checks the triggers and advances the playing pattern.
+    pub fn update_at(&mut self, mut device: Option<&mut dyn FeedbackDevice>, now: Instant) {
+        for t in self.triggers.iter_mut() {
+            let value = (t.value)();
+            if t.armed && value < t.threshold {
+                t.armed = false;
+                if self.enabled {
+                    self.queue.push(t.pattern.clone());
+                }
+            } else if !t.armed && value >= t.threshold + t.hysteresis {
+                t.armed = true;
+            }
+        }
+        if let Some(ref mut playing) = self.playing {
+            let pulse = &playing.pattern.pulses[playing.pulse];
+            if now.duration_since(playing.started) < pulse.duration {
+                return;
+            }
+            playing.pulse += 1;
+            playing.started = now;
+            if playing.pulse >= playing.pattern.pulses.len() {
+                let changed_led = playing.pattern.pulses.iter().any(|p| p.led.is_some());
+                self.playing = None;
+                if let (true, Some((r, g, b)), Some(d)) =
+                    (changed_led, self.idle_led, device.as_deref_mut())
+                {
+                    d.led(r, g, b);
+                }
+            }
+        }
+        if self.playing.is_none() && !self.queue.is_empty() {
+            let pattern = self.queue.remove(0);
+            if !pattern.pulses.is_empty() {
+                self.playing = Some(Playing {
+                    pattern,
+                    pulse: 0,
+                    started: now,
+                });
+            }
+        }
+        if let (Some(playing), Some(d)) = (self.playing.as_ref(), device) {
+            if playing.started == now {
+                let pulse = &playing.pattern.pulses[playing.pulse];
+                d.rumble(pulse.low, pulse.high, pulse.duration);
+                if let Some((r, g, b)) = pulse.led {
+                    d.led(r, g, b);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        cell::Cell,
+        rc::Rc,
+        time::{Duration, Instant},
+    };
+
+    use super::{Feedback, FeedbackDevice, Pattern};
+
+    #[derive(Default)]
+    struct Recorded {
+        rumbles: Vec<(u16, u16)>,
+        leds: Vec<(u8, u8, u8)>,
+    }
+
+    impl FeedbackDevice for Recorded {
+        fn rumble(&mut self, low: u16, high: u16, _duration: Duration) -> bool {
+            self.rumbles.push((low, high));
+            true
+        }
+
+        fn led(&mut self, r: u8, g: u8, b: u8) -> bool {
+            self.leds.push((r, g, b));
+            true
+        }
+    }
+
+    #[test]
+    fn threshold_crossing_plays_once() {
+        let battery = Rc::new(Cell::new(0.5));
+        let b = battery.clone();
+        let mut feedback = Feedback::new();
+        feedback.set_idle_led(Some((0, 0, 255)));
+        let pattern = Pattern::new().pulse(1, 2, 100).pause(50).led(255, 0, 0);
+        feedback.below(move || b.get(), 0.1, pattern);
+
+        let mut device = Recorded::default();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        feedback.update_at(Some(&mut device), at(0));
+        assert!(!feedback.is_playing());
+
+        battery.set(0.08);
+        feedback.update_at(Some(&mut device), at(10));
+        feedback.update_at(Some(&mut device), at(50));
+        feedback.update_at(Some(&mut device), at(110));
+        feedback.update_at(Some(&mut device), at(160));
+        assert!(!feedback.is_playing());
+        assert_eq!(vec![(1, 2), (0, 0)], device.rumbles);
+        assert_eq!(vec![(255, 0, 0), (255, 0, 0), (0, 0, 255)], device.leds);
+
+        // still low, no repeat until the value recovers
+        battery.set(0.09);
+        feedback.update_at(Some(&mut device), at(200));
+        assert!(!feedback.is_playing());
+        battery.set(0.2);
+        feedback.update_at(Some(&mut device), at(300));
+        battery.set(0.05);
+        feedback.update_at(None, at(400));
+        assert!(feedback.is_playing());
+    }
+}
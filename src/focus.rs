@@ -0,0 +1,108 @@
+use sdl2::{controller::Button, event::Event, keyboard::Keycode, rect::Rect};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+// Input of the focus navigation, delivered to the focused widget by
+// `Widget::focus_action`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FocusAction {
+    // A button, Return or Space
+    Activate,
+    // B button or Backspace
+    Back,
+    // D-pad or arrow keys, moves the focus when the widget doesn't use it
+    Adjust(Direction),
+}
+
+// Maps the D-pad and keyboard to the focus actions.
+pub(crate) fn action_of(event: &Event) -> Option<FocusAction> {
+    match *event {
+        Event::ControllerButtonDown { button, .. } => match button {
+            Button::DPadUp => Some(FocusAction::Adjust(Direction::Up)),
+            Button::DPadDown => Some(FocusAction::Adjust(Direction::Down)),
+            Button::DPadLeft => Some(FocusAction::Adjust(Direction::Left)),
+            Button::DPadRight => Some(FocusAction::Adjust(Direction::Right)),
+            Button::A => Some(FocusAction::Activate),
+            Button::B => Some(FocusAction::Back),
+            _ => None,
+        },
+        Event::KeyDown {
+            keycode: Some(k), ..
+        } => match k {
+            Keycode::UP => Some(FocusAction::Adjust(Direction::Up)),
+            Keycode::DOWN => Some(FocusAction::Adjust(Direction::Down)),
+            Keycode::LEFT => Some(FocusAction::Adjust(Direction::Left)),
+            Keycode::RIGHT => Some(FocusAction::Adjust(Direction::Right)),
+            Keycode::RETURN | Keycode::SPACE => Some(FocusAction::Activate),
+            Keycode::BACKSPACE => Some(FocusAction::Back),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Widget gets the focus first, the top left one.
+pub(crate) fn first(areas: &[(usize, Rect)]) -> Option<usize> {
+    areas
+        .iter()
+        .min_by_key(|(_, a)| (a.center().y(), a.center().x()))
+        .map(|(idx, _)| *idx)
+}
+
+// Closest widget in the direction. The distance across the direction counts
+// twice, so widgets in line are preferred over diagonal ones.
+pub(crate) fn neighbour(areas: &[(usize, Rect)], current: Rect, dir: Direction) -> Option<usize> {
+    let from = current.center();
+    areas
+        .iter()
+        .filter_map(|(idx, a)| {
+            let dx = a.center().x() - from.x();
+            let dy = a.center().y() - from.y();
+            let (along, across) = match dir {
+                Direction::Up => (-dy, dx),
+                Direction::Down => (dy, dx),
+                Direction::Left => (-dx, dy),
+                Direction::Right => (dx, dy),
+            };
+            if along <= 0 {
+                return None;
+            }
+            Some((along + 2 * across.abs(), *idx))
+        })
+        .min()
+        .map(|(_, idx)| idx)
+}
+
+#[cfg(test)]
+mod test {
+    use sdl2::rect::Rect;
+
+    use super::{first, neighbour, Direction};
+
+    #[test]
+    fn spatial_navigation() {
+        // 0 1
+        //   2
+        // 3
+        let areas = [
+            (0, Rect::new(0, 0, 100, 100)),
+            (1, Rect::new(200, 0, 100, 100)),
+            (2, Rect::new(200, 200, 100, 100)),
+            (3, Rect::new(0, 400, 100, 100)),
+        ];
+        assert_eq!(Some(0), first(&areas));
+        assert_eq!(Some(1), neighbour(&areas, areas[0].1, Direction::Right));
+        assert_eq!(Some(2), neighbour(&areas, areas[1].1, Direction::Down));
+        assert_eq!(Some(3), neighbour(&areas, areas[2].1, Direction::Down));
+        // straight up wins over the closer diagonal one
+        assert_eq!(Some(0), neighbour(&areas, areas[3].1, Direction::Up));
+        assert_eq!(Some(2), neighbour(&areas, areas[3].1, Direction::Right));
+        assert_eq!(None, neighbour(&areas, areas[0].1, Direction::Left));
+    }
+}
@@ -0,0 +1,487 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::OnceLock,
+};
+
+use sdl2::{
+    render::{Canvas, Texture},
+    ttf::{Font, FontStyle, Sdl2TtfContext},
+    video::Window,
+};
+
+use crate::{color::RgbColor, error::Error, utils};
+
+// How many rendered strings are kept, HUD labels change every frame so
+// only the recently drawn values are worth keeping.
+const TEXT_CACHE_SIZE: usize = 256;
+
+// SDL_ttf is initialized once and stays for the whole process, every font
+// manager shares it and the loaded fonts borrow from it.
+static TTF: OnceLock<Result<Sdl2TtfContext, String>> = OnceLock::new();
+
+pub const MONOSPACE: &str = "monospace";
+pub const SANS: &str = "sans";
+pub const SYMBOLS: &str = "symbols";
+
+lazy_static! {
+    // Font files tried for the default families, the first usable one wins.
+    static ref DEFAULT_FAMILIES: Vec<(&'static str, Vec<&'static str>)> = vec![
+        (
+            MONOSPACE,
+            vec![
+                "/usr/share/fonts/truetype/ubuntu/UbuntuMono-R.ttf",
+                "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+                "/usr/share/fonts/dejavu-sans-mono-fonts/DejaVuSansMono.ttf",
+                "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+                "/usr/share/fonts/truetype/liberation/LiberationMono-Regular.ttf",
+                "/usr/share/fonts/liberation-mono/LiberationMono-Regular.ttf",
+                "/System/Library/Fonts/Menlo.ttc",
+                "C:\\Windows\\Fonts\\consola.ttf",
+            ],
+        ),
+        (
+            SANS,
+            vec![
+                "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+                "/usr/share/fonts/dejavu-sans-fonts/DejaVuSans.ttf",
+                "/usr/share/fonts/TTF/DejaVuSans.ttf",
+                "/usr/share/fonts/truetype/ubuntu/Ubuntu-R.ttf",
+                "/usr/share/fonts/truetype/noto/NotoSans-Regular.ttf",
+                "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+                "/System/Library/Fonts/Helvetica.ttc",
+                "C:\\Windows\\Fonts\\arial.ttf",
+            ],
+        ),
+        (
+            SYMBOLS,
+            vec![
+                "/usr/share/fonts/truetype/noto/NotoSansSymbols-Regular.ttf",
+                "/usr/share/fonts/truetype/noto/NotoSansSymbols2-Regular.ttf",
+                "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+                "/usr/share/fonts/truetype/freefont/FreeSerif.ttf",
+                "C:\\Windows\\Fonts\\seguisym.ttf",
+            ],
+        ),
+    ];
+}
+
+// Font is identified by the family name, point size and style.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FontKey {
+    family: String,
+    size: u16,
+    style: i32,
+}
+
+impl FontKey {
+    pub fn new(family: &str, size: u16, style: FontStyle) -> Self {
+        Self {
+            family: family.to_owned(),
+            size,
+            style: style.bits(),
+        }
+    }
+
+    pub fn with_style(self, style: FontStyle) -> Self {
+        Self {
+            style: style.bits(),
+            ..self
+        }
+    }
+}
+
+// Single font file loaded in given size and style.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct FileKey {
+    path: String,
+    size: u16,
+    style: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct TextKey {
+    text: String,
+    font: FontKey,
+    color: [u8; 4],
+}
+
+// FontManager keeps loaded fonts and textures of rendered strings, so
+// drawing the same label again is just a texture copy. The textures belong
+// to the renderer of the window the manager was created for.
+//
+// Fonts are looked up by family. Every family is a list of font files, the
+// first one which can be loaded is used. When the font lacks a glyph of the
+// text, the fallback families are tried, e.g. `symbols` for the degree sign.
+// `font_runs` splits the text so only the missing glyphs use the fallback.
+pub struct FontManager {
+    ttf: &'static Sdl2TtfContext,
+    families: HashMap<String, Vec<String>>,
+    fallback: Vec<String>,
+    fonts: HashMap<FileKey, Font<'static, 'static>>,
+    // files which failed to load, not tried again
+    broken: HashSet<String>,
+    // messages of the missing fonts and draw errors, reported once
+    reported: HashSet<String>,
+    texts: Lru<TextKey, Texture>,
+}
+
+impl FontManager {
+    pub fn new() -> Result<Self, Error> {
+        let ttf = TTF
+            .get_or_init(|| sdl2::ttf::init().map_err(|e| e.to_string()))
+            .as_ref()
+            .map_err(|e| Error::Font(format!("can't setup ttf: {e}")))?;
+        let mut families: HashMap<String, Vec<String>> = HashMap::new();
+        for (name, paths) in DEFAULT_FAMILIES.iter() {
+            let paths = paths.iter().map(|p| p.to_string()).collect();
+            families.insert(name.to_string(), paths);
+        }
+        // the font selected by the user takes precedence
+        let user_font = utils::get_env("SDL_UI_FONT", "");
+        if !user_font.is_empty() {
+            families.get_mut(MONOSPACE).unwrap().insert(0, user_font);
+        }
+        Ok(Self {
+            ttf,
+            families,
+            fallback: vec![SANS.to_owned(), SYMBOLS.to_owned()],
+            fonts: HashMap::new(),
+            broken: HashSet::new(),
+            reported: HashSet::new(),
+            texts: Lru::new(TEXT_CACHE_SIZE),
+        })
+    }
+
+    // Registers the font files of the family, replacing the previous ones.
+    pub fn register_family(&mut self, name: &str, paths: &[&str]) {
+        let paths = paths.iter().map(|p| p.to_string()).collect();
+        self.families.insert(name.to_owned(), paths);
+        // the fonts of the family may load now
+        self.reported.clear();
+        self.clear_texts();
+    }
+
+    // Families tried in order when a glyph is missing in the requested family.
+    pub fn set_fallback(&mut self, families: &[&str]) {
+        self.fallback = families.iter().map(|f| f.to_string()).collect();
+        self.clear_texts();
+    }
+
+    // Font files of the family followed by the fallback families.
+    fn chain(&self, family: &str) -> Vec<String> {
+        let mut chain = Vec::new();
+        let families = std::iter::once(family).chain(self.fallback.iter().map(|f| f.as_str()));
+        for name in families {
+            for path in self.families.get(name).into_iter().flatten() {
+                if !chain.contains(path) {
+                    chain.push(path.clone());
+                }
+            }
+        }
+        chain
+    }
+
+    fn load(&mut self, file: &FileKey) -> bool {
+        if self.fonts.contains_key(file) {
+            return true;
+        }
+        if self.broken.contains(&file.path) || !std::path::Path::new(&file.path).exists() {
+            return false;
+        }
+        match self.ttf.load_font(&file.path, file.size) {
+            Ok(mut font) => {
+                font.set_style(FontStyle::from_bits_truncate(file.style));
+                tracing::info!(path = file.path, size = file.size, "font loaded");
+                self.fonts.insert(file.clone(), font);
+                true
+            }
+            Err(e) => {
+                tracing::warn!(path = file.path, "can't load font: {e}");
+                self.broken.insert(file.path.clone());
+                false
+            }
+        }
+    }
+
+    // Picks the first font of the chain having all glyphs of the text, or
+    // the first usable font when none has them all.
+    fn resolve(&mut self, key: &FontKey, text: &str) -> Result<FileKey, Error> {
+        let chain = self.chain(&key.family);
+        let mut first = None;
+        for path in chain.iter() {
+            let file = FileKey {
+                path: path.clone(),
+                size: key.size,
+                style: key.style,
+            };
+            if !self.load(&file) {
+                continue;
+            }
+            let font = &self.fonts[&file];
+            if text
+                .chars()
+                .all(|c| c.is_whitespace() || font.find_glyph(c).is_some())
+            {
+                return Ok(file);
+            }
+            if first.is_none() {
+                first = Some(file);
+            }
+        }
+        match first {
+            Some(file) => Ok(file),
+            None => {
+                let err = Error::Font(format!(
+                    "no usable font for family '{}', tried: {}; install one of them, \
+                     set SDL_UI_FONT or register the family on the window",
+                    key.family,
+                    chain.join(", ")
+                ));
+                self.report(&err);
+                Err(err)
+            }
+        }
+    }
+
+    // Splits the text into pieces drawn with one font each. Every character
+    // takes the first font of the chain having its glyph, the pieces are
+    // then resolved to that font by `font` and `text`.
+    pub fn font_runs(&mut self, key: &FontKey, text: &str) -> Vec<String> {
+        let files: Vec<FileKey> = self
+            .chain(&key.family)
+            .into_iter()
+            .map(|path| FileKey {
+                path,
+                size: key.size,
+                style: key.style,
+            })
+            .filter(|file| self.load(file))
+            .collect();
+        let fonts = &self.fonts;
+        split_runs(text, |c| {
+            files
+                .iter()
+                .position(|file| fonts[file].find_glyph(c).is_some())
+        })
+    }
+
+    // Width of the text in pixels, with the fallback fonts for missing glyphs.
+    pub fn text_width(&mut self, key: &FontKey, text: &str) -> u32 {
+        self.font_runs(key, text)
+            .iter()
+            .map(|run| {
+                self.font(key, run)
+                    .ok()
+                    .and_then(|f| f.size_of(run).ok())
+                    .map(|(w, _)| w)
+                    .unwrap_or(0)
+            })
+            .sum()
+    }
+
+    // Font of the family able to render the text.
+    pub fn font(&mut self, key: &FontKey, text: &str) -> Result<&Font<'static, 'static>, Error> {
+        let file = self.resolve(key, text)?;
+        Ok(&self.fonts[&file])
+    }
+
+    // Texture with the rendered text, created on the first use.
+    pub fn text(
+        &mut self,
+        canvas: &mut Canvas<Window>,
+        text: &str,
+        font: &FontKey,
+        color: &RgbColor,
+    ) -> Result<&Texture, Error> {
+        let key = TextKey {
+            text: text.to_owned(),
+            font: font.clone(),
+            color: color.to_rgba(),
+        };
+        if !self.texts.contains(&key) {
+            let surface = self
+                .font(font, text)?
+                .render(text)
+                .blended(color.to_sdl_rgba())
+                .map_err(|e| Error::Font(format!("render text: {e}")))?;
+            let texture = canvas
+                .texture_creator()
+                .create_texture_from_surface(&surface)
+                .map_err(|e| Error::Sdl(format!("text texture: {e}")))?;
+            if let Some(evicted) = self.texts.insert(key.clone(), texture) {
+                unsafe { evicted.destroy() };
+            }
+        }
+        Ok(self.texts.get(&key).unwrap())
+    }
+
+    // Logs the error the first time only, text is drawn again every frame.
+    // Returns true when the error was logged.
+    pub(crate) fn report(&mut self, e: &Error) -> bool {
+        let msg = e.to_string();
+        if !self.reported.insert(msg.clone()) {
+            return false;
+        }
+        tracing::error!("draw text: {msg}");
+        true
+    }
+
+    // Drops all rendered strings, e.g. after the renderer was reset.
+    pub fn clear_texts(&mut self) {
+        for texture in self.texts.drain() {
+            unsafe { texture.destroy() };
+        }
+    }
+}
+
+// Groups the characters by the index of the font drawing them, whitespace
+// goes with the neighbouring characters. Characters of no font are grouped
+// too, they end up with the first usable font.
+fn split_runs(text: &str, mut font_of: impl FnMut(char) -> Option<usize>) -> Vec<String> {
+    let mut runs: Vec<String> = Vec::new();
+    // font of the last run, none while it has whitespace only
+    let mut current = None;
+    for c in text.chars() {
+        let font = if c.is_whitespace() {
+            None
+        } else {
+            Some(font_of(c))
+        };
+        match (runs.last_mut(), font) {
+            (Some(run), None) => run.push(c),
+            (Some(run), Some(_)) if current.is_none() || current == font => {
+                run.push(c);
+                current = font;
+            }
+            _ => {
+                runs.push(c.to_string());
+                current = font;
+            }
+        }
+    }
+    runs
+}
+
+// Least recently used cache, the oldest entry is evicted when full.
+struct Lru<K, V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<K, (V, u64)>,
+}
+
+impl<K: Eq + Hash + Clone, V> Lru<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(key).map(|(v, used)| {
+            *used = tick;
+            &*v
+        })
+    }
+
+    // Returns the evicted value, if any.
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.tick += 1;
+        let mut evicted = None;
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                evicted = self.entries.remove(&oldest).map(|(v, _)| v);
+            }
+        }
+        if let Some((old, _)) = self.entries.insert(key, (value, self.tick)) {
+            evicted = Some(old);
+        }
+        evicted
+    }
+
+    fn drain(&mut self) -> Vec<V> {
+        self.entries.drain().map(|(_, (v, _))| v).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sdl2::ttf::FontStyle;
+
+    use super::{split_runs, FontKey, FontManager, Lru};
+    use crate::error::Error;
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let mut lru = Lru::new(2);
+        assert_eq!(None, lru.insert("a", 1));
+        assert_eq!(None, lru.insert("b", 2));
+        // touch "a", so "b" is the oldest one
+        assert_eq!(Some(&1), lru.get(&"a"));
+        assert_eq!(Some(2), lru.insert("c", 3));
+        assert!(lru.contains(&"a"));
+        assert!(!lru.contains(&"b"));
+        assert!(lru.contains(&"c"));
+    }
+
+    #[test]
+    fn lru_replace() {
+        let mut lru = Lru::new(2);
+        lru.insert("a", 1);
+        assert_eq!(Some(1), lru.insert("a", 2));
+        assert_eq!(Some(&2), lru.get(&"a"));
+    }
+
+    #[test]
+    fn runs_split_by_glyph_coverage() {
+        // ASCII in the first font, the degree sign in the second, no font
+        // has the snowman
+        let font_of = |c: char| match c {
+            '°' => Some(1),
+            '☃' => None,
+            _ => Some(0),
+        };
+        assert_eq!(
+            vec!["ALT 12", "°", "C ", "☃"],
+            split_runs("ALT 12°C ☃", font_of)
+        );
+        assert_eq!(vec!["  N"], split_runs("  N", font_of));
+        assert!(split_runs("", font_of).is_empty());
+    }
+
+    #[test]
+    fn missing_family_reported_once() {
+        let mut fonts = FontManager::new().unwrap();
+        fonts.register_family(
+            "hud",
+            &["/nonexistent/hud.ttf", "/nonexistent/hud-bold.ttf"],
+        );
+        fonts.set_fallback(&[]);
+        let key = FontKey::new("hud", 16, FontStyle::NORMAL);
+
+        let err = fonts.font(&key, "ALT").err().unwrap();
+        let Error::Font(ref msg) = err else {
+            panic!("unexpected error {err}");
+        };
+        assert!(msg.contains("family 'hud'"));
+        assert!(msg.contains("tried: /nonexistent/hud.ttf, /nonexistent/hud-bold.ttf;"));
+        // logged by the font manager already, not again by the draw path
+        assert!(!fonts.report(&err));
+        assert_eq!(Some(err.clone()), fonts.font(&key, "SPD").err());
+        assert!(!fonts.report(&err));
+        assert_eq!(1, fonts.reported.len());
+    }
+}
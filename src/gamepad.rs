@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+
+use sdl2::{controller::GameController, event::Event, GameControllerSubsystem};
+
+// Id of the gamepad, stays the same when the pad is unplugged and plugged in
+// again, unlike the SDL device index and instance id.
+pub type GamepadId = u32;
+
+pub struct Gamepad {
+    pub id: GamepadId,
+    pub name: String,
+    pub controller: GameController,
+}
+
+// Ids handed out to the pads. Pads are recognized by name, vendor and
+// product, a returning pad gets its previous id back.
+#[derive(Default)]
+struct Slots {
+    // id, pad identity and SDL instance id while connected
+    slots: Vec<(GamepadId, String, Option<u32>)>,
+}
+
+impl Slots {
+    fn connect(&mut self, identity: &str, instance: u32) -> GamepadId {
+        if let Some(slot) = self.slots.iter_mut().find(|s| s.2 == Some(instance)) {
+            return slot.0;
+        }
+        let free = self
+            .slots
+            .iter_mut()
+            .find(|s| s.2.is_none() && s.1 == identity);
+        match free {
+            Some(slot) => {
+                slot.2 = Some(instance);
+                slot.0
+            }
+            None => {
+                let id = self.slots.len() as GamepadId;
+                self.slots.push((id, identity.to_owned(), Some(instance)));
+                id
+            }
+        }
+    }
+
+    fn disconnect(&mut self, instance: u32) -> Option<GamepadId> {
+        let slot = self.slots.iter_mut().find(|s| s.2 == Some(instance))?;
+        slot.2 = None;
+        Some(slot.0)
+    }
+
+    fn id_of(&self, instance: u32) -> Option<GamepadId> {
+        self.slots
+            .iter()
+            .find(|s| s.2 == Some(instance))
+            .map(|s| s.0)
+    }
+}
+
+// Connected gamepads, kept up to date by `Window::dispatch_event` from the
+// controller device events. One of the pads is the active one flying the
+// drone; the first connected pad becomes active, when it is unplugged the
+// next connected one takes over.
+pub struct GamepadRegistry {
+    subsystem: Option<GameControllerSubsystem>,
+    slots: Slots,
+    pads: HashMap<GamepadId, Gamepad>,
+    active: Option<GamepadId>,
+}
+
+impl GamepadRegistry {
+    // Opens all connected pads. Without the subsystem the registry stays
+    // empty, e.g. when the window was created without gamepad support.
+    pub fn new(subsystem: Option<GameControllerSubsystem>) -> Self {
+        let mut registry = Self {
+            subsystem,
+            slots: Slots::default(),
+            pads: HashMap::new(),
+            active: None,
+        };
+        let available = match registry.subsystem.as_ref().map(|s| s.num_joysticks()) {
+            Some(Ok(n)) => n,
+            Some(Err(e)) => {
+                tracing::error!("can't enumerate joysticks: {e}");
+                0
+            }
+            None => 0,
+        };
+        tracing::info!("{available} joysticks available");
+        for index in 0..available {
+            registry.open(index);
+        }
+        registry
+    }
+
+    fn open(&mut self, index: u32) -> Option<GamepadId> {
+        let subsystem = self.subsystem.as_ref()?;
+        if !subsystem.is_game_controller(index) {
+            tracing::warn!("{index} is not a game controller");
+            return None;
+        }
+        let controller = match subsystem.open(index) {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::error!("can't open controller {index}: {e}");
+                return None;
+            }
+        };
+        let instance = controller.instance_id();
+        // pads opened at startup are reported as added again
+        if let Some(id) = self.slots.id_of(instance) {
+            return Some(id);
+        }
+        let name = controller.name();
+        let identity = format!(
+            "{name}:{:?}:{:?}",
+            controller.vendor_id(),
+            controller.product_id()
+        );
+        let id = self.slots.connect(&identity, instance);
+        tracing::info!(
+            id,
+            name,
+            mapping = controller.mapping(),
+            "gamepad connected"
+        );
+        self.pads.insert(
+            id,
+            Gamepad {
+                id,
+                name,
+                controller,
+            },
+        );
+        if self.active.is_none() {
+            self.set_active(id);
+        }
+        Some(id)
+    }
+
+    fn close(&mut self, instance: u32) {
+        let Some(id) = self.slots.disconnect(instance) else {
+            return;
+        };
+        if let Some(pad) = self.pads.remove(&id) {
+            tracing::info!(id, name = pad.name, "gamepad disconnected");
+        }
+        if self.active == Some(id) {
+            self.active = None;
+            if let Some(next) = self.ids().first() {
+                self.set_active(*next);
+            } else {
+                tracing::warn!("no gamepad left");
+            }
+        }
+    }
+
+    // Updates the registry on controller device events, returns true when
+    // the set of connected pads changed.
+    pub fn handle_event(&mut self, event: &Event) -> bool {
+        let count = self.pads.len();
+        match *event {
+            // `which` is the device index here...
+            Event::ControllerDeviceAdded { which, .. } => {
+                self.open(which);
+            }
+            // ...and the instance id in the other events
+            Event::ControllerDeviceRemoved { which, .. } => self.close(which),
+            Event::ControllerDeviceRemapped { which, .. } => {
+                if let Some(pad) = self.slots.id_of(which).and_then(|id| self.pads.get(&id)) {
+                    tracing::info!(
+                        id = pad.id,
+                        mapping = pad.controller.mapping(),
+                        "gamepad remapped"
+                    );
+                }
+            }
+            _ => {}
+        }
+        count != self.pads.len()
+    }
+
+    // Ids of the connected pads in ascending order.
+    pub fn ids(&self) -> Vec<GamepadId> {
+        let mut ids: Vec<GamepadId> = self.pads.keys().copied().collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn get(&self, id: GamepadId) -> Option<&Gamepad> {
+        self.pads.get(&id)
+    }
+
+    pub fn active(&self) -> Option<&Gamepad> {
+        self.active.and_then(|id| self.pads.get(&id))
+    }
+
+    pub fn active_mut(&mut self) -> Option<&mut Gamepad> {
+        self.active.and_then(|id| self.pads.get_mut(&id))
+    }
+
+    pub fn active_id(&self) -> Option<GamepadId> {
+        self.active
+    }
+
+    // SDL instance id of the active pad, the `which` of its events.
+    pub fn active_instance(&self) -> Option<u32> {
+        self.active().map(|pad| pad.controller.instance_id())
+    }
+
+    // Hands the control over to the pad, returns false when it is not connected.
+    pub fn set_active(&mut self, id: GamepadId) -> bool {
+        if !self.pads.contains_key(&id) {
+            return false;
+        }
+        if self.active != Some(id) {
+            tracing::info!(id, "active gamepad");
+        }
+        self.active = Some(id);
+        true
+    }
+
+    // Pad which sent the controller input event.
+    pub fn gamepad_of(&self, event: &Event) -> Option<GamepadId> {
+        let instance = match *event {
+            Event::ControllerAxisMotion { which, .. }
+            | Event::ControllerButtonDown { which, .. }
+            | Event::ControllerButtonUp { which, .. }
+            | Event::ControllerTouchpadDown { which, .. }
+            | Event::ControllerTouchpadMotion { which, .. }
+            | Event::ControllerTouchpadUp { which, .. } => which,
+            _ => return None,
+        };
+        self.slots.id_of(instance)
+    }
+
+    // True for controller input of the active pad, input of the other pads
+    // should be ignored by the pilot controls.
+    pub fn is_active(&self, event: &Event) -> bool {
+        self.active.is_some() && self.gamepad_of(event) == self.active
+    }
+
+    // Takes the active controller out of the registry.
+    pub fn into_active(mut self) -> Option<GameController> {
+        let id = self.active?;
+        self.pads.remove(&id).map(|pad| pad.controller)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Slots;
+
+    #[test]
+    fn replugged_pad_keeps_id() {
+        let mut slots = Slots::default();
+        assert_eq!(0, slots.connect("pad:1", 10));
+        assert_eq!(1, slots.connect("pad:2", 11));
+        assert_eq!(Some(0), slots.disconnect(10));
+        assert_eq!(None, slots.id_of(10));
+        // SDL assigns a new instance id on reconnect
+        assert_eq!(0, slots.connect("pad:1", 12));
+        assert_eq!(Some(0), slots.id_of(12));
+        // identical pad gets a new id while the first one is connected
+        assert_eq!(2, slots.connect("pad:1", 13));
+        assert_eq!(1, slots.connect("pad:2", 11));
+    }
+}
@@ -0,0 +1,320 @@
+use crate::error::Error;
+
+// Reads the H.264 syntax elements bit by bit, most significant bit first.
+// The data is the RBSP, with the emulation prevention bytes removed.
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(crate) fn flag(&mut self) -> Result<bool, Error> {
+        let byte = self
+            .data
+            .get(self.pos / 8)
+            .ok_or_else(|| Error::Video("bitstream ended".to_owned()))?;
+        let bit = byte >> (7 - self.pos % 8) & 1;
+        self.pos += 1;
+        Ok(bit == 1)
+    }
+
+    // Unsigned number of `n` bits, at most 32.
+    pub(crate) fn bits(&mut self, n: u32) -> Result<u32, Error> {
+        let mut value = 0u64;
+        for _ in 0..n {
+            value = value << 1 | self.flag()? as u64;
+        }
+        Ok(value as u32)
+    }
+
+    // Unsigned exp-Golomb code: leading zeros, one, as many bits as zeros.
+    pub(crate) fn ue(&mut self) -> Result<u32, Error> {
+        let mut zeros = 0;
+        while !self.flag()? {
+            zeros += 1;
+            if zeros > 31 {
+                return Err(Error::Video("invalid exp-Golomb code".to_owned()));
+            }
+        }
+        Ok(((1u64 << zeros) - 1 + self.bits(zeros)? as u64) as u32)
+    }
+
+    // Signed exp-Golomb code, 1, 2, 3, 4... map to 1, -1, 2, -2...
+    pub(crate) fn se(&mut self) -> Result<i32, Error> {
+        let k = self.ue()? as i64;
+        Ok(if k % 2 == 1 { (k + 1) / 2 } else { -k / 2 } as i32)
+    }
+}
+
+// Sequence parameter set, the parts needed to know the picture size and to
+// follow the frame numbers of the slices.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sps {
+    pub profile_idc: u8,
+    // constraint_set0..5 flags in the high bits
+    pub constraint_flags: u8,
+    // level times 10, e.g. 31 for 3.1
+    pub level_idc: u8,
+    pub id: u32,
+    pub chroma_format_idc: u32,
+    pub separate_colour_plane: bool,
+    // frame_num of the slices has this many bits
+    pub log2_max_frame_num: u32,
+    pub gaps_in_frame_num_allowed: bool,
+    pub frame_mbs_only: bool,
+    // picture size after cropping
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Sps {
+    // Parses the RBSP of the unit, see `Nal::rbsp`.
+    pub fn parse(rbsp: &[u8]) -> Result<Self, Error> {
+        let mut r = BitReader::new(rbsp);
+        let profile_idc = r.bits(8)? as u8;
+        let constraint_flags = r.bits(8)? as u8;
+        let level_idc = r.bits(8)? as u8;
+        let id = r.ue()?;
+
+        let mut chroma_format_idc = 1;
+        let mut separate_colour_plane = false;
+        if matches!(
+            profile_idc,
+            100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+        ) {
+            chroma_format_idc = r.ue()?;
+            if chroma_format_idc == 3 {
+                separate_colour_plane = r.flag()?;
+            }
+            let _bit_depth_luma = r.ue()?;
+            let _bit_depth_chroma = r.ue()?;
+            let _transform_bypass = r.flag()?;
+            if r.flag()? {
+                let lists = if chroma_format_idc == 3 { 12 } else { 8 };
+                for i in 0..lists {
+                    if r.flag()? {
+                        skip_scaling_list(&mut r, if i < 6 { 16 } else { 64 })?;
+                    }
+                }
+            }
+        }
+
+        let log2_max_frame_num = r.ue()? + 4;
+        if log2_max_frame_num > 16 {
+            return Err(Error::Video(format!(
+                "invalid frame_num size {log2_max_frame_num}"
+            )));
+        }
+        match r.ue()? {
+            0 => {
+                let _log2_max_pic_order_cnt_lsb = r.ue()?;
+            }
+            1 => {
+                let _delta_pic_order_always_zero = r.flag()?;
+                let _offset_for_non_ref_pic = r.se()?;
+                let _offset_for_top_to_bottom_field = r.se()?;
+                for _ in 0..r.ue()? {
+                    let _offset_for_ref_frame = r.se()?;
+                }
+            }
+            _ => {}
+        }
+        let _max_num_ref_frames = r.ue()?;
+        let gaps_in_frame_num_allowed = r.flag()?;
+        let width_in_mbs = r.ue()? as u64 + 1;
+        let height_in_map_units = r.ue()? as u64 + 1;
+        let frame_mbs_only = r.flag()?;
+        if !frame_mbs_only {
+            let _mb_adaptive_frame_field = r.flag()?;
+        }
+        let _direct_8x8_inference = r.flag()?;
+
+        // fields have half the height, frames are made of two
+        let field_factor = if frame_mbs_only { 1 } else { 2 };
+        let mut width = width_in_mbs * 16;
+        let mut height = height_in_map_units * 16 * field_factor;
+        if r.flag()? {
+            // cropping is in chroma samples
+            let (crop_x, crop_y) = match (separate_colour_plane, chroma_format_idc) {
+                (true, _) | (_, 0) => (1, field_factor),
+                (_, 1) => (2, 2 * field_factor),
+                (_, 2) => (2, field_factor),
+                _ => (1, field_factor),
+            };
+            let left = r.ue()? as u64;
+            let right = r.ue()? as u64;
+            let top = r.ue()? as u64;
+            let bottom = r.ue()? as u64;
+            width = width.saturating_sub(crop_x * (left + right));
+            height = height.saturating_sub(crop_y * (top + bottom));
+        }
+        if width == 0 || height == 0 || width > 16384 || height > 16384 {
+            return Err(Error::Video(format!(
+                "invalid picture size {width}x{height}"
+            )));
+        }
+
+        Ok(Self {
+            profile_idc,
+            constraint_flags,
+            level_idc,
+            id,
+            chroma_format_idc,
+            separate_colour_plane,
+            log2_max_frame_num,
+            gaps_in_frame_num_allowed,
+            frame_mbs_only,
+            width: width as u32,
+            height: height as u32,
+        })
+    }
+
+    pub fn profile_name(&self) -> &'static str {
+        match self.profile_idc {
+            // constraint_set1 limits the stream to the baseline and main subset
+            66 if self.constraint_flags & 0x40 != 0 => "Constrained Baseline",
+            66 => "Baseline",
+            77 => "Main",
+            88 => "Extended",
+            100 => "High",
+            110 => "High 10",
+            122 => "High 4:2:2",
+            244 => "High 4:4:4",
+            44 => "CAVLC 4:4:4 Intra",
+            _ => "unknown",
+        }
+    }
+}
+
+// Start of the slice header, up to the frame number.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SliceHeader {
+    // 0 for the first slice of a picture
+    pub first_mb: u32,
+    pub slice_type: u32,
+    pub pps_id: u32,
+    // counts the reference pictures since the last IDR
+    pub frame_num: u32,
+}
+
+impl SliceHeader {
+    // Parses the RBSP of a slice unit, the first few bytes are enough.
+    pub fn parse(rbsp: &[u8], sps: &Sps) -> Result<Self, Error> {
+        let mut r = BitReader::new(rbsp);
+        let first_mb = r.ue()?;
+        let slice_type = r.ue()?;
+        let pps_id = r.ue()?;
+        if sps.separate_colour_plane {
+            let _colour_plane_id = r.bits(2)?;
+        }
+        Ok(Self {
+            first_mb,
+            slice_type,
+            pps_id,
+            frame_num: r.bits(sps.log2_max_frame_num)?,
+        })
+    }
+}
+
+// The scaling matrices don't matter here, only their length.
+fn skip_scaling_list(r: &mut BitReader, size: usize) -> Result<(), Error> {
+    let mut last = 8;
+    let mut next = 8;
+    for _ in 0..size {
+        if next != 0 {
+            next = (last + r.se()? + 256) % 256;
+        }
+        if next != 0 {
+            last = next;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BitReader, SliceHeader, Sps};
+
+    // Writes exp-Golomb codes to build parameter sets.
+    #[derive(Default)]
+    struct BitWriter {
+        bits: Vec<bool>,
+    }
+
+    impl BitWriter {
+        fn bits(&mut self, n: u32, value: u32) -> &mut Self {
+            for i in (0..n).rev() {
+                self.bits.push(value >> i & 1 == 1);
+            }
+            self
+        }
+
+        fn ue(&mut self, value: u32) -> &mut Self {
+            let code = value + 1;
+            let len = 32 - code.leading_zeros();
+            self.bits(len - 1, 0).bits(len, code)
+        }
+
+        fn bytes(&self) -> Vec<u8> {
+            self.bits
+                .chunks(8)
+                .map(|c| (0..8).fold(0, |b, i| b << 1 | *c.get(i).unwrap_or(&false) as u8))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn exp_golomb() {
+        // 1, 010, 011, 00100, 00101
+        let data = [0b1010_0110, 0b0100_0010, 0b1000_0000];
+        let mut r = BitReader::new(&data);
+        assert_eq!(Ok(0), r.ue());
+        assert_eq!(Ok(1), r.ue());
+        assert_eq!(Ok(2), r.ue());
+        assert_eq!(Ok(2), r.se());
+        assert_eq!(Ok(-2), r.se());
+        assert!(r.ue().is_err());
+    }
+
+    #[test]
+    fn parse_main_profile_sps() {
+        // SPS of the test stream in `video`
+        let sps = Sps::parse(&[77, 64, 40, 149, 160, 60, 5, 185]).unwrap();
+        assert_eq!((960, 720), (sps.width, sps.height));
+        assert_eq!((77, 40), (sps.profile_idc, sps.level_idc));
+        assert_eq!("Main", sps.profile_name());
+        assert!(sps.frame_mbs_only);
+    }
+
+    #[test]
+    fn parse_high_profile_sps_with_cropping() {
+        // 1920x1088 coded, the bottom 8 lines are cropped
+        let mut w = BitWriter::default();
+        w.bits(8, 100).bits(8, 0).bits(8, 40).ue(0);
+        // 4:2:0, 8 bit, no bypass, scaling list 0 present with a single delta
+        w.ue(1).ue(0).ue(0).bits(1, 0).bits(1, 1);
+        w.bits(1, 1).ue(16).bits(7, 0);
+        // frame_num in 8 bits, pic order count type 2
+        w.ue(4).ue(2);
+        w.ue(4).bits(1, 0).ue(119).ue(67).bits(1, 1).bits(1, 1);
+        w.bits(1, 1).ue(0).ue(0).ue(0).ue(4);
+        w.bits(1, 0).bits(1, 1);
+        let sps = Sps::parse(&w.bytes()).unwrap();
+        assert_eq!((1920, 1080), (sps.width, sps.height));
+        assert_eq!(8, sps.log2_max_frame_num);
+        assert_eq!("High", sps.profile_name());
+
+        // P slice, frame_num 7 in 8 bits
+        let mut w = BitWriter::default();
+        w.ue(0).ue(5).ue(0).bits(8, 7).bits(1, 1);
+        let slice = SliceHeader::parse(&w.bytes(), &sps).unwrap();
+        assert_eq!(
+            (0, 5, 7),
+            (slice.first_mb, slice.slice_type, slice.frame_num)
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::utils;
+
+// How often the watched files are checked for modifications.
+const POLL_PERIOD: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Changes {
+    pub(crate) layout: bool,
+    pub(crate) assets: bool,
+}
+
+// FileWatcher polls modification times of the layout file and all files
+// in the asset directory, the same way `TextureCache` tracks `last_modified`.
+pub(crate) struct FileWatcher {
+    pub(crate) layout_file: Option<String>,
+    asset_dir: Option<String>,
+    layout_modified: u128,
+    assets_modified: HashMap<String, u128>,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub(crate) fn new(layout_file: Option<&str>, asset_dir: Option<&str>) -> Self {
+        let mut watcher = Self {
+            layout_file: layout_file.map(|f| f.to_owned()),
+            asset_dir: asset_dir.map(|d| d.to_owned()),
+            layout_modified: 0,
+            assets_modified: HashMap::new(),
+            last_poll: Instant::now(),
+        };
+        // remember the current state, only later modifications are reported
+        watcher.scan();
+        watcher
+    }
+
+    pub(crate) fn poll(&mut self) -> Changes {
+        self.poll_at(Instant::now())
+    }
+
+    // Same as `poll`, with the current time given by the caller.
+    pub(crate) fn poll_at(&mut self, now: Instant) -> Changes {
+        if now.saturating_duration_since(self.last_poll) < POLL_PERIOD {
+            return Changes::default();
+        }
+        self.last_poll = now;
+        self.scan()
+    }
+
+    fn scan(&mut self) -> Changes {
+        let mut changes = Changes::default();
+        if let Some(ref layout_file) = self.layout_file {
+            let modified = utils::file_mtime(layout_file);
+            changes.layout = modified != self.layout_modified;
+            self.layout_modified = modified;
+        }
+        if let Some(ref asset_dir) = self.asset_dir {
+            let mut current = HashMap::new();
+            for file in utils::DirectoryReader::new(asset_dir).list() {
+                let modified = utils::file_mtime(&file);
+                current.insert(file, modified);
+            }
+            changes.assets = current != self.assets_modified;
+            self.assets_modified = current;
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        time::{Duration, Instant, SystemTime},
+    };
+
+    use super::{Changes, FileWatcher, POLL_PERIOD};
+
+    #[test]
+    fn detect_layout_change() {
+        let dir = std::env::temp_dir().join(format!("sdl-ui-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let layout = dir.join("layout.toml");
+        fs::write(&layout, "[[widget]]").unwrap();
+        let written = SystemTime::now() - Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&layout)
+            .unwrap()
+            .set_modified(written)
+            .unwrap();
+
+        let layout = layout.to_str().unwrap();
+        let start = Instant::now();
+        let mut watcher = FileWatcher::new(Some(layout), dir.to_str());
+        assert_eq!(Changes::default(), watcher.poll_at(start + POLL_PERIOD));
+
+        // explicit modification time, file systems may store whole seconds
+        let file = fs::File::options().write(true).open(layout).unwrap();
+        file.set_modified(written + Duration::from_secs(10))
+            .unwrap();
+        drop(file);
+        // not checked again before the poll period passed
+        assert_eq!(Changes::default(), watcher.poll_at(start + POLL_PERIOD));
+        assert_eq!(
+            Changes {
+                layout: true,
+                assets: true
+            },
+            watcher.poll_at(start + POLL_PERIOD * 2)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn asset_from_the_future() {
+        let dir = std::env::temp_dir().join(format!("sdl-ui-future-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let asset = fs::File::create(dir.join("logo.png")).unwrap();
+        let tomorrow = SystemTime::now() + Duration::from_secs(24 * 3600);
+        asset.set_modified(tomorrow).unwrap();
+
+        let mut watcher = FileWatcher::new(None, dir.to_str());
+        assert_eq!(
+            Changes::default(),
+            watcher.poll_at(Instant::now() + POLL_PERIOD)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
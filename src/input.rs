@@ -0,0 +1,725 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    time::Duration,
+};
+
+use sdl2::{
+    controller::{Axis, Button},
+    event::Event,
+    keyboard::Keycode,
+    mouse::MouseButton,
+    rect::Rect,
+};
+use serde::Deserialize;
+
+use crate::error::Error;
+
+// Maps controller axes, buttons and keyboard keys to named axes and actions,
+// e.g.:
+//
+// ```toml
+// [[axis]]
+// name = "forward"
+// axis = "lefty"
+// dead_zone = 0.1
+// expo = 0.3
+// invert = true
+// key_positive = "W"
+// key_negative = "S"
+//
+// [[action]]
+// name = "take_picture"
+// buttons = ["a"]
+// keys = ["Space"]
+// ```
+//
+// Axis and button names are the ones of the SDL controller mappings
+// (`leftx`, `righttrigger`, `a`, `leftshoulder`, ...), keys use the SDL key
+// names (`W`, `Up`, `Space`, ...).
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct InputConfig {
+    #[serde(rename = "axis", default)]
+    pub axes: Vec<AxisConfig>,
+    #[serde(rename = "action", default)]
+    pub actions: Vec<ActionConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct AxisConfig {
+    pub name: String,
+    // controller axis
+    pub axis: Option<String>,
+    // stick values below the dead zone are zero, the rest is rescaled to 0..1
+    #[serde(default)]
+    pub dead_zone: f32,
+    // 0 is linear, 1 is cubic, finer control around the center
+    #[serde(default)]
+    pub expo: f32,
+    #[serde(default)]
+    pub invert: bool,
+    #[serde(default = "default_sensitivity")]
+    pub sensitivity: f32,
+    // keys deflecting the axis fully
+    pub key_positive: Option<String>,
+    pub key_negative: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ActionConfig {
+    pub name: String,
+    #[serde(default)]
+    pub buttons: Vec<String>,
+    #[serde(default)]
+    pub keys: Vec<String>,
+}
+
+fn default_sensitivity() -> f32 {
+    1.0
+}
+
+impl AxisConfig {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            axis: None,
+            dead_zone: 0.0,
+            expo: 0.0,
+            invert: false,
+            sensitivity: default_sensitivity(),
+            key_positive: None,
+            key_negative: None,
+        }
+    }
+
+    // Applies inversion, dead zone, expo curve and sensitivity to the
+    // normalized stick value, the result is clamped to -1..1.
+    pub fn apply(&self, value: f32) -> f32 {
+        let mut v = value.clamp(-1.0, 1.0);
+        if self.invert {
+            v = -v;
+        }
+        let dead_zone = self.dead_zone.clamp(0.0, 0.99);
+        if v.abs() <= dead_zone {
+            return 0.0;
+        }
+        v = v.signum() * (v.abs() - dead_zone) / (1.0 - dead_zone);
+        let expo = self.expo.clamp(0.0, 1.0);
+        v = (1.0 - expo) * v + expo * v * v * v;
+        (v * self.sensitivity).clamp(-1.0, 1.0)
+    }
+}
+
+impl InputConfig {
+    pub fn parse(content: &str) -> Result<Self, Error> {
+        toml::from_str(content).map_err(|e| Error::Input(e.to_string()))
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        let content = fs::read_to_string(path).map_err(|e| Error::Io(format!("{path}: {e}")))?;
+        Self::parse(&content)
+    }
+}
+
+// Controller axes range from -32768 to 32767, triggers from 0 to 32767.
+pub fn normalize_axis(value: i16) -> f32 {
+    (value as f32 / 32767.0).clamp(-1.0, 1.0)
+}
+
+fn key(name: &str) -> Result<Keycode, Error> {
+    Keycode::from_name(name).ok_or_else(|| Error::Input(format!("unknown key '{name}'")))
+}
+
+struct AxisBinding {
+    config: AxisConfig,
+    axis: Option<Axis>,
+    key_positive: Option<Keycode>,
+    key_negative: Option<Keycode>,
+    raw: f32,
+    positive_down: bool,
+    negative_down: bool,
+}
+
+impl AxisBinding {
+    fn value(&self) -> f32 {
+        let keys = self.positive_down as i32 - self.negative_down as i32;
+        if keys != 0 {
+            return (keys as f32 * self.config.sensitivity).clamp(-1.0, 1.0);
+        }
+        self.config.apply(self.raw)
+    }
+}
+
+struct ActionBinding {
+    name: String,
+    buttons: Vec<Button>,
+    keys: Vec<Keycode>,
+}
+
+// Tracks the state of the mapped axes and actions, fed with the events from
+// `Window::poll_events`. Only the active gamepad is mapped:
+//
+// ```
+// let mut input = InputMapper::from_file("input.toml")?;
+// for event in win.poll_events() {
+//     input.handle_event(&event, win.gamepads.active_instance());
+// }
+// let forward = input.axis("forward");
+// if input.take_pressed().contains(&"take_picture".to_owned()) { ... }
+// ```
+pub struct InputMapper {
+    axes: Vec<AxisBinding>,
+    actions: Vec<ActionBinding>,
+    down: HashSet<String>,
+    // actions pressed since the last `take_pressed`
+    pressed: Vec<String>,
+    // buttons and keys holding the action down
+    holders: HashMap<String, usize>,
+}
+
+impl InputMapper {
+    // Fails on unknown axis, button or key names.
+    pub fn new(config: &InputConfig) -> Result<Self, Error> {
+        let mut axes = Vec::new();
+        for a in config.axes.iter() {
+            let axis = match a.axis {
+                Some(ref name) => Some(
+                    Axis::from_string(name)
+                        .ok_or_else(|| Error::Input(format!("unknown axis '{name}'")))?,
+                ),
+                None => None,
+            };
+            axes.push(AxisBinding {
+                config: a.clone(),
+                axis,
+                key_positive: a.key_positive.as_deref().map(key).transpose()?,
+                key_negative: a.key_negative.as_deref().map(key).transpose()?,
+                raw: 0.0,
+                positive_down: false,
+                negative_down: false,
+            });
+        }
+        let mut actions = Vec::new();
+        for a in config.actions.iter() {
+            let buttons = a
+                .buttons
+                .iter()
+                .map(|b| {
+                    Button::from_string(b)
+                        .ok_or_else(|| Error::Input(format!("unknown button '{b}'")))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let keys = a
+                .keys
+                .iter()
+                .map(|k| key(k))
+                .collect::<Result<Vec<_>, _>>()?;
+            actions.push(ActionBinding {
+                name: a.name.clone(),
+                buttons,
+                keys,
+            });
+        }
+        Ok(Self {
+            axes,
+            actions,
+            down: HashSet::new(),
+            pressed: Vec::new(),
+            holders: HashMap::new(),
+        })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        Self::new(&InputConfig::from_file(path)?)
+    }
+
+    fn action(&mut self, idx: usize, down: bool) {
+        let name = self.actions[idx].name.clone();
+        let holders = self.holders.entry(name.clone()).or_insert(0);
+        if down {
+            *holders += 1;
+            if self.down.insert(name.clone()) {
+                self.pressed.push(name);
+            }
+        } else {
+            *holders = holders.saturating_sub(1);
+            if *holders == 0 {
+                self.down.remove(&name);
+            }
+        }
+    }
+
+    fn key(&mut self, keycode: Keycode, down: bool) -> bool {
+        let mut mapped = false;
+        for a in self.axes.iter_mut() {
+            if a.key_positive == Some(keycode) {
+                a.positive_down = down;
+                mapped = true;
+            }
+            if a.key_negative == Some(keycode) {
+                a.negative_down = down;
+                mapped = true;
+            }
+        }
+        for idx in 0..self.actions.len() {
+            if self.actions[idx].keys.contains(&keycode) {
+                self.action(idx, down);
+                mapped = true;
+            }
+        }
+        mapped
+    }
+
+    fn button(&mut self, button: Button, down: bool) -> bool {
+        let mut mapped = false;
+        for idx in 0..self.actions.len() {
+            if self.actions[idx].buttons.contains(&button) {
+                self.action(idx, down);
+                mapped = true;
+            }
+        }
+        mapped
+    }
+
+    // Updates the state, returns true when the event is mapped to an axis
+    // or action. Controller events of other pads than `active_pad`, the SDL
+    // instance id of the active one, are ignored.
+    pub fn handle_event(&mut self, event: &Event, active_pad: Option<u32>) -> bool {
+        match *event {
+            Event::ControllerAxisMotion { which, .. }
+            | Event::ControllerButtonDown { which, .. }
+            | Event::ControllerButtonUp { which, .. }
+                if active_pad != Some(which) =>
+            {
+                false
+            }
+            Event::ControllerAxisMotion { axis, value, .. } => {
+                let mut mapped = false;
+                for a in self.axes.iter_mut().filter(|a| a.axis == Some(axis)) {
+                    a.raw = normalize_axis(value);
+                    mapped = true;
+                }
+                mapped
+            }
+            Event::ControllerButtonDown { button, .. } => self.button(button, true),
+            Event::ControllerButtonUp { button, .. } => self.button(button, false),
+            Event::KeyDown {
+                keycode: Some(keycode),
+                repeat: false,
+                ..
+            } => self.key(keycode, true),
+            Event::KeyUp {
+                keycode: Some(keycode),
+                ..
+            } => self.key(keycode, false),
+            _ => false,
+        }
+    }
+
+    // Current value of the axis in -1..1, 0 for unknown axes.
+    pub fn axis(&self, name: &str) -> f32 {
+        self.axes
+            .iter()
+            .find(|a| a.config.name == name)
+            .map(|a| a.value())
+            .unwrap_or(0.0)
+    }
+
+    pub fn set_sensitivity(&mut self, name: &str, sensitivity: f32) {
+        for a in self.axes.iter_mut().filter(|a| a.config.name == name) {
+            a.config.sensitivity = sensitivity;
+        }
+    }
+
+    // True while any button or key of the action is held.
+    pub fn is_down(&self, action: &str) -> bool {
+        self.down.contains(action)
+    }
+
+    // Actions pressed since the previous call, in order.
+    pub fn take_pressed(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.pressed)
+    }
+
+    // Releases everything, e.g. when the window loses focus.
+    pub fn reset(&mut self) {
+        for a in self.axes.iter_mut() {
+            a.raw = 0.0;
+            a.positive_down = false;
+            a.negative_down = false;
+        }
+        self.down.clear();
+        self.holders.clear();
+        self.pressed.clear();
+    }
+}
+
+// Moves the value towards the target by the step, without overshooting.
+fn approach(value: f32, target: f32, step: f32) -> f32 {
+    if (target - value).abs() <= step {
+        target
+    } else {
+        value + step * (target - value).signum()
+    }
+}
+
+// Stick driven by the keyboard or mouse, for flying without a gamepad. The
+// value has the same range as the controller sticks, so it can be passed to
+// `GamepadStick::set_stick` as is:
+//
+// ```
+// let mut stick = VirtualStick::wasd().ramp(0.3).centering(0.15);
+// // in the main loop
+// for event in win.poll_events() {
+//     stick.handle_event(&event);
+// }
+// left_stick.write().unwrap().set_stick(stick.update(frame_time));
+// ```
+//
+// Held keys deflect the stick gradually, released keys let it return to the
+// center. Dragging the mouse inside the mouse area moves the stick directly,
+// so do the gamepad axes set by `gamepad_axes` once deflected.
+pub struct VirtualStick {
+    // left, right, up, down
+    keys: [Keycode; 4],
+    held: [bool; 4],
+    // seconds to full deflection and back to the center
+    ramp: f32,
+    centering: f32,
+    mouse_area: Option<Rect>,
+    // drag start and the offset for full deflection
+    drag: Option<(i32, i32)>,
+    drag_radius: f32,
+    // horizontal and vertical controller axes overriding the keys
+    pad_axes: Option<(Axis, Axis)>,
+    pad_value: (f32, f32),
+    value: (f32, f32),
+}
+
+// Gamepad deflection below which the keys drive the stick.
+const PAD_OVERRIDE_DEAD_ZONE: f32 = 0.1;
+
+impl VirtualStick {
+    pub fn new(left: Keycode, right: Keycode, up: Keycode, down: Keycode) -> Self {
+        Self {
+            keys: [left, right, up, down],
+            held: [false; 4],
+            ramp: 0.25,
+            centering: 0.15,
+            mouse_area: None,
+            drag: None,
+            drag_radius: 100.0,
+            pad_axes: None,
+            pad_value: (0.0, 0.0),
+            value: (0.0, 0.0),
+        }
+    }
+
+    pub fn wasd() -> Self {
+        Self::new(Keycode::A, Keycode::D, Keycode::W, Keycode::S)
+    }
+
+    pub fn arrows() -> Self {
+        Self::new(Keycode::LEFT, Keycode::RIGHT, Keycode::UP, Keycode::DOWN)
+    }
+
+    // Seconds a held key takes to deflect the stick fully, 0 is immediate.
+    pub fn ramp(self, ramp: f32) -> Self {
+        Self { ramp, ..self }
+    }
+
+    // Seconds the released stick takes to return to the center.
+    pub fn centering(self, centering: f32) -> Self {
+        Self { centering, ..self }
+    }
+
+    // Left button drag starting inside the area (in window coordinates)
+    // moves the stick, `radius` pixels from the start is full deflection.
+    pub fn mouse_area(self, area: Rect, radius: u32) -> Self {
+        Self {
+            mouse_area: Some(area),
+            drag_radius: radius.max(1) as f32,
+            ..self
+        }
+    }
+
+    // Controller axes taking over from the keys while the stick is deflected,
+    // the events should come from the active gamepad only.
+    pub fn gamepad_axes(self, horizontal: Axis, vertical: Axis) -> Self {
+        Self {
+            pad_axes: Some((horizontal, vertical)),
+            ..self
+        }
+    }
+
+    // Returns true when the event moved the stick.
+    pub fn handle_event(&mut self, event: &Event) -> bool {
+        match *event {
+            Event::KeyDown {
+                keycode: Some(k), ..
+            } => self.key(k, true),
+            Event::KeyUp {
+                keycode: Some(k), ..
+            } => self.key(k, false),
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => match self.mouse_area {
+                Some(area) if area.contains_point((x, y)) => {
+                    self.drag = Some((x, y));
+                    true
+                }
+                _ => false,
+            },
+            Event::MouseMotion { x, y, .. } => match self.drag {
+                Some((sx, sy)) => {
+                    let r = self.drag_radius;
+                    self.value = (
+                        ((x - sx) as f32 / r).clamp(-1.0, 1.0),
+                        ((y - sy) as f32 / r).clamp(-1.0, 1.0),
+                    );
+                    true
+                }
+                None => false,
+            },
+            Event::MouseButtonUp {
+                mouse_btn: MouseButton::Left,
+                ..
+            } => self.drag.take().is_some(),
+            Event::ControllerAxisMotion { axis, value, .. } => match self.pad_axes {
+                Some((horizontal, _)) if axis == horizontal => {
+                    self.pad_value.0 = normalize_axis(value);
+                    true
+                }
+                Some((_, vertical)) if axis == vertical => {
+                    self.pad_value.1 = normalize_axis(value);
+                    true
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn key(&mut self, keycode: Keycode, down: bool) -> bool {
+        match self.keys.iter().position(|k| *k == keycode) {
+            Some(idx) => {
+                self.held[idx] = down;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Advances the ramp by the frame time, returns the stick value.
+    pub fn update(&mut self, elapsed: Duration) -> (f32, f32) {
+        if self.drag.is_some() {
+            return self.value;
+        }
+        let (px, py) = self.pad_value;
+        if px.abs() > PAD_OVERRIDE_DEAD_ZONE || py.abs() > PAD_OVERRIDE_DEAD_ZONE {
+            self.value = self.pad_value;
+            return self.value;
+        }
+        let dt = elapsed.as_secs_f32();
+        let step = |value: f32, target: f32, ramp: f32, centering: f32| {
+            let secs = if target == 0.0 { centering } else { ramp };
+            if secs <= 0.0 {
+                target
+            } else {
+                approach(value, target, dt / secs)
+            }
+        };
+        let target = (
+            self.held[1] as i32 as f32 - self.held[0] as i32 as f32,
+            self.held[3] as i32 as f32 - self.held[2] as i32 as f32,
+        );
+        self.value = (
+            step(self.value.0, target.0, self.ramp, self.centering),
+            step(self.value.1, target.1, self.ramp, self.centering),
+        );
+        self.value
+    }
+
+    pub fn value(&self) -> (f32, f32) {
+        self.value
+    }
+
+    // Centers the stick and releases the keys.
+    pub fn reset(&mut self) {
+        self.held = [false; 4];
+        self.drag = None;
+        self.pad_value = (0.0, 0.0);
+        self.value = (0.0, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use sdl2::{
+        controller::{Axis, Button},
+        event::Event,
+        keyboard::{Keycode, Mod},
+    };
+
+    use super::{approach, AxisConfig, InputConfig, InputMapper, VirtualStick};
+
+    #[test]
+    fn dead_zone_and_expo() {
+        let axis = AxisConfig {
+            dead_zone: 0.2,
+            expo: 1.0,
+            invert: true,
+            ..AxisConfig::new("forward")
+        };
+        assert_eq!(0.0, axis.apply(0.15));
+        assert_eq!(-1.0, axis.apply(1.0));
+        // rescaled to 0.5 outside the dead zone, then cubed
+        assert!((axis.apply(-0.6) - 0.125).abs() < 1e-6);
+        let fast = AxisConfig {
+            sensitivity: 2.0,
+            ..AxisConfig::new("turn")
+        };
+        assert_eq!(1.0, fast.apply(0.8));
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = InputConfig::parse(
+            r#"
+            [[axis]]
+            name = "forward"
+            axis = "lefty"
+            dead_zone = 0.1
+
+            [[action]]
+            name = "hover"
+            buttons = ["guide"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(1.0, config.axes[0].sensitivity);
+        assert_eq!(Some("lefty".to_owned()), config.axes[0].axis);
+        assert_eq!(vec!["guide".to_owned()], config.actions[0].buttons);
+        assert!(config.actions[0].keys.is_empty());
+    }
+
+    #[test]
+    fn only_active_pad_is_mapped() {
+        let config = InputConfig::parse(
+            r#"
+            [[axis]]
+            name = "forward"
+            axis = "lefty"
+
+            [[action]]
+            name = "hover"
+            buttons = ["a"]
+            "#,
+        )
+        .unwrap();
+        let mut input = InputMapper::new(&config).unwrap();
+        let button = |which| Event::ControllerButtonDown {
+            timestamp: 0,
+            which,
+            button: Button::A,
+        };
+        let axis = |which| Event::ControllerAxisMotion {
+            timestamp: 0,
+            which,
+            axis: Axis::LeftY,
+            value: i16::MAX,
+        };
+        // the second pad of the co-pilot
+        assert!(!input.handle_event(&button(4), Some(3)));
+        assert!(!input.handle_event(&axis(4), Some(3)));
+        assert!(!input.handle_event(&button(3), None));
+        assert!(!input.is_down("hover"));
+        assert_eq!(0.0, input.axis("forward"));
+
+        assert!(input.handle_event(&button(3), Some(3)));
+        assert!(input.handle_event(&axis(3), Some(3)));
+        assert!(input.is_down("hover"));
+        assert!(input.axis("forward") > 0.99);
+    }
+
+    #[test]
+    fn approach_target() {
+        // ramping up in 0.25 s steps of a 1 s ramp
+        assert_eq!(0.25, approach(0.0, 1.0, 0.25));
+        assert_eq!(1.0, approach(0.9, 1.0, 0.25));
+        // centering from both sides
+        assert_eq!(-0.5, approach(-1.0, 0.0, 0.5));
+        assert_eq!(0.0, approach(0.1, 0.0, 0.5));
+    }
+
+    fn key(keycode: Keycode, down: bool) -> Event {
+        if down {
+            Event::KeyDown {
+                timestamp: 0,
+                window_id: 0,
+                keycode: Some(keycode),
+                scancode: None,
+                keymod: Mod::NOMOD,
+                repeat: false,
+            }
+        } else {
+            Event::KeyUp {
+                timestamp: 0,
+                window_id: 0,
+                keycode: Some(keycode),
+                scancode: None,
+                keymod: Mod::NOMOD,
+                repeat: false,
+            }
+        }
+    }
+
+    #[test]
+    fn virtual_stick_keys_and_gamepad() {
+        let mut stick = VirtualStick::wasd()
+            .ramp(0.2)
+            .centering(0.1)
+            .gamepad_axes(Axis::LeftX, Axis::LeftY);
+        let frame = Duration::from_millis(50);
+        let near = |(x, y): (f32, f32), (ex, ey): (f32, f32)| {
+            (x - ex).abs() < 1e-4 && (y - ey).abs() < 1e-4
+        };
+
+        // full deflection after the 0.2 s ramp
+        assert!(stick.handle_event(&key(Keycode::D, true)));
+        assert!(stick.handle_event(&key(Keycode::W, true)));
+        assert!(!stick.handle_event(&key(Keycode::Q, true)));
+        assert!(near((0.25, -0.25), stick.update(frame)));
+        for _ in 0..3 {
+            stick.update(frame);
+        }
+        assert_eq!((1.0, -1.0), stick.update(frame));
+
+        // back in the center 0.1 s after the release
+        stick.handle_event(&key(Keycode::D, false));
+        stick.handle_event(&key(Keycode::W, false));
+        assert!(near((0.5, -0.5), stick.update(frame)));
+        assert_eq!((0.0, 0.0), stick.update(frame));
+
+        // the deflected gamepad wins over the held key
+        stick.handle_event(&key(Keycode::D, true));
+        let axis = |axis, value| Event::ControllerAxisMotion {
+            timestamp: 0,
+            which: 0,
+            axis,
+            value,
+        };
+        assert!(stick.handle_event(&axis(Axis::LeftX, i16::MIN)));
+        assert!(!stick.handle_event(&axis(Axis::RightX, i16::MAX)));
+        assert_eq!((-1.0, 0.0), stick.update(frame));
+        assert_eq!((-1.0, 0.0), stick.update(frame));
+        // released gamepad hands over to the key again
+        stick.handle_event(&axis(Axis::LeftX, 0));
+        assert!(near((-0.75, 0.0), stick.update(frame)));
+    }
+}
@@ -0,0 +1,607 @@
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{Arc, RwLock},
+};
+
+use sdl2::render::Canvas;
+use serde::Deserialize;
+
+use crate::{
+    color::{self, RgbColor},
+    desktop::{
+        BatteryStatusWidget, ButtonState, ButtonWidget, CheckboxWidget, CommonWidgetProps,
+        DroneOrientation, DroneYawWidget, FlightLog, FlightLogWidget, FloatClampedValue,
+        FloatGenericValue, GamepadStick, GamepadStickWidget, HorizSlider, HorizSliderWidget,
+        HorizonWidget, ImageCarousel, ImageCarouselWidget, LightSignal, LightSignalWidget,
+        RawImage, RawImageWidget, Text, TextInputState, TextInputWidget, TextWidget, ToggleWidget,
+        VertThrust, VertThrustWidget, WifiStrengthWidget, Window,
+    },
+    error::Error,
+};
+
+// Layout describes widgets placed on the `desktop::Window`, e.g.:
+//
+// ```toml
+// [[widget]]
+// type = "horiz_slider"
+// id = "sensitivity"
+// place = [0.2, 0.5]
+// size = [0.15, 0.003]
+// min = 0.0
+// max = 1.0
+// steps = 5.0
+// ```
+//
+// Placement and size are fractions of the window, `rect` can be used
+// instead of `size` for square widgets.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Layout {
+    #[serde(rename = "widget", default)]
+    pub widgets: Vec<WidgetSpec>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WidgetSpec {
+    pub id: String,
+    pub place: [f32; 2],
+    pub size: Option<[f32; 2]>,
+    pub rect: Option<f32>,
+    #[serde(default)]
+    pub textures: Vec<String>,
+    pub color: Option<[f32; 4]>,
+    #[serde(flatten)]
+    pub kind: WidgetKind,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+// Struct variants only, unit variants would ignore unknown keys.
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum WidgetKind {
+    Text {
+        #[serde(default)]
+        text: String,
+    },
+    GamepadStick {},
+    HorizSlider {
+        min: f32,
+        max: f32,
+        steps: f32,
+        value: Option<f32>,
+        #[serde(default)]
+        vertical: bool,
+        #[serde(default)]
+        ticks: bool,
+        #[serde(default)]
+        labels: bool,
+        #[serde(default)]
+        readout: bool,
+    },
+    VertThrust {
+        value: Option<f32>,
+        color1: Option<[f32; 4]>,
+        color2: Option<[f32; 4]>,
+        color_scale_factor: Option<f32>,
+        scale: Option<f32>,
+    },
+    RawImage {
+        width: u32,
+        height: u32,
+    },
+    BatteryStatus {
+        value: Option<f32>,
+    },
+    WifiStrength {
+        value: Option<f32>,
+    },
+    LightSignal {},
+    Horizon {
+        max_pitch: f32,
+    },
+    ImageCarousel {
+        image_dir: String,
+        images: usize,
+    },
+    DroneYaw {
+        value: Option<f32>,
+    },
+    FlightLog {},
+    Button {
+        label: String,
+        enabled: Option<bool>,
+    },
+    Toggle {
+        label: String,
+        checked: Option<bool>,
+    },
+    Checkbox {
+        label: String,
+        checked: Option<bool>,
+    },
+    TextInput {
+        #[serde(default)]
+        placeholder: String,
+        text: Option<String>,
+        max_len: Option<usize>,
+        #[serde(default)]
+        keyboard: bool,
+    },
+}
+
+// Shared state of the widget created from the layout.
+#[derive(Clone)]
+pub enum WidgetHandle {
+    Text(Arc<RwLock<Text>>),
+    GamepadStick(Arc<RwLock<GamepadStick>>),
+    HorizSlider(Arc<RwLock<HorizSlider>>),
+    VertThrust(Arc<RwLock<VertThrust>>),
+    RawImage(Arc<RwLock<RawImage>>),
+    BatteryStatus(Arc<RwLock<FloatClampedValue>>),
+    WifiStrength(Arc<RwLock<FloatClampedValue>>),
+    LightSignal(Arc<RwLock<LightSignal>>),
+    Horizon(Arc<RwLock<DroneOrientation>>),
+    ImageCarousel(Arc<RwLock<ImageCarousel>>),
+    DroneYaw(Arc<RwLock<FloatGenericValue>>),
+    FlightLog(Arc<RwLock<FlightLog>>),
+    Button(Arc<RwLock<ButtonState>>),
+    Toggle(Arc<RwLock<ButtonState>>),
+    Checkbox(Arc<RwLock<ButtonState>>),
+    TextInput(Arc<RwLock<TextInputState>>),
+}
+
+// Named handles to all widgets loaded from the layout.
+#[derive(Default)]
+pub struct LayoutHandles {
+    handles: HashMap<String, WidgetHandle>,
+}
+
+fn to_color(c: [f32; 4]) -> RgbColor {
+    RgbColor::new(c[0], c[1], c[2], c[3])
+}
+
+impl Layout {
+    pub fn parse(content: &str) -> Result<Self, Error> {
+        let layout: Layout = toml::from_str(content).map_err(|e| Error::Layout(e.to_string()))?;
+        let mut ids = Vec::new();
+        for w in layout.widgets.iter() {
+            if ids.contains(&&w.id) {
+                return Err(Error::Layout(format!("duplicate widget id '{}'", w.id)));
+            }
+            ids.push(&w.id);
+        }
+        Ok(layout)
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        let content = fs::read_to_string(path).map_err(|e| Error::Io(format!("{path}: {e}")))?;
+        Self::parse(&content)
+    }
+
+    // Creates all widgets on the window in the order they are listed.
+    pub fn load(
+        &self,
+        window: &mut Window,
+        canvas: &mut Canvas<sdl2::video::Window>,
+    ) -> Result<LayoutHandles, Error> {
+        let mut handles = LayoutHandles::default();
+        for spec in self.widgets.iter() {
+            let handle = spec.create(window, canvas)?;
+            window.bind_layout_widget(&spec.id, handle.clone());
+            handles.handles.insert(spec.id.clone(), handle);
+        }
+        Ok(handles)
+    }
+}
+
+impl WidgetSpec {
+    pub(crate) fn props(&self, canvas: &Canvas<sdl2::video::Window>) -> CommonWidgetProps {
+        self.apply(CommonWidgetProps::new(canvas))
+    }
+
+    // Applies placement, size and textures of the spec to the widget properties.
+    pub(crate) fn apply(&self, props: CommonWidgetProps) -> CommonWidgetProps {
+        let mut props = props.place(self.place[0], self.place[1]);
+        if let Some(w) = self.rect {
+            props = props.rect(w);
+        } else if let Some([w, h]) = self.size {
+            props = props.size(w, h);
+        }
+        if !self.textures.is_empty() {
+            props = props.textures(self.textures.iter().map(|t| t.as_str()).collect());
+        }
+        if let Some(c) = self.color {
+            props = props.color(to_color(c));
+        }
+        props
+    }
+
+    // Applies the spec to already existing widget, used by the hot reload.
+    // Only the look of the widget changes, values stay untouched.
+    pub(crate) fn reconfigure(&self, props: &mut CommonWidgetProps, handle: &WidgetHandle) {
+        props.set_place(self.place[0], self.place[1]);
+        if let Some(w) = self.rect {
+            props.set_rect(w);
+        } else if let Some([w, h]) = self.size {
+            props.set_size(w, h);
+        }
+        if !self.textures.is_empty() {
+            props.set_textures(&self.textures);
+        }
+        props.set_color(self.color.map(to_color));
+
+        if let (
+            WidgetKind::VertThrust {
+                color1,
+                color2,
+                color_scale_factor,
+                scale,
+                ..
+            },
+            WidgetHandle::VertThrust(h),
+        ) = (&self.kind, handle)
+        {
+            let mut p = h.write().unwrap();
+            if let Some(c) = color1 {
+                p.set_color1(to_color(*c));
+            }
+            if let Some(c) = color2 {
+                p.set_color2(to_color(*c));
+            }
+            if let Some(f) = color_scale_factor {
+                p.set_color_scale_factor(*f);
+            }
+            if let Some(s) = scale {
+                p.set_scale(*s);
+            }
+        }
+    }
+
+    fn create(
+        &self,
+        window: &mut Window,
+        canvas: &mut Canvas<sdl2::video::Window>,
+    ) -> Result<WidgetHandle, Error> {
+        let props = self.props(canvas);
+        let handle = match &self.kind {
+            WidgetKind::Text { text } => {
+                let h = TextWidget::new(props).on_window(window);
+                h.write().unwrap().set(text.clone());
+                WidgetHandle::Text(h)
+            }
+            WidgetKind::GamepadStick {} => {
+                WidgetHandle::GamepadStick(GamepadStickWidget::new(props).on_window(window))
+            }
+            WidgetKind::HorizSlider {
+                min,
+                max,
+                steps,
+                value,
+                vertical,
+                ticks,
+                labels,
+                readout,
+            } => {
+                let mut slider = HorizSliderWidget::new(props, *min, *max, *steps)
+                    .ticks(*ticks)
+                    .labels(*labels)
+                    .readout(*readout);
+                if *vertical {
+                    slider = slider.vertical();
+                }
+                let h = slider.on_window(window);
+                if let Some(v) = value {
+                    h.write().unwrap().set(*v);
+                }
+                WidgetHandle::HorizSlider(h)
+            }
+            WidgetKind::VertThrust {
+                value,
+                color1,
+                color2,
+                color_scale_factor,
+                scale,
+            } => {
+                let h = VertThrustWidget::new(props).on_window(window);
+                let mut p = h.write().unwrap();
+                if let Some(v) = value {
+                    p.set(*v);
+                }
+                if let Some(c) = color1 {
+                    p.set_color1(to_color(*c));
+                }
+                if let Some(c) = color2 {
+                    p.set_color2(to_color(*c));
+                }
+                if let Some(f) = color_scale_factor {
+                    p.set_color_scale_factor(*f);
+                }
+                if let Some(s) = scale {
+                    p.set_scale(*s);
+                }
+                drop(p);
+                WidgetHandle::VertThrust(h)
+            }
+            WidgetKind::RawImage { width, height } => WidgetHandle::RawImage(
+                RawImageWidget::try_new(props, canvas, *width, *height)?.on_window(window),
+            ),
+            WidgetKind::BatteryStatus { value } => {
+                let h = BatteryStatusWidget::new(props).on_window(window);
+                h.write().unwrap().set(value.unwrap_or(0.0));
+                WidgetHandle::BatteryStatus(h)
+            }
+            WidgetKind::WifiStrength { value } => {
+                let h = WifiStrengthWidget::new(props).on_window(window);
+                h.write().unwrap().set(value.unwrap_or(0.0));
+                WidgetHandle::WifiStrength(h)
+            }
+            WidgetKind::LightSignal {} => {
+                WidgetHandle::LightSignal(LightSignalWidget::new(props).on_window(window))
+            }
+            WidgetKind::Horizon { max_pitch } => WidgetHandle::Horizon(
+                HorizonWidget::new(props, *max_pitch, color::YELLOW.clone()).on_window(window),
+            ),
+            WidgetKind::ImageCarousel { image_dir, images } => WidgetHandle::ImageCarousel(
+                ImageCarouselWidget::new(props, image_dir, *images).on_window(window),
+            ),
+            WidgetKind::DroneYaw { value } => {
+                let h = DroneYawWidget::new(props).on_window(window);
+                h.write().unwrap().set(value.unwrap_or(0.0));
+                WidgetHandle::DroneYaw(h)
+            }
+            WidgetKind::FlightLog {} => {
+                WidgetHandle::FlightLog(FlightLogWidget::new(props).on_window(window))
+            }
+            WidgetKind::Button { label, enabled } => {
+                let h = ButtonWidget::new(props, label).on_window(window);
+                h.write().unwrap().set_enabled(enabled.unwrap_or(true));
+                WidgetHandle::Button(h)
+            }
+            WidgetKind::Toggle { label, checked } => {
+                let h = ToggleWidget::new(props, label).on_window(window);
+                h.write().unwrap().set_checked(checked.unwrap_or(false));
+                WidgetHandle::Toggle(h)
+            }
+            WidgetKind::Checkbox { label, checked } => {
+                let h = CheckboxWidget::new(props, label).on_window(window);
+                h.write().unwrap().set_checked(checked.unwrap_or(false));
+                WidgetHandle::Checkbox(h)
+            }
+            WidgetKind::TextInput {
+                placeholder,
+                text,
+                max_len,
+                keyboard,
+            } => {
+                let mut input =
+                    TextInputWidget::new(props, placeholder).on_screen_keyboard(*keyboard);
+                if let Some(max_len) = max_len {
+                    input = input.max_len(*max_len);
+                }
+                let h = input.on_window(window);
+                if let Some(text) = text {
+                    h.write().unwrap().set_text(text);
+                }
+                WidgetHandle::TextInput(h)
+            }
+        };
+        Ok(handle)
+    }
+}
+
+impl LayoutHandles {
+    pub fn get(&self, id: &str) -> Option<&WidgetHandle> {
+        self.handles.get(id)
+    }
+
+    pub fn ids(&self) -> Vec<&String> {
+        self.handles.keys().collect()
+    }
+
+    pub fn text(&self, id: &str) -> Option<Arc<RwLock<Text>>> {
+        match self.get(id) {
+            Some(WidgetHandle::Text(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn gamepad_stick(&self, id: &str) -> Option<Arc<RwLock<GamepadStick>>> {
+        match self.get(id) {
+            Some(WidgetHandle::GamepadStick(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn horiz_slider(&self, id: &str) -> Option<Arc<RwLock<HorizSlider>>> {
+        match self.get(id) {
+            Some(WidgetHandle::HorizSlider(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn vert_thrust(&self, id: &str) -> Option<Arc<RwLock<VertThrust>>> {
+        match self.get(id) {
+            Some(WidgetHandle::VertThrust(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn raw_image(&self, id: &str) -> Option<Arc<RwLock<RawImage>>> {
+        match self.get(id) {
+            Some(WidgetHandle::RawImage(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn battery_status(&self, id: &str) -> Option<Arc<RwLock<FloatClampedValue>>> {
+        match self.get(id) {
+            Some(WidgetHandle::BatteryStatus(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn wifi_strength(&self, id: &str) -> Option<Arc<RwLock<FloatClampedValue>>> {
+        match self.get(id) {
+            Some(WidgetHandle::WifiStrength(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn light_signal(&self, id: &str) -> Option<Arc<RwLock<LightSignal>>> {
+        match self.get(id) {
+            Some(WidgetHandle::LightSignal(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn horizon(&self, id: &str) -> Option<Arc<RwLock<DroneOrientation>>> {
+        match self.get(id) {
+            Some(WidgetHandle::Horizon(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn image_carousel(&self, id: &str) -> Option<Arc<RwLock<ImageCarousel>>> {
+        match self.get(id) {
+            Some(WidgetHandle::ImageCarousel(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn drone_yaw(&self, id: &str) -> Option<Arc<RwLock<FloatGenericValue>>> {
+        match self.get(id) {
+            Some(WidgetHandle::DroneYaw(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn flight_log(&self, id: &str) -> Option<Arc<RwLock<FlightLog>>> {
+        match self.get(id) {
+            Some(WidgetHandle::FlightLog(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn button(&self, id: &str) -> Option<Arc<RwLock<ButtonState>>> {
+        match self.get(id) {
+            Some(WidgetHandle::Button(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn toggle(&self, id: &str) -> Option<Arc<RwLock<ButtonState>>> {
+        match self.get(id) {
+            Some(WidgetHandle::Toggle(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn checkbox(&self, id: &str) -> Option<Arc<RwLock<ButtonState>>> {
+        match self.get(id) {
+            Some(WidgetHandle::Checkbox(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn text_input(&self, id: &str) -> Option<Arc<RwLock<TextInputState>>> {
+        match self.get(id) {
+            Some(WidgetHandle::TextInput(h)) => Some(h.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Layout, WidgetKind};
+
+    #[test]
+    fn reject_unknown_keys() {
+        for typo in [
+            "type = \"horiz_slider\"\nmin = 0.0\nmax = 1.0\nsteps = 5.0\nvalu = 0.5",
+            "type = \"battery_status\"\ncolour = [1.0, 0.0, 0.0, 1.0]",
+            "type = \"flight_log\"\ntexture = [\"log.png\"]",
+        ] {
+            let content = format!("[[widget]]\nid = \"w\"\nplace = [0.5, 0.5]\n{typo}");
+            let err = Layout::parse(&content).unwrap_err();
+            assert!(err.to_string().contains("unknown field"), "{err}");
+        }
+        assert!(Layout::parse("[[widgets]]\nid = \"w\"").is_err());
+    }
+
+    #[test]
+    fn parse_layout() {
+        let layout = Layout::parse(
+            r#"
+            [[widget]]
+            type = "horiz_slider"
+            id = "sensitivity"
+            place = [0.2, 0.5]
+            size = [0.15, 0.003]
+            min = 0.0
+            max = 1.0
+            steps = 5.0
+
+            [[widget]]
+            type = "gamepad_stick"
+            id = "left_stick"
+            place = [0.2, 0.7]
+            rect = 0.1
+            textures = ["skin/joy.png", "skin/stick.png"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(2, layout.widgets.len());
+        assert_eq!("sensitivity", layout.widgets[0].id);
+        assert_eq!(
+            WidgetKind::HorizSlider {
+                min: 0.0,
+                max: 1.0,
+                steps: 5.0,
+                value: None,
+                vertical: false,
+                ticks: false,
+                labels: false,
+                readout: false,
+            },
+            layout.widgets[0].kind
+        );
+        assert_eq!(Some([0.15, 0.003]), layout.widgets[0].size);
+        assert_eq!(WidgetKind::GamepadStick {}, layout.widgets[1].kind);
+        assert_eq!(Some(0.1), layout.widgets[1].rect);
+        assert_eq!(2, layout.widgets[1].textures.len());
+    }
+
+    #[test]
+    fn parse_layout_duplicate_id() {
+        let layout = Layout::parse(
+            r#"
+            [[widget]]
+            type = "light_signal"
+            id = "light"
+            place = [0.2, 0.5]
+            rect = 0.1
+
+            [[widget]]
+            type = "flight_log"
+            id = "light"
+            place = [0.2, 0.7]
+            rect = 0.1
+            "#,
+        );
+        assert!(layout.is_err());
+    }
+
+    #[test]
+    fn parse_layout_unknown_type() {
+        let layout = Layout::parse(
+            r#"
+            [[widget]]
+            type = "teapot"
+            id = "tea"
+            place = [0.2, 0.5]
+            "#,
+        );
+        assert!(layout.is_err());
+    }
+}
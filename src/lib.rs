@@ -1,10 +1,26 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub mod assets;
 pub mod color;
+pub mod container;
 pub mod desktop;
+pub mod error;
+pub mod feedback;
+pub mod focus;
+pub mod font;
+pub mod gamepad;
+pub mod h264;
+pub(crate) mod hotreload;
+pub mod input;
+pub mod layout;
+pub(crate) mod replay;
 pub mod sdl;
+pub mod snapshot;
 pub(crate) mod texcache;
+pub mod text;
+pub(crate) mod textedit;
 pub(crate) mod utils;
 pub mod vec;
-pub(crate) mod video;
+pub mod video;
+pub mod videosource;
@@ -0,0 +1,537 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    str::SplitWhitespace,
+    time::Instant,
+};
+
+use sdl2::{
+    controller::{Axis, Button},
+    event::Event,
+    keyboard::{Keycode, Mod, Scancode},
+    mouse::{MouseButton, MouseState, MouseWheelDirection},
+};
+
+use crate::error::Error;
+
+// Input sessions are stored as text, one event per line:
+//
+// ```
+// 120 2001 key_down 119 26 0 0
+// 121 2018 axis 0 lefty -12000
+// 130 2168 mouse_down 1 1 640 360
+// ```
+//
+// The line starts with the frame number and the time in ms since the
+// recording started. Controller device and window events are not recorded,
+// they depend on the hardware and the window of the replaying session. For
+// the same reason the recorded instance id of controller events is replaced
+// by the one of the pad active when replaying.
+pub(crate) struct Recorder {
+    out: BufWriter<File>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub(crate) fn new(path: &str) -> Result<Self, Error> {
+        let file = File::create(path).map_err(|e| Error::Io(format!("{path}: {e}")))?;
+        tracing::info!(path, "recording input");
+        Ok(Self {
+            out: BufWriter::new(file),
+            start: Instant::now(),
+        })
+    }
+
+    pub(crate) fn record(&mut self, frame: u64, event: &Event) {
+        let Some(encoded) = encode(event) else {
+            return;
+        };
+        let ms = self.start.elapsed().as_millis();
+        if let Err(e) = writeln!(self.out, "{frame} {ms} {encoded}") {
+            tracing::error!("can't record event: {e}");
+        }
+    }
+
+    pub(crate) fn flush(&mut self) {
+        if let Err(e) = self.out.flush() {
+            tracing::error!("can't write recording: {e}");
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+// Recorded session fed back frame by frame.
+pub(crate) struct Player {
+    // frame and the event
+    events: Vec<(u64, Event)>,
+    next: usize,
+}
+
+impl Player {
+    pub(crate) fn new(path: &str) -> Result<Self, Error> {
+        let content = fs::read_to_string(path).map_err(|e| Error::Io(format!("{path}: {e}")))?;
+        let events = parse(&content)?;
+        tracing::info!(path, events = events.len(), "replaying input");
+        Ok(Self { events, next: 0 })
+    }
+
+    // Events recorded in the frame, earlier frames not taken yet included.
+    // Controller events are sent as coming from `active_pad`.
+    pub(crate) fn take(&mut self, frame: u64, active_pad: Option<u32>) -> Vec<Event> {
+        let mut taken = Vec::new();
+        while let Some((f, event)) = self.events.get(self.next) {
+            if *f > frame {
+                break;
+            }
+            taken.push(match active_pad {
+                Some(instance) => retarget(event.clone(), instance),
+                None => event.clone(),
+            });
+            self.next += 1;
+        }
+        taken
+    }
+
+    pub(crate) fn finished(&self) -> bool {
+        self.next >= self.events.len()
+    }
+}
+
+// Moves controller event to the pad with the SDL instance id.
+fn retarget(event: Event, instance: u32) -> Event {
+    match event {
+        Event::ControllerAxisMotion {
+            timestamp,
+            axis,
+            value,
+            ..
+        } => Event::ControllerAxisMotion {
+            timestamp,
+            which: instance,
+            axis,
+            value,
+        },
+        Event::ControllerButtonDown {
+            timestamp, button, ..
+        } => Event::ControllerButtonDown {
+            timestamp,
+            which: instance,
+            button,
+        },
+        Event::ControllerButtonUp {
+            timestamp, button, ..
+        } => Event::ControllerButtonUp {
+            timestamp,
+            which: instance,
+            button,
+        },
+        event => event,
+    }
+}
+
+fn encode(event: &Event) -> Option<String> {
+    let line = match event {
+        Event::Quit { .. } => "quit".to_owned(),
+        Event::KeyDown {
+            keycode,
+            scancode,
+            keymod,
+            repeat,
+            ..
+        }
+        | Event::KeyUp {
+            keycode,
+            scancode,
+            keymod,
+            repeat,
+            ..
+        } => {
+            let kind = if matches!(event, Event::KeyDown { .. }) {
+                "key_down"
+            } else {
+                "key_up"
+            };
+            format!(
+                "{kind} {} {} {} {}",
+                // 0 is parsed back as no key
+                keycode.map(|k| k.into_i32()).unwrap_or(0),
+                scancode.map(|s| s as i32).unwrap_or(0),
+                keymod.bits(),
+                *repeat as u8
+            )
+        }
+        Event::TextInput { text, .. } => format!("text_input {}", text.replace('\n', " ")),
+        Event::MouseMotion {
+            mousestate,
+            x,
+            y,
+            xrel,
+            yrel,
+            ..
+        } => format!(
+            "mouse_motion {x} {y} {xrel} {yrel} {}",
+            mousestate.to_sdl_state()
+        ),
+        Event::MouseButtonDown {
+            mouse_btn,
+            clicks,
+            x,
+            y,
+            ..
+        } => format!("mouse_down {} {clicks} {x} {y}", *mouse_btn as u8),
+        Event::MouseButtonUp {
+            mouse_btn,
+            clicks,
+            x,
+            y,
+            ..
+        } => format!("mouse_up {} {clicks} {x} {y}", *mouse_btn as u8),
+        Event::MouseWheel {
+            x,
+            y,
+            direction,
+            precise_x,
+            precise_y,
+            mouse_x,
+            mouse_y,
+            ..
+        } => format!(
+            "mouse_wheel {x} {y} {} {precise_x} {precise_y} {mouse_x} {mouse_y}",
+            direction.to_ll()
+        ),
+        Event::ControllerAxisMotion {
+            which, axis, value, ..
+        } => format!("axis {which} {} {value}", axis.string()),
+        Event::ControllerButtonDown { which, button, .. } => {
+            format!("button_down {which} {}", button.string())
+        }
+        Event::ControllerButtonUp { which, button, .. } => {
+            format!("button_up {which} {}", button.string())
+        }
+        Event::FingerDown {
+            touch_id,
+            finger_id,
+            x,
+            y,
+            dx,
+            dy,
+            pressure,
+            ..
+        }
+        | Event::FingerUp {
+            touch_id,
+            finger_id,
+            x,
+            y,
+            dx,
+            dy,
+            pressure,
+            ..
+        }
+        | Event::FingerMotion {
+            touch_id,
+            finger_id,
+            x,
+            y,
+            dx,
+            dy,
+            pressure,
+            ..
+        } => {
+            let kind = match event {
+                Event::FingerDown { .. } => "finger_down",
+                Event::FingerUp { .. } => "finger_up",
+                _ => "finger_motion",
+            };
+            format!("{kind} {touch_id} {finger_id} {x} {y} {dx} {dy} {pressure}")
+        }
+        _ => return None,
+    };
+    Some(line)
+}
+
+struct Fields<'a>(SplitWhitespace<'a>);
+
+impl Fields<'_> {
+    fn next<T: std::str::FromStr>(&mut self) -> Result<T, String> {
+        let field = self.0.next().ok_or("missing field")?;
+        field
+            .parse()
+            .map_err(|_| format!("invalid field '{field}'"))
+    }
+}
+
+// Returns the frame and the event, the time is its timestamp.
+fn parse_line(line: &str) -> Result<(u64, Event), String> {
+    let mut f = Fields(line.split_whitespace());
+    let frame = f.next()?;
+    let timestamp = f.next()?;
+    let kind: String = f.next()?;
+    let event = match kind.as_str() {
+        "quit" => Event::Quit { timestamp },
+        "key_down" | "key_up" => {
+            // older recordings store a missing key as -1
+            let keycode = Keycode::from_i32(f.next::<i32>()?.max(0));
+            let scancode = Scancode::from_i32(f.next::<i32>()?.max(0));
+            let keymod = Mod::from_bits_truncate(f.next()?);
+            let repeat = f.next::<u8>()? != 0;
+            if kind == "key_down" {
+                Event::KeyDown {
+                    timestamp,
+                    window_id: 0,
+                    keycode,
+                    scancode,
+                    keymod,
+                    repeat,
+                }
+            } else {
+                Event::KeyUp {
+                    timestamp,
+                    window_id: 0,
+                    keycode,
+                    scancode,
+                    keymod,
+                    repeat,
+                }
+            }
+        }
+        "text_input" => {
+            let text = line.splitn(4, ' ').nth(3).unwrap_or("").to_owned();
+            Event::TextInput {
+                timestamp,
+                window_id: 0,
+                text,
+            }
+        }
+        "mouse_motion" => Event::MouseMotion {
+            timestamp,
+            window_id: 0,
+            which: 0,
+            x: f.next()?,
+            y: f.next()?,
+            xrel: f.next()?,
+            yrel: f.next()?,
+            mousestate: MouseState::from_sdl_state(f.next()?),
+        },
+        "mouse_down" | "mouse_up" => {
+            let mouse_btn = MouseButton::from_ll(f.next()?);
+            let clicks = f.next()?;
+            let x = f.next()?;
+            let y = f.next()?;
+            if kind == "mouse_down" {
+                Event::MouseButtonDown {
+                    timestamp,
+                    window_id: 0,
+                    which: 0,
+                    mouse_btn,
+                    clicks,
+                    x,
+                    y,
+                }
+            } else {
+                Event::MouseButtonUp {
+                    timestamp,
+                    window_id: 0,
+                    which: 0,
+                    mouse_btn,
+                    clicks,
+                    x,
+                    y,
+                }
+            }
+        }
+        "mouse_wheel" => Event::MouseWheel {
+            timestamp,
+            window_id: 0,
+            which: 0,
+            x: f.next()?,
+            y: f.next()?,
+            direction: MouseWheelDirection::from_ll(f.next()?),
+            precise_x: f.next()?,
+            precise_y: f.next()?,
+            mouse_x: f.next()?,
+            mouse_y: f.next()?,
+        },
+        "axis" => {
+            let which = f.next()?;
+            let name: String = f.next()?;
+            Event::ControllerAxisMotion {
+                timestamp,
+                which,
+                axis: Axis::from_string(&name).ok_or(format!("unknown axis '{name}'"))?,
+                value: f.next()?,
+            }
+        }
+        "button_down" | "button_up" => {
+            let which = f.next()?;
+            let name: String = f.next()?;
+            let button = Button::from_string(&name).ok_or(format!("unknown button '{name}'"))?;
+            if kind == "button_down" {
+                Event::ControllerButtonDown {
+                    timestamp,
+                    which,
+                    button,
+                }
+            } else {
+                Event::ControllerButtonUp {
+                    timestamp,
+                    which,
+                    button,
+                }
+            }
+        }
+        "finger_down" | "finger_up" | "finger_motion" => {
+            let touch_id = f.next()?;
+            let finger_id = f.next()?;
+            let x = f.next()?;
+            let y = f.next()?;
+            let dx = f.next()?;
+            let dy = f.next()?;
+            let pressure = f.next()?;
+            match kind.as_str() {
+                "finger_down" => Event::FingerDown {
+                    timestamp,
+                    touch_id,
+                    finger_id,
+                    x,
+                    y,
+                    dx,
+                    dy,
+                    pressure,
+                },
+                "finger_up" => Event::FingerUp {
+                    timestamp,
+                    touch_id,
+                    finger_id,
+                    x,
+                    y,
+                    dx,
+                    dy,
+                    pressure,
+                },
+                _ => Event::FingerMotion {
+                    timestamp,
+                    touch_id,
+                    finger_id,
+                    x,
+                    y,
+                    dx,
+                    dy,
+                    pressure,
+                },
+            }
+        }
+        _ => return Err(format!("unknown event '{kind}'")),
+    };
+    Ok((frame, event))
+}
+
+fn parse(content: &str) -> Result<Vec<(u64, Event)>, Error> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            parse_line(line).map_err(|e| Error::Replay(format!("line {}: {e}", n + 1)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use sdl2::{
+        event::Event,
+        keyboard::{Keycode, Mod, Scancode},
+        mouse::MouseButton,
+    };
+
+    use super::{encode, parse_line, retarget, Recorder};
+    use crate::input::{InputConfig, InputMapper};
+
+    #[test]
+    fn mouse_roundtrip() {
+        let event = Event::MouseButtonDown {
+            timestamp: 2001,
+            window_id: 0,
+            which: 0,
+            mouse_btn: MouseButton::Left,
+            clicks: 1,
+            x: 640,
+            y: 360,
+        };
+        let line = format!("130 2001 {}", encode(&event).unwrap());
+        assert_eq!("130 2001 mouse_down 1 1 640 360", line);
+        assert_eq!((130, event), parse_line(&line).unwrap());
+        assert!(parse_line("130 2001 mouse_down 1").is_err());
+    }
+
+    #[test]
+    fn flushed_frame_survives_exit() {
+        let path = std::env::temp_dir().join(format!("sdl-ui-flush-{}.rec", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut recorder = Recorder::new(path).unwrap();
+        recorder.record(7, &Event::Quit { timestamp: 0 });
+        recorder.flush();
+        // process::exit doesn't run the destructors either
+        std::mem::forget(recorder);
+        let content = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(content.starts_with("7 "));
+        assert!(content.ends_with(" quit\n"));
+    }
+
+    #[test]
+    fn key_roundtrip() {
+        let event = Event::KeyDown {
+            timestamp: 2001,
+            window_id: 0,
+            keycode: Some(Keycode::W),
+            scancode: Some(Scancode::W),
+            keymod: Mod::LSHIFTMOD,
+            repeat: true,
+        };
+        let line = format!("120 2001 {}", encode(&event).unwrap());
+        assert_eq!("120 2001 key_down 119 26 1 1", line);
+        assert_eq!((120, event), parse_line(&line).unwrap());
+
+        // e.g. keys typed through an input method
+        let event = Event::KeyUp {
+            timestamp: 2018,
+            window_id: 0,
+            keycode: None,
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat: false,
+        };
+        let line = format!("121 2018 {}", encode(&event).unwrap());
+        assert_eq!("121 2018 key_up 0 0 0 0", line);
+        assert_eq!((121, event.clone()), parse_line(&line).unwrap());
+        assert_eq!(
+            (121, event),
+            parse_line("121 2018 key_up -1 -1 0 0").unwrap()
+        );
+    }
+
+    #[test]
+    fn replayed_axis_follows_active_pad() {
+        let config = InputConfig::parse(
+            r#"
+            [[axis]]
+            name = "forward"
+            axis = "lefty"
+            "#,
+        )
+        .unwrap();
+        let mut input = InputMapper::new(&config).unwrap();
+        // recorded with the pad 0, replayed with the pad 5 connected
+        let (_, event) = parse_line("121 2018 axis 0 lefty 32767").unwrap();
+        assert!(!input.handle_event(&event, Some(5)));
+        assert!(input.handle_event(&retarget(event, 5), Some(5)));
+        assert!(input.axis("forward") > 0.99);
+    }
+}
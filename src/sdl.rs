@@ -2,74 +2,254 @@ use std::time::{Duration, Instant};
 
 use sdl2::{
     controller::GameController,
-    image::LoadTexture,
     pixels::Color,
     rect::{Point, Rect},
     render::{Canvas, Texture},
+    sys::SDL_WindowFlags,
+    ttf::FontStyle,
     video::Window,
     EventPump, Sdl,
 };
 
-use crate::{utils, vec::Vec4};
+use crate::{
+    assets,
+    error::Error,
+    font::{self, FontKey, FontManager},
+    gamepad::GamepadRegistry,
+    vec::Vec4,
+};
 
 use super::color::RgbColor;
-lazy_static! {
-    static ref FONT_PATH: String = utils::get_env(
-        "SDL_UI_FONT",
-        "/usr/share/fonts/truetype/ubuntu/UbuntuMono-R.ttf"
-    );
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowMode {
+    Windowed,
+    // changes the display video mode to the window size
+    Fullscreen,
+    // covers the whole display keeping its video mode
+    FullscreenDesktop,
+    Borderless,
+}
+
+// Window setup used by `sdl_init_with`:
+//
+// ```
+// let config = WindowConfig::new(1280, 720)
+//     .title("ground station")
+//     .resizable(true)
+//     .high_dpi(true);
+// ```
+#[derive(Clone, Debug)]
+pub struct WindowConfig {
+    title: String,
+    width: u32,
+    height: u32,
+    mode: WindowMode,
+    resizable: bool,
+    display: i32,
+    vsync: bool,
+    high_dpi: bool,
 }
 
+impl WindowConfig {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            title: "rust-sdl-ui".to_owned(),
+            width,
+            height,
+            mode: WindowMode::Windowed,
+            resizable: false,
+            display: 0,
+            vsync: false,
+            high_dpi: false,
+        }
+    }
+
+    pub fn title(self, title: &str) -> Self {
+        Self {
+            title: title.to_owned(),
+            ..self
+        }
+    }
+
+    pub fn mode(self, mode: WindowMode) -> Self {
+        Self { mode, ..self }
+    }
+
+    pub fn resizable(self, resizable: bool) -> Self {
+        Self { resizable, ..self }
+    }
+
+    // Index of the display the window is centered on.
+    pub fn display(self, display: i32) -> Self {
+        Self { display, ..self }
+    }
+
+    pub fn vsync(self, vsync: bool) -> Self {
+        Self { vsync, ..self }
+    }
+
+    // Renders in the full resolution of HiDPI displays, the drawable size is
+    // then bigger than the window size.
+    pub fn high_dpi(self, high_dpi: bool) -> Self {
+        Self { high_dpi, ..self }
+    }
+}
+
+// SDL window flags for the mode and options of the config.
+pub(crate) fn window_flags(config: &WindowConfig) -> u32 {
+    let mut flags = match config.mode {
+        WindowMode::Windowed => 0,
+        WindowMode::Fullscreen => SDL_WindowFlags::SDL_WINDOW_FULLSCREEN as u32,
+        WindowMode::FullscreenDesktop => SDL_WindowFlags::SDL_WINDOW_FULLSCREEN_DESKTOP as u32,
+        WindowMode::Borderless => SDL_WindowFlags::SDL_WINDOW_BORDERLESS as u32,
+    };
+    if config.resizable {
+        flags |= SDL_WindowFlags::SDL_WINDOW_RESIZABLE as u32;
+    }
+    if config.high_dpi {
+        flags |= SDL_WindowFlags::SDL_WINDOW_ALLOW_HIGHDPI as u32;
+    }
+    flags
+}
+
+// Canvas pixels per screen coordinate, bigger than 1 on HiDPI displays.
+pub fn scale_factor(window_size: (u32, u32), drawable_size: (u32, u32)) -> (f32, f32) {
+    let scale = |window: u32, drawable: u32| {
+        if window == 0 {
+            1.0
+        } else {
+            drawable as f32 / window as f32
+        }
+    };
+    (
+        scale(window_size.0, drawable_size.0),
+        scale(window_size.1, drawable_size.1),
+    )
+}
+
+// Fullscreen desktop window, the requested size is only used when the
+// fullscreen mode is not available. Use `sdl_init_with` for other setups.
 pub fn sdl_init(
     width: u32,
     height: u32,
     gamepad: bool,
 ) -> (EventPump, Canvas<Window>, Option<GameController>, u32, u32) {
-    let sdl_context = sdl2::init().unwrap();
+    let (event_pump, canvas, gamepads, w, h) =
+        sdl_init_with(&fullscreen_config(width, height), gamepad).unwrap_or_else(|e| panic!("{e}"));
+    (event_pump, canvas, gamepads.into_active(), w, h)
+}
+
+pub(crate) fn fullscreen_config(width: u32, height: u32) -> WindowConfig {
+    WindowConfig::new(width, height)
+        .title("Rustvaders")
+        .mode(WindowMode::FullscreenDesktop)
+}
+
+// Returns the event pump, the canvas, registry of the connected gamepads and
+// the drawable size of the window in pixels.
+// A missing gamepad is not an error, pads plugged in later are picked up by
+// the registry.
+pub fn sdl_init_with(
+    config: &WindowConfig,
+    gamepad: bool,
+) -> Result<(EventPump, Canvas<Window>, GamepadRegistry, u32, u32), Error> {
+    let sdl_context = sdl2::init()?;
 
-    let mut controller = None;
+    let mut subsystem = None;
     if gamepad {
-        let r = sdl_joy_init(sdl_context.clone());
-        if r.is_err() {
-            tracing::error!("error initializing gamepad");
-        } else {
-            controller = Some(r.unwrap());
+        match sdl_context.game_controller() {
+            Ok(s) => subsystem = Some(s),
+            Err(e) => tracing::error!("error initializing gamepad: {e}"),
         }
     }
-    let event_pump = sdl_context.event_pump().unwrap();
+    let gamepads = GamepadRegistry::new(subsystem);
+    let event_pump = sdl_context.event_pump()?;
+
+    let video_subsystem = sdl_context.video()?;
+
+    let mut builder = video_subsystem.window(&config.title, config.width, config.height);
+    match video_subsystem.display_bounds(config.display) {
+        Ok(bounds) => {
+            // center on the requested display, fullscreen modes use the display
+            // the window is positioned on
+            let x = bounds.x() + (bounds.width() as i32 - config.width as i32) / 2;
+            let y = bounds.y() + (bounds.height() as i32 - config.height as i32) / 2;
+            builder.position(x, y);
+        }
+        Err(e) => {
+            tracing::warn!("display {}: {e}", config.display);
+            builder.position_centered();
+        }
+    }
+    builder.set_window_flags(window_flags(config));
+    let window = builder
+        .build()
+        .map_err(|e| Error::Sdl(format!("could not create window: {e}")))?;
+    tracing::info!(
+        "window {:?} {}x{} on display {}",
+        config.mode,
+        config.width,
+        config.height,
+        config.display
+    );
 
-    let video_subsystem = sdl_context.video().unwrap();
+    let mut canvas = window.into_canvas();
+    if config.vsync {
+        canvas = canvas.present_vsync();
+    }
+    let canvas = canvas
+        .build()
+        .map_err(|e| Error::Sdl(format!("could not make a canvas: {e}")))?;
+
+    let drawable_size = canvas
+        .output_size()
+        .unwrap_or(canvas.window().drawable_size());
+    tracing::info!("drawable_size={:?}", drawable_size);
+    Ok((
+        event_pump,
+        canvas,
+        gamepads,
+        drawable_size.0,
+        drawable_size.1,
+    ))
+}
 
-    let (w, h) = if let Ok(video_mode) = video_subsystem.current_display_mode(0) {
-        (video_mode.w as u32, video_mode.h as u32)
-    } else {
-        (width, height)
-    };
-    tracing::info!("Using video mode {w}x{h}");
+// Initializes SDL without a display: the dummy video driver provides a hidden
+// window and the canvas uses the software renderer, so the frame can be read
+// back with `canvas.read_pixels`. Useful for CI and golden-image tests.
+pub fn sdl_init_headless(width: u32, height: u32) -> Result<(EventPump, Canvas<Window>), Error> {
+    // a hint instead of the environment variable, the environment belongs
+    // to the application
+    if !sdl2::hint::set("SDL_VIDEODRIVER", "dummy") {
+        tracing::warn!("can't select the dummy video driver");
+    }
+    let sdl_context = sdl2::init()?;
+    let event_pump = sdl_context.event_pump()?;
+    let video_subsystem = sdl_context.video()?;
 
     let window = video_subsystem
-        .window("Rustvaders", width, height)
-        .fullscreen_desktop()
-        .position_centered()
+        .window("rust-sdl-ui (headless)", width, height)
+        .hidden()
         .build()
-        .expect("could not initialize video subsystem");
+        .map_err(|e| Error::Sdl(format!("could not create dummy window: {e}")))?;
 
-    let border_size = window.drawable_size();
     let canvas = window
         .into_canvas()
+        .software()
         .build()
-        .expect("could not make a canvas");
+        .map_err(|e| Error::Sdl(format!("could not make a software canvas: {e}")))?;
 
-    tracing::info!("border_size={:?}", border_size);
-    (event_pump, canvas, controller, border_size.0, border_size.1)
+    tracing::info!("headless canvas {width}x{height}");
+    Ok((event_pump, canvas))
 }
 
-pub fn sdl_joy_init(sdl_context: Sdl) -> Result<GameController, String> {
-    let game_controller_subsystem = sdl_context.game_controller()?;
+pub fn sdl_joy_init(sdl_context: Sdl) -> Result<GameController, Error> {
+    let game_controller_subsystem = sdl_context.game_controller().map_err(Error::Gamepad)?;
 
     let available = game_controller_subsystem
         .num_joysticks()
-        .map_err(|e| format!("can't enumerate joysticks: {}", e))?;
+        .map_err(|e| Error::Gamepad(format!("can't enumerate joysticks: {}", e)))?;
 
     tracing::info!("{} joysticks available", available);
 
@@ -96,34 +276,53 @@ pub fn sdl_joy_init(sdl_context: Sdl) -> Result<GameController, String> {
                 }
             }
         })
-        .expect("Couldn't open any controller");
+        .ok_or_else(|| Error::Gamepad("couldn't open any controller".to_owned()))?;
 
     tracing::info!("Controller mapping: {}", controller.mapping());
     tracing::info!("is attached: {}", controller.attached());
     Ok(controller)
 }
 
-pub fn sdl_load_textures(canvas: &Canvas<Window>, images: Vec<String>) -> Vec<Texture> {
+pub fn sdl_load_textures(
+    canvas: &Canvas<Window>,
+    images: Vec<String>,
+) -> Result<Vec<Texture>, Error> {
     let mut textures: Vec<Texture> = Vec::new();
     let tc = canvas.texture_creator();
     for img in images.iter() {
-        let tex = tc.load_texture(img).unwrap();
-        textures.push(tex);
+        match assets::load_texture(&tc, img) {
+            Ok(tex) => textures.push(tex),
+            Err(e) => {
+                // do not leak the textures loaded so far
+                for tex in textures {
+                    unsafe { tex.destroy() };
+                }
+                return Err(e);
+            }
+        }
     }
-    textures
+    Ok(textures)
 }
 
 pub fn sdl_render_rect_with_caption(
     canvas: &mut Canvas<Window>,
     text: &str,
-    ttf: &mut sdl2::ttf::Sdl2TtfContext,
+    fonts: &mut FontManager,
     font_size: u16,
     (x1, y1): (i32, i32),
     (x2, y2): (i32, i32),
     text_color: Vec4,
     color: Vec4,
 ) {
-    sdl_text(ttf, canvas, text, font_size, text_color, (x1 + x2) / 2, y1);
+    sdl_text(
+        fonts,
+        canvas,
+        text,
+        font_size,
+        text_color,
+        (x1 + x2) / 2,
+        y1,
+    );
     sdl_render_rect(canvas, (x1, y1), (x2, y2), color);
 }
 
@@ -144,18 +343,22 @@ pub fn sdl_render_rect(
     let _ = canvas.draw_rect(rect);
 }
 
-pub fn sdl_render_tex(canvas: &mut Canvas<Window>, texture: &Texture, x: i32, y: i32) {
+pub fn sdl_render_tex(
+    canvas: &mut Canvas<Window>,
+    texture: &Texture,
+    x: i32,
+    y: i32,
+) -> Result<(), Error> {
     let h = texture.query().height;
     let w = texture.query().width;
 
     let sprite = Rect::new(0, 0, w, h);
-    canvas
-        .copy(
-            texture,
-            sprite,
-            Rect::from_center(Point::new(x, y), sprite.width(), sprite.height()),
-        )
-        .unwrap();
+    canvas.copy(
+        texture,
+        sprite,
+        Rect::from_center(Point::new(x, y), sprite.width(), sprite.height()),
+    )?;
+    Ok(())
 }
 
 pub fn sdl_scale_tex(
@@ -165,15 +368,14 @@ pub fn sdl_scale_tex(
     y: i32,
     w: i32,
     h: i32,
-) {
+) -> Result<(), Error> {
     let sprite = Rect::new(0, 0, texture.query().width, texture.query().height);
-    canvas
-        .copy(
-            texture,
-            sprite,
-            Rect::from_center(Point::new(x, y), w as u32, h as u32),
-        )
-        .unwrap();
+    canvas.copy(
+        texture,
+        sprite,
+        Rect::from_center(Point::new(x, y), w.max(1) as u32, h.max(1) as u32),
+    )?;
+    Ok(())
 }
 
 pub fn sdl_clear(canvas: &mut Canvas<Window>, r: u8, g: u8, b: u8) {
@@ -181,44 +383,15 @@ pub fn sdl_clear(canvas: &mut Canvas<Window>, r: u8, g: u8, b: u8) {
     canvas.clear();
 }
 
-fn text2tex(
-    ttf: &mut sdl2::ttf::Sdl2TtfContext,
-    canvas: &mut Canvas<Window>,
-    text: &str,
-    font_size: u16,
-    color: RgbColor,
-    x: i32,
-    y: i32,
-) -> Option<Texture> {
-    let mut fsize = font_size;
-    if fsize == 0 {
-        fsize = 24;
-    }
-    let font = ttf.load_font(FONT_PATH.clone(), fsize);
-    if font.is_err() {
-        return None;
-    }
-
-    let tc = canvas.texture_creator();
-
-    // let val = vert_speed as i32;
-    let font = font.unwrap();
-    //font.set_style(sdl2::ttf::FontStyle::BOLD);
-    let surface = font.render(text).blended(color.to_sdl_rgba());
-    if surface.is_err() {
-        return None;
-    }
-    let surface = surface.unwrap();
-    let texture = tc.create_texture_from_surface(&surface);
-    if texture.is_err() {
-        return None;
-    }
-    let texture = texture.unwrap();
-    Some(texture)
+// Default font of the given size, 0 selects 24pt. The monospace family can
+// be overridden with the `SDL_UI_FONT` env variable.
+pub fn default_font(font_size: u16) -> FontKey {
+    let size = if font_size == 0 { 24 } else { font_size };
+    FontKey::new(font::MONOSPACE, size, FontStyle::NORMAL)
 }
 
 pub fn sdl_text(
-    ttf: &mut sdl2::ttf::Sdl2TtfContext,
+    fonts: &mut FontManager,
     canvas: &mut Canvas<Window>,
     text: &str,
     font_size: u16,
@@ -226,13 +399,16 @@ pub fn sdl_text(
     x: i32,
     y: i32,
 ) {
-    if let Some(texture) = text2tex(ttf, canvas, text, font_size, color, x, y) {
-        sdl_render_tex(canvas, &texture, x, y);
+    let r = fonts
+        .text(canvas, text, &default_font(font_size), &color)
+        .and_then(|texture| sdl_render_tex(canvas, texture, x, y));
+    if let Err(e) = r {
+        fonts.report(&e);
     }
 }
 
 pub fn sdl_scale_text(
-    ttf: &mut sdl2::ttf::Sdl2TtfContext,
+    fonts: &mut FontManager,
     canvas: &mut Canvas<Window>,
     text: &str,
     font_size: u16,
@@ -242,8 +418,11 @@ pub fn sdl_scale_text(
     w: i32,
     h: i32,
 ) {
-    if let Some(texture) = text2tex(ttf, canvas, text, font_size, color, x, y) {
-        sdl_scale_tex(canvas, &texture, x, y, w, h);
+    let r = fonts
+        .text(canvas, text, &default_font(font_size), &color)
+        .and_then(|texture| sdl_scale_tex(canvas, texture, x, y, w, h));
+    if let Err(e) = r {
+        fonts.report(&e);
     }
 }
 
@@ -308,3 +487,47 @@ pub fn draw_horizontal_gradient_box(
         at += a_s;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use sdl2::sys::SDL_WindowFlags;
+
+    use super::{fullscreen_config, scale_factor, window_flags, WindowConfig, WindowMode};
+
+    #[test]
+    fn config_window_flags() {
+        assert_eq!(0, window_flags(&WindowConfig::new(1280, 720)));
+        assert_eq!(
+            SDL_WindowFlags::SDL_WINDOW_FULLSCREEN_DESKTOP as u32,
+            window_flags(&fullscreen_config(1280, 720))
+        );
+        let config = WindowConfig::new(1280, 720)
+            .mode(WindowMode::Borderless)
+            .resizable(true)
+            .high_dpi(true);
+        assert_eq!(
+            SDL_WindowFlags::SDL_WINDOW_BORDERLESS as u32
+                | SDL_WindowFlags::SDL_WINDOW_RESIZABLE as u32
+                | SDL_WindowFlags::SDL_WINDOW_ALLOW_HIGHDPI as u32,
+            window_flags(&config)
+        );
+        assert_eq!(
+            SDL_WindowFlags::SDL_WINDOW_FULLSCREEN as u32,
+            window_flags(
+                &config
+                    .mode(WindowMode::Fullscreen)
+                    .resizable(false)
+                    .high_dpi(false)
+            )
+        );
+    }
+
+    #[test]
+    fn hidpi_scale_factor() {
+        assert_eq!((1.0, 1.0), scale_factor((1280, 720), (1280, 720)));
+        assert_eq!((2.0, 2.0), scale_factor((1280, 720), (2560, 1440)));
+        assert_eq!((1.5, 1.5), scale_factor((960, 540), (1440, 810)));
+        // minimized window
+        assert_eq!((1.0, 1.0), scale_factor((0, 0), (0, 0)));
+    }
+}
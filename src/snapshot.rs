@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use sdl2::{
+    image::{LoadSurface, SaveSurface},
+    pixels::PixelFormatEnum,
+    render::Canvas,
+    surface::Surface,
+    video::Window,
+};
+
+use crate::utils;
+
+// RGBA image read back from a canvas or loaded from a png file.
+// Pixels are stored row by row, 4 bytes per pixel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ImageDiff {
+    // number of pixels where any channel differs by more than the tolerance
+    pub mismatched: usize,
+    // biggest channel difference found in the image
+    pub max_delta: u8,
+}
+
+impl RgbaImage {
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn capture(canvas: &Canvas<Window>) -> Result<Self, String> {
+        let (width, height) = canvas.output_size()?;
+        let pixels = canvas.read_pixels(None, PixelFormatEnum::RGBA32)?;
+        Ok(Self::new(width, height, pixels))
+    }
+
+    pub fn load_png<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let surface = Surface::from_file(path)?;
+        let surface = surface.convert_format(PixelFormatEnum::RGBA32)?;
+        let (width, height) = (surface.width(), surface.height());
+        let pitch = surface.pitch() as usize;
+        let pixels = surface.with_lock(|data| {
+            let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+            for y in 0..height as usize {
+                let row = y * pitch;
+                pixels.extend_from_slice(&data[row..row + width as usize * 4]);
+            }
+            pixels
+        });
+        Ok(Self::new(width, height, pixels))
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let mut pixels = self.pixels.clone();
+        let surface = Surface::from_data(
+            &mut pixels,
+            self.width,
+            self.height,
+            self.width * 4,
+            PixelFormatEnum::RGBA32,
+        )?;
+        surface.save(path)
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let offset = ((y * self.width + x) * 4) as usize;
+        [
+            self.pixels[offset],
+            self.pixels[offset + 1],
+            self.pixels[offset + 2],
+            self.pixels[offset + 3],
+        ]
+    }
+}
+
+// Compares two images pixel by pixel, a pixel matches when none of its
+// channels differs by more than `tolerance`.
+pub fn compare(
+    expected: &RgbaImage,
+    actual: &RgbaImage,
+    tolerance: u8,
+) -> Result<ImageDiff, String> {
+    if expected.width != actual.width || expected.height != actual.height {
+        return Err(format!(
+            "image size differs: expected {}x{}, got {}x{}",
+            expected.width, expected.height, actual.width, actual.height
+        ));
+    }
+    let mut diff = ImageDiff {
+        mismatched: 0,
+        max_delta: 0,
+    };
+    for (e, a) in expected.pixels.chunks(4).zip(actual.pixels.chunks(4)) {
+        let delta = e
+            .iter()
+            .zip(a.iter())
+            .map(|(e, a)| e.abs_diff(*a))
+            .max()
+            .unwrap_or(0);
+        diff.max_delta = diff.max_delta.max(delta);
+        if delta > tolerance {
+            diff.mismatched += 1;
+        }
+    }
+    Ok(diff)
+}
+
+// Compares canvas content with the golden png image.
+//
+// Set `SDL_UI_BLESS=1` to record the golden images, e.g. after an intended
+// visual change. A missing golden image fails the test otherwise, so a run
+// without them can't pass unnoticed. On mismatch or a missing image the
+// rendered one is stored next to the golden one with `.actual.png` suffix.
+pub fn assert_golden(canvas: &Canvas<Window>, golden: &str, tolerance: u8, max_mismatched: usize) {
+    let actual = RgbaImage::capture(canvas).expect("can't read canvas pixels");
+    let actual_file = format!("{golden}.actual.png");
+    if utils::get_env("SDL_UI_BLESS", "0") == "1" {
+        tracing::warn!(golden, "recording golden image");
+        actual.save_png(golden).expect("can't save golden image");
+        return;
+    }
+    if !Path::new(golden).exists() {
+        let _ = actual.save_png(&actual_file);
+        panic!(
+            "golden image {golden} is missing, see {actual_file} and record it with SDL_UI_BLESS=1"
+        );
+    }
+
+    let expected = RgbaImage::load_png(golden).expect("can't load golden image");
+    let diff = compare(&expected, &actual, tolerance);
+    let failure = match diff {
+        Ok(ref d) if d.mismatched <= max_mismatched => None,
+        Ok(d) => Some(format!(
+            "{} pixels differ (max delta {}), allowed {}",
+            d.mismatched, d.max_delta, max_mismatched
+        )),
+        Err(e) => Some(e),
+    };
+    if let Some(msg) = failure {
+        let _ = actual.save_png(&actual_file);
+        panic!("golden image {golden} mismatch: {msg}, see {actual_file}");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compare, ImageDiff, RgbaImage};
+
+    #[test]
+    fn compare_within_tolerance() {
+        let a = RgbaImage::new(2, 1, vec![10, 20, 30, 255, 0, 0, 0, 255]);
+        let b = RgbaImage::new(2, 1, vec![12, 20, 30, 255, 0, 0, 9, 255]);
+        assert_eq!(
+            ImageDiff {
+                mismatched: 1,
+                max_delta: 9
+            },
+            compare(&a, &b, 2).unwrap()
+        );
+        assert_eq!(0, compare(&a, &b, 9).unwrap().mismatched);
+    }
+
+    #[test]
+    fn compare_size_mismatch() {
+        let a = RgbaImage::new(1, 1, vec![0, 0, 0, 255]);
+        let b = RgbaImage::new(1, 2, vec![0, 0, 0, 255, 0, 0, 0, 255]);
+        assert!(compare(&a, &b, 0).is_err());
+    }
+}
@@ -4,7 +4,6 @@ use std::{
 };
 
 use sdl2::{
-    image::LoadTexture,
     rect::Rect,
     render::{Canvas, Texture},
     video::Window,
@@ -12,7 +11,7 @@ use sdl2::{
 
 use tracing;
 
-use crate::sdl;
+use crate::{assets, error::Error, sdl};
 
 pub(crate) struct TextureCache {
     lookup: HashMap<String, Vec<TexInfo>>,
@@ -77,36 +76,35 @@ impl TextureCache {
         w: u32,
         h: u32,
         last_modified: Option<u128>,
-    ) -> Result<TexInfo, String> {
+    ) -> Result<TexInfo, Error> {
         let tex = self.get(&name, w, h, last_modified);
         if tex.is_some() {
             return Ok(tex.unwrap());
         }
         let tc = canvas.texture_creator();
-        let src_texture = tc.load_texture(&name)?;
+        let src_texture = assets::load_texture(&tc, &name)?;
         let original_aspect = src_texture.query().width as f32 / src_texture.query().height as f32;
         tracing::info!(name, "pixel format: {:?}", src_texture.query().format);
         let mut dst_texture = tc
-            .create_texture_target(src_texture.query().format, w, h)
-            .expect("can't create texture");
+            .create_texture_target(src_texture.query().format, w.max(1), h.max(1))
+            .map_err(|e| Error::Sdl(format!("load_texture {name}: {e}")))?;
         dst_texture.set_blend_mode(sdl2::render::BlendMode::Blend);
-        let dst = Rect::new(0, 0, w, h);
+        let dst = Rect::new(0, 0, w.max(1), h.max(1));
+        let mut copied = Ok(());
         let result = canvas.with_texture_canvas(&mut dst_texture, |texture_canvas| {
-            texture_canvas
-                .copy(&src_texture, None, dst)
-                .expect("can't copy/scale texture");
+            copied = texture_canvas.copy(&src_texture, None, dst);
         });
-        if result.is_err() {
-            let err_msg = format!("load_texture: {}", result.err().unwrap());
-            tracing::error!(err_msg);
-            return Err(err_msg);
+        unsafe { src_texture.destroy() };
+        if let Err(e) = result.map_err(|e| e.to_string()).and(copied) {
+            unsafe { dst_texture.destroy() };
+            return Err(Error::Sdl(format!("load_texture {name}: {e}")));
         }
 
         let tex_info = TexInfo {
             texture: Arc::new(RwLock::new(dst_texture)),
             w,
             h,
-            last_modified: last_modified.unwrap_or(0),
+            last_modified: last_modified.unwrap_or_else(|| assets::modified(&name)),
             original_aspect,
         };
 
@@ -120,13 +118,52 @@ impl TextureCache {
 
         return Ok(tex);
     }
+
+    // Drops textures whose image files changed since they were loaded,
+    // they get loaded again on the next `load_texture` call.
+    pub(crate) fn invalidate_modified(&mut self) {
+        self.lookup.retain(|name, tex_infos| {
+            let modified = assets::modified(name);
+            let (keep, stale): (Vec<TexInfo>, Vec<TexInfo>) = tex_infos
+                .drain(..)
+                .partition(|t| t.last_modified == modified);
+            if !stale.is_empty() {
+                tracing::info!(name, "texture modified, reloading");
+            }
+            for tex in stale {
+                tex.destroy();
+            }
+            *tex_infos = keep;
+            !tex_infos.is_empty()
+        });
+    }
+
+    pub(crate) fn clear(&mut self) {
+        for (_, tex_infos) in self.lookup.drain() {
+            for tex in tex_infos {
+                tex.destroy();
+            }
+        }
+    }
 }
 
 impl TexInfo {
-    pub(crate) fn render(&self, canvas: &mut Canvas<sdl2::video::Window>, x: i32, y: i32) {
+    fn destroy(self) {
+        if let Ok(lock) = Arc::try_unwrap(self.texture) {
+            if let Ok(texture) = lock.into_inner() {
+                unsafe { texture.destroy() };
+            }
+        }
+    }
+
+    pub(crate) fn render(
+        &self,
+        canvas: &mut Canvas<sdl2::video::Window>,
+        x: i32,
+        y: i32,
+    ) -> Result<(), Error> {
         let g = self.texture.read().unwrap();
-        sdl::sdl_render_tex(canvas, &g, x, y);
-        drop(g);
+        sdl::sdl_render_tex(canvas, &g, x, y)
     }
 
     pub(crate) fn render_rot(
@@ -135,14 +172,11 @@ impl TexInfo {
         x: i32,
         y: i32,
         angle: f32,
-    ) {
+    ) -> Result<(), Error> {
         let g = self.texture.read().unwrap();
         // tracing::info!("pixel format: {:?}", g.query().format);
         let dst = Rect::new(x - self.w as i32 / 2, y - self.h as i32 / 2, self.w, self.h);
-        let r = canvas.copy_ex(&g, None, dst, angle as f64, None, false, false);
-        if r.is_err() {
-            tracing::error!("render_rot: {}", r.err().unwrap());
-        }
-        drop(g);
+        canvas.copy_ex(&g, None, dst, angle as f64, None, false, false)?;
+        Ok(())
     }
 }
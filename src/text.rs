@@ -0,0 +1,430 @@
+use sdl2::{rect::Rect, render::Canvas, ttf::FontStyle, video::Window};
+
+use crate::{
+    color::{self, RgbColor},
+    font::{self, FontKey, FontManager},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VAlign {
+    Top,
+    Middle,
+    Bottom,
+}
+
+// Part of the text drawn with its own color, the style color is used otherwise.
+#[derive(Clone, Debug)]
+pub struct Span {
+    pub text: String,
+    pub color: Option<RgbColor>,
+}
+
+impl Span {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_owned(),
+            color: None,
+        }
+    }
+
+    pub fn colored(text: &str, color: RgbColor) -> Self {
+        Self {
+            text: text.to_owned(),
+            color: Some(color),
+        }
+    }
+}
+
+// How the text is laid out inside the rectangle:
+//
+// ```
+// let style = TextStyle::new(20)
+//     .bold()
+//     .align(HAlign::Left, VAlign::Top)
+//     .wrap(true)
+//     .outline(color::BLACK.clone(), 1);
+// text::draw_text(fonts, canvas, &[Span::new("ALT 120 m")], &style, area);
+// ```
+#[derive(Clone, Debug)]
+pub struct TextStyle {
+    family: String,
+    font_size: u16,
+    font_style: FontStyle,
+    color: RgbColor,
+    halign: HAlign,
+    valign: VAlign,
+    wrap: bool,
+    outline: Option<(RgbColor, i32)>,
+    shadow: Option<(RgbColor, i32, i32)>,
+}
+
+impl TextStyle {
+    pub fn new(font_size: u16) -> Self {
+        Self {
+            family: font::MONOSPACE.to_owned(),
+            font_size,
+            font_style: FontStyle::NORMAL,
+            color: color::WHITE.clone(),
+            halign: HAlign::Center,
+            valign: VAlign::Middle,
+            wrap: false,
+            outline: None,
+            shadow: None,
+        }
+    }
+
+    // Font family registered on the window, e.g. `font::SANS`.
+    pub fn family(self, family: &str) -> Self {
+        Self {
+            family: family.to_owned(),
+            ..self
+        }
+    }
+
+    pub fn bold(self) -> Self {
+        Self {
+            font_style: self.font_style | FontStyle::BOLD,
+            ..self
+        }
+    }
+
+    pub fn italic(self) -> Self {
+        Self {
+            font_style: self.font_style | FontStyle::ITALIC,
+            ..self
+        }
+    }
+
+    pub fn underline(self) -> Self {
+        Self {
+            font_style: self.font_style | FontStyle::UNDERLINE,
+            ..self
+        }
+    }
+
+    pub fn color(self, color: RgbColor) -> Self {
+        Self { color, ..self }
+    }
+
+    pub fn align(self, halign: HAlign, valign: VAlign) -> Self {
+        Self {
+            halign,
+            valign,
+            ..self
+        }
+    }
+
+    // Breaks lines between words to fit the width of the rectangle.
+    pub fn wrap(self, wrap: bool) -> Self {
+        Self { wrap, ..self }
+    }
+
+    // Outline of given width in pixels, keeps the text readable over video.
+    pub fn outline(self, color: RgbColor, width: i32) -> Self {
+        Self {
+            outline: Some((color, width)),
+            ..self
+        }
+    }
+
+    pub fn shadow(self, color: RgbColor, dx: i32, dy: i32) -> Self {
+        Self {
+            shadow: Some((color, dx, dy)),
+            ..self
+        }
+    }
+
+    pub(crate) fn font(&self) -> FontKey {
+        let size = if self.font_size == 0 {
+            24
+        } else {
+            self.font_size
+        };
+        FontKey::new(&self.family, size, self.font_style)
+    }
+}
+
+// Continuous piece of a line drawn with one color, `span` indexes the spans.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Run {
+    pub(crate) text: String,
+    pub(crate) span: usize,
+}
+
+enum Token {
+    Word(String, usize),
+    Space(String, usize),
+    Newline,
+}
+
+fn tokenize(spans: &[Span]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (idx, span) in spans.iter().enumerate() {
+        for (n, line) in span.text.split('\n').enumerate() {
+            if n > 0 {
+                tokens.push(Token::Newline);
+            }
+            let mut word = String::new();
+            for c in line.chars() {
+                if c.is_whitespace() {
+                    if !word.is_empty() {
+                        tokens.push(Token::Word(std::mem::take(&mut word), idx));
+                    }
+                    tokens.push(Token::Space(c.to_string(), idx));
+                } else {
+                    word.push(c);
+                }
+            }
+            if !word.is_empty() {
+                tokens.push(Token::Word(word, idx));
+            }
+        }
+    }
+    tokens
+}
+
+fn push_run(line: &mut Vec<Run>, text: &str, span: usize) {
+    match line.last_mut() {
+        Some(run) if run.span == span => run.text.push_str(text),
+        _ => line.push(Run {
+            text: text.to_owned(),
+            span,
+        }),
+    }
+}
+
+fn trim_end(line: &mut Vec<Run>) {
+    while let Some(run) = line.last_mut() {
+        let trimmed = run.text.trim_end().len();
+        run.text.truncate(trimmed);
+        if !run.text.is_empty() {
+            break;
+        }
+        line.pop();
+    }
+}
+
+// Splits the spans into lines. Explicit line breaks are always honored,
+// with `max_width` the lines are also broken between words. Words wider than
+// `max_width` are kept on their own line.
+pub(crate) fn layout_lines(
+    spans: &[Span],
+    max_width: Option<u32>,
+    measure: &mut impl FnMut(&str) -> u32,
+) -> Vec<Vec<Run>> {
+    let mut lines = Vec::new();
+    let mut line: Vec<Run> = Vec::new();
+    let mut width = 0;
+    for token in tokenize(spans) {
+        match token {
+            Token::Newline => {
+                trim_end(&mut line);
+                lines.push(std::mem::take(&mut line));
+                width = 0;
+            }
+            Token::Space(s, span) => {
+                // spaces at the start of wrapped lines are dropped
+                if !line.is_empty() || max_width.is_none() {
+                    width += measure(&s);
+                    push_run(&mut line, &s, span);
+                }
+            }
+            Token::Word(w, span) => {
+                let w_width = measure(&w);
+                if let Some(max_width) = max_width {
+                    if width + w_width > max_width && !line.is_empty() {
+                        trim_end(&mut line);
+                        lines.push(std::mem::take(&mut line));
+                        width = 0;
+                    }
+                }
+                width += w_width;
+                push_run(&mut line, &w, span);
+            }
+        }
+    }
+    trim_end(&mut line);
+    lines.push(line);
+    lines
+}
+
+fn aligned(align: i32, start: i32, size: i32, content: i32) -> i32 {
+    match align {
+        -1 => start,
+        1 => start + size - content,
+        _ => start + (size - content) / 2,
+    }
+}
+
+// Draws the spans inside the area according to the style.
+pub fn draw_text(
+    fonts: &mut FontManager,
+    canvas: &mut Canvas<Window>,
+    spans: &[Span],
+    style: &TextStyle,
+    area: Rect,
+) {
+    let font = style.font();
+    // a missing font was reported by the font manager already
+    let Ok(line_height) = fonts.font(&font, "").map(|f| f.recommended_line_spacing()) else {
+        return;
+    };
+    let max_width = if style.wrap { Some(area.width()) } else { None };
+    let mut measure = |s: &str| fonts.text_width(&font, s);
+    let lines = layout_lines(spans, max_width, &mut measure);
+    let widths: Vec<i32> = lines
+        .iter()
+        .map(|line| line.iter().map(|run| measure(&run.text) as i32).sum())
+        .collect();
+
+    let v = match style.valign {
+        VAlign::Top => -1,
+        VAlign::Middle => 0,
+        VAlign::Bottom => 1,
+    };
+    let h = match style.halign {
+        HAlign::Left => -1,
+        HAlign::Center => 0,
+        HAlign::Right => 1,
+    };
+    let total_height = line_height * lines.len() as i32;
+    let mut y = aligned(v, area.y(), area.height() as i32, total_height);
+    for (line, width) in lines.iter().zip(widths) {
+        let mut x = aligned(h, area.x(), area.width() as i32, width);
+        for run in line.iter() {
+            let color = spans[run.span].color.clone().unwrap_or(style.color.clone());
+            x += draw_run(fonts, canvas, &run.text, &font, style, color, x, y);
+        }
+        y += line_height;
+    }
+}
+
+// Draws the run with its outline and shadow, returns the width of the run.
+// Glyphs missing in the font are drawn with the fallback fonts.
+#[allow(clippy::too_many_arguments)]
+fn draw_run(
+    fonts: &mut FontManager,
+    canvas: &mut Canvas<Window>,
+    text: &str,
+    font: &FontKey,
+    style: &TextStyle,
+    color: RgbColor,
+    x: i32,
+    y: i32,
+) -> i32 {
+    let mut width = 0;
+    for piece in fonts.font_runs(font, text) {
+        width += draw_piece(fonts, canvas, &piece, font, style, &color, x + width, y);
+    }
+    width
+}
+
+// Part of the run drawn with a single font.
+#[allow(clippy::too_many_arguments)]
+fn draw_piece(
+    fonts: &mut FontManager,
+    canvas: &mut Canvas<Window>,
+    text: &str,
+    font: &FontKey,
+    style: &TextStyle,
+    color: &RgbColor,
+    x: i32,
+    y: i32,
+) -> i32 {
+    let mut copy = |fonts: &mut FontManager, color: &RgbColor, dx: i32, dy: i32| -> i32 {
+        match fonts.text(canvas, text, font, color) {
+            Ok(texture) => {
+                let q = texture.query();
+                let _ = canvas.copy(texture, None, Rect::new(x + dx, y + dy, q.width, q.height));
+                q.width as i32
+            }
+            Err(e) => {
+                fonts.report(&e);
+                0
+            }
+        }
+    };
+    if let Some((ref c, dx, dy)) = style.shadow {
+        copy(fonts, c, dx, dy);
+    }
+    if let Some((ref c, width)) = style.outline {
+        for (dx, dy) in [
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ] {
+            copy(fonts, c, dx * width, dy * width);
+        }
+    }
+    copy(fonts, color, 0, 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{layout_lines, Run, Span};
+
+    // every character is 10 pixels wide
+    fn measure(s: &str) -> u32 {
+        s.chars().count() as u32 * 10
+    }
+
+    fn texts(lines: &[Vec<Run>]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|l| l.iter().map(|r| r.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn wrap_words() {
+        let lines = layout_lines(&[Span::new("wind 12 knots NW")], Some(100), &mut measure);
+        assert_eq!(vec!["wind 12", "knots NW"], texts(&lines));
+    }
+
+    #[test]
+    fn explicit_line_breaks() {
+        let lines = layout_lines(&[Span::new("ALT 120\nSPD 14")], None, &mut measure);
+        assert_eq!(vec!["ALT 120", "SPD 14"], texts(&lines));
+    }
+
+    #[test]
+    fn long_word_on_own_line() {
+        let lines = layout_lines(&[Span::new("a verylongword b")], Some(50), &mut measure);
+        assert_eq!(vec!["a", "verylongword", "b"], texts(&lines));
+    }
+
+    #[test]
+    fn spans_keep_colors() {
+        let spans = [Span::new("BAT "), Span::new("12%"), Span::new(" low")];
+        let lines = layout_lines(&spans, None, &mut measure);
+        assert_eq!(
+            vec![
+                Run {
+                    text: "BAT ".to_owned(),
+                    span: 0
+                },
+                Run {
+                    text: "12%".to_owned(),
+                    span: 1
+                },
+                Run {
+                    text: " low".to_owned(),
+                    span: 2
+                },
+            ],
+            lines[0]
+        );
+    }
+}
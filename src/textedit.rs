@@ -0,0 +1,350 @@
+use crate::focus::Direction;
+
+// Single line of text being edited. Positions are in chars, not bytes, so
+// the cursor never ends up inside a multi-byte character.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct TextEdit {
+    text: String,
+    cursor: usize,
+    // other end of the selection, the cursor is the moving end
+    anchor: Option<usize>,
+    // IME composition not committed yet, shown at the cursor
+    composition: String,
+    composition_cursor: usize,
+    max_len: Option<usize>,
+}
+
+impl TextEdit {
+    pub(crate) fn text(&self) -> &str {
+        &self.text
+    }
+
+    // Replaces the text, the cursor goes to the end.
+    pub(crate) fn set_text(&mut self, text: &str) {
+        self.text = String::new();
+        self.cursor = 0;
+        self.anchor = None;
+        self.insert(text);
+    }
+
+    pub(crate) fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.max_len = max_len;
+        if let Some(max) = max_len {
+            let len = self.len();
+            if len > max {
+                self.text = self.text.chars().take(max).collect();
+                self.cursor = self.cursor.min(max);
+                self.anchor = self.anchor.map(|a| a.min(max));
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    fn byte_pos(&self, pos: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(pos)
+            .map(|(i, _)| i)
+            .unwrap_or(self.text.len())
+    }
+
+    // Text before the cursor, used to place the cursor on screen.
+    pub(crate) fn before_cursor(&self) -> &str {
+        &self.text[..self.byte_pos(self.cursor)]
+    }
+
+    // Start and end of the selection, empty selection is none.
+    pub(crate) fn selection(&self) -> Option<(usize, usize)> {
+        let anchor = self.anchor?;
+        match anchor.cmp(&self.cursor) {
+            std::cmp::Ordering::Less => Some((anchor, self.cursor)),
+            std::cmp::Ordering::Greater => Some((self.cursor, anchor)),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    pub(crate) fn selected_text(&self) -> Option<&str> {
+        let (start, end) = self.selection()?;
+        Some(&self.text[self.byte_pos(start)..self.byte_pos(end)])
+    }
+
+    // Text up to the position, to measure where the selection is drawn.
+    pub(crate) fn prefix(&self, pos: usize) -> &str {
+        &self.text[..self.byte_pos(pos)]
+    }
+
+    pub(crate) fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.len();
+    }
+
+    pub(crate) fn delete_selection(&mut self) -> bool {
+        let Some((start, end)) = self.selection() else {
+            self.anchor = None;
+            return false;
+        };
+        let range = self.byte_pos(start)..self.byte_pos(end);
+        self.text.replace_range(range, "");
+        self.cursor = start;
+        self.anchor = None;
+        true
+    }
+
+    // Inserts the text at the cursor, replacing the selection. Line breaks
+    // and other control characters are dropped, and the text is cut at the
+    // maximum length.
+    pub(crate) fn insert(&mut self, s: &str) {
+        self.delete_selection();
+        let room = self.max_len.map(|m| m.saturating_sub(self.len()));
+        let s: String = s
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(room.unwrap_or(usize::MAX))
+            .collect();
+        let at = self.byte_pos(self.cursor);
+        self.text.insert_str(at, &s);
+        self.cursor += s.chars().count();
+    }
+
+    pub(crate) fn backspace(&mut self) {
+        if self.delete_selection() || self.cursor == 0 {
+            return;
+        }
+        let range = self.byte_pos(self.cursor - 1)..self.byte_pos(self.cursor);
+        self.text.replace_range(range, "");
+        self.cursor -= 1;
+    }
+
+    pub(crate) fn delete(&mut self) {
+        if self.delete_selection() || self.cursor >= self.len() {
+            return;
+        }
+        let range = self.byte_pos(self.cursor)..self.byte_pos(self.cursor + 1);
+        self.text.replace_range(range, "");
+    }
+
+    // Moves the cursor to the position, extending the selection when
+    // `select` is set, otherwise dropping it.
+    fn move_to(&mut self, pos: usize, select: bool) {
+        if select {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = pos.min(self.len());
+    }
+
+    pub(crate) fn left(&mut self, select: bool) {
+        // collapsing the selection puts the cursor at its start
+        let pos = match (select, self.selection()) {
+            (false, Some((start, _))) => start,
+            _ => self.cursor.saturating_sub(1),
+        };
+        self.move_to(pos, select);
+    }
+
+    pub(crate) fn right(&mut self, select: bool) {
+        let pos = match (select, self.selection()) {
+            (false, Some((_, end))) => end,
+            _ => self.cursor + 1,
+        };
+        self.move_to(pos, select);
+    }
+
+    pub(crate) fn home(&mut self, select: bool) {
+        self.move_to(0, select);
+    }
+
+    pub(crate) fn end(&mut self, select: bool) {
+        self.move_to(self.len(), select);
+    }
+
+    // Cut of the selection, the text is removed.
+    pub(crate) fn cut(&mut self) -> Option<String> {
+        let cut = self.selected_text()?.to_owned();
+        self.delete_selection();
+        Some(cut)
+    }
+
+    // IME composition in progress, `cursor` is in chars of the composition.
+    pub(crate) fn set_composition(&mut self, text: &str, cursor: usize) {
+        self.composition = text.to_owned();
+        self.composition_cursor = cursor.min(text.chars().count());
+    }
+
+    pub(crate) fn composition(&self) -> &str {
+        &self.composition
+    }
+
+    // Composition text before its cursor.
+    pub(crate) fn composition_before_cursor(&self) -> &str {
+        let end = self
+            .composition
+            .char_indices()
+            .nth(self.composition_cursor)
+            .map(|(i, _)| i)
+            .unwrap_or(self.composition.len());
+        &self.composition[..end]
+    }
+}
+
+// Key of the on-screen keyboard.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Key {
+    Char(char),
+    Shift,
+    Space,
+    Backspace,
+    Left,
+    Right,
+    Done,
+}
+
+impl Key {
+    pub(crate) fn label(&self) -> String {
+        match self {
+            Key::Char(c) => c.to_string(),
+            Key::Shift => "SHIFT".to_owned(),
+            Key::Space => "SPACE".to_owned(),
+            Key::Backspace => "DEL".to_owned(),
+            Key::Left => "<".to_owned(),
+            Key::Right => ">".to_owned(),
+            Key::Done => "DONE".to_owned(),
+        }
+    }
+}
+
+const ROWS: [&str; 4] = ["1234567890", "qwertyuiop", "asdfghjkl-", "zxcvbnm_.@"];
+const SHIFTED_ROWS: [&str; 4] = ["!#$%&*()+=", "QWERTYUIOP", "ASDFGHJKL:", "ZXCVBNM,/?"];
+const BOTTOM_ROW: [Key; 6] = [
+    Key::Shift,
+    Key::Space,
+    Key::Left,
+    Key::Right,
+    Key::Backspace,
+    Key::Done,
+];
+
+// Key grid of the on-screen keyboard moved around with the D-pad. Shift
+// applies to the next typed character only.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Keyboard {
+    row: usize,
+    col: usize,
+    shift: bool,
+}
+
+impl Keyboard {
+    pub(crate) fn rows(&self) -> Vec<Vec<Key>> {
+        let chars = if self.shift { SHIFTED_ROWS } else { ROWS };
+        let mut rows: Vec<Vec<Key>> = chars
+            .iter()
+            .map(|r| r.chars().map(Key::Char).collect())
+            .collect();
+        rows.push(BOTTOM_ROW.to_vec());
+        rows
+    }
+
+    pub(crate) fn selected(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+
+    pub(crate) fn is_shift(&self) -> bool {
+        self.shift
+    }
+
+    // Moves the selection, wrapping around at the edges. Moving between rows
+    // of different length keeps the horizontal position.
+    pub(crate) fn navigate(&mut self, dir: Direction) {
+        let rows = self.rows();
+        let len = rows[self.row].len();
+        match dir {
+            Direction::Left => self.col = (self.col + len - 1) % len,
+            Direction::Right => self.col = (self.col + 1) % len,
+            Direction::Up | Direction::Down => {
+                let center = (self.col as f32 + 0.5) / len as f32;
+                self.row = match dir {
+                    Direction::Up => (self.row + rows.len() - 1) % rows.len(),
+                    _ => (self.row + 1) % rows.len(),
+                };
+                let len = rows[self.row].len();
+                self.col = ((center * len as f32) as usize).min(len - 1);
+            }
+        }
+    }
+
+    // Selects the key touched on the screen.
+    pub(crate) fn select(&mut self, row: usize, col: usize) {
+        let rows = self.rows();
+        self.row = row.min(rows.len() - 1);
+        self.col = col.min(rows[self.row].len() - 1);
+    }
+
+    // Presses the selected key. Shift is handled here, the other keys are
+    // returned to be applied to the text.
+    pub(crate) fn press(&mut self) -> Key {
+        let key = self.rows()[self.row][self.col];
+        match key {
+            Key::Shift => self.shift = !self.shift,
+            Key::Char(_) => self.shift = false,
+            _ => {}
+        }
+        key
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Key, Keyboard, TextEdit};
+    use crate::focus::Direction;
+
+    #[test]
+    fn edit_with_selection() {
+        let mut edit = TextEdit::default();
+        edit.set_text("drône");
+        edit.left(false);
+        edit.left(true);
+        edit.left(true);
+        assert_eq!(Some("ôn"), edit.selected_text());
+        edit.insert("o");
+        assert_eq!("droe", edit.text());
+        assert_eq!("dro", edit.before_cursor());
+        edit.backspace();
+        edit.home(false);
+        edit.delete();
+        assert_eq!("re", edit.text());
+        edit.select_all();
+        assert_eq!(Some("re".to_owned()), edit.cut());
+        edit.set_max_len(Some(4));
+        edit.insert("mission\n1");
+        assert_eq!("miss", edit.text());
+    }
+
+    #[test]
+    fn keyboard_navigation() {
+        let mut kb = Keyboard::default();
+        kb.navigate(Direction::Left);
+        assert_eq!((0, 9), kb.selected());
+        // from the right end of the last letter row to DONE
+        kb.navigate(Direction::Up);
+        assert_eq!((4, 5), kb.selected());
+        kb.navigate(Direction::Down);
+        kb.navigate(Direction::Down);
+        assert_eq!((1, 9), kb.selected());
+        kb.navigate(Direction::Up);
+        kb.navigate(Direction::Up);
+        kb.navigate(Direction::Left);
+        kb.navigate(Direction::Left);
+        kb.navigate(Direction::Left);
+        kb.navigate(Direction::Left);
+        kb.navigate(Direction::Left);
+        assert_eq!(Key::Shift, kb.press());
+        assert!(kb.is_shift());
+        kb.navigate(Direction::Up);
+        assert_eq!(Key::Char('Z'), kb.press());
+        assert!(!kb.is_shift());
+    }
+}
@@ -37,6 +37,16 @@ pub fn now_msecs() -> u128 {
     tm.as_millis()
 }
 
+// file modification time in ms since epoch, 0 when not available
+pub(crate) fn file_mtime(path: &str) -> u128 {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|tm| tm.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
 pub fn alloc_vec(size: usize) -> Vec<u8> {
     let mut v = Vec::with_capacity(size);
     unsafe {
@@ -78,8 +88,10 @@ impl DirectoryReader {
             if !meta.is_file() {
                 continue;
             }
+            // files modified in the future, e.g. copied from a device with
+            // a wrong clock, count as just modified
             let modfied_tm = if let Ok(tm) = meta.modified() {
-                tm.elapsed().expect("elapsed time").as_millis()
+                tm.elapsed().map(|d| d.as_millis()).unwrap_or(0)
             } else {
                 0
             };
@@ -1,72 +1,417 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use openh264::formats::YUVSource;
+
+use crate::{
+    error::Error,
+    h264::{SliceHeader, Sps},
+};
 
 #[derive(PartialEq, Debug)]
 pub enum StreamAction {
     CallNext,
     ReadMore,
-    ProcessPacket(Vec<u8>),
 }
 
-// NalParser parser NAL marks (0, 0, 1) from the byte stream
-// It deals with cross-boundary checks when frame is partially
-// read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NalType {
+    Slice,
+    Idr,
+    Sei,
+    Sps,
+    Pps,
+    AccessUnitDelimiter,
+    Other(u8),
+}
+
+impl From<u8> for NalType {
+    fn from(t: u8) -> Self {
+        match t {
+            1 => NalType::Slice,
+            5 => NalType::Idr,
+            6 => NalType::Sei,
+            7 => NalType::Sps,
+            8 => NalType::Pps,
+            9 => NalType::AccessUnitDelimiter,
+            t => NalType::Other(t),
+        }
+    }
+}
+
+// NAL unit borrowed from the parser buffer, including its start code.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Nal<'a> {
+    data: &'a [u8],
+    // length of the start code, 3 or 4
+    start_code: usize,
+}
+
+impl<'a> Nal<'a> {
+    // Unit with the start code, as passed to the decoder.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    // Unit without the start code, starts with the header byte.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.data[self.start_code..]
+    }
+
+    pub fn nal_type(&self) -> NalType {
+        NalType::from(self.payload()[0] & 0x1f)
+    }
+
+    // 0 for units not used as reference, e.g. disposable frames.
+    pub fn ref_idc(&self) -> u8 {
+        (self.payload()[0] >> 5) & 0x3
+    }
+
+    // Payload after the header with the emulation prevention bytes removed.
+    pub fn rbsp(&self) -> Vec<u8> {
+        remove_emulation_prevention(&self.payload()[1..])
+    }
+}
+
+// Drops the 3 of every 0, 0, 3 sequence, the encoder inserts it so the
+// payload never contains a start code.
+pub fn remove_emulation_prevention(data: &[u8]) -> Vec<u8> {
+    let mut rbsp = Vec::with_capacity(data.len());
+    let mut zeros = 0;
+    for &b in data {
+        if zeros >= 2 && b == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if b == 0 { zeros + 1 } else { 0 };
+        rbsp.push(b);
+    }
+    rbsp
+}
+
+// Position of the next 0, 0, 1 at or after `from`.
+fn find_start_code(buffer: &[u8], from: usize) -> Option<usize> {
+    buffer
+        .get(from..)?
+        .windows(3)
+        .position(|w| w == [0, 0, 1])
+        .map(|p| p + from)
+}
+
+// NalParser splits the H.264 Annex-B byte stream into NAL units. Units start
+// with the 3 byte (0, 0, 1) or 4 byte (0, 0, 0, 1) start code, zero bytes
+// before the next start code don't belong to the unit. Start codes split
+// between two reads are found as well.
+//
+// The stream is appended to a single buffer and the units borrow from it.
+// The search continues where the previous one stopped, and consumed bytes
+// are only dropped when they make up most of the buffer.
 pub struct NalParser {
-    leftover_buffer: Vec<u8>,
-    curr_offset: usize,
-    last_nal: Option<usize>,
+    buffer: Vec<u8>,
+    // 0, 0, 1 of the unit in progress
+    nal_start: Option<usize>,
+    // the search for the next start code continues here
+    scan: usize,
+    // bytes before were returned already
+    consumed: usize,
+    end_of_stream: bool,
+}
+
+impl Default for NalParser {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl NalParser {
     pub fn new() -> Self {
         Self {
-            leftover_buffer: Vec::new(),
-            curr_offset: 0,
-            last_nal: None,
+            buffer: Vec::new(),
+            nal_start: None,
+            scan: 0,
+            consumed: 0,
+            end_of_stream: false,
         }
     }
 
-    // This is the main function responsible for read more, handling current buffer,
-    // returning packet for parsing and buffer truncation (from the start)
-    fn get_packet(&mut self) -> StreamAction {
-        if self.leftover_buffer.is_empty() {
-            return StreamAction::ReadMore;
+    pub fn read_stream(&mut self, buffer: &mut Vec<u8>) {
+        if self.consumed > 0 && self.consumed >= self.buffer.len() / 2 {
+            self.buffer.drain(..self.consumed);
+            self.nal_start = self.nal_start.map(|s| s - self.consumed);
+            self.scan -= self.consumed;
+            self.consumed = 0;
         }
+        self.buffer.append(buffer);
+    }
+
+    // No more data comes, the last unit is returned without waiting for the
+    // next start code.
+    pub fn finish(&mut self) {
+        self.end_of_stream = true;
+    }
+
+    // Next complete unit, none when more data has to be read.
+    pub fn next_nal(&mut self) -> Option<Nal<'_>> {
+        loop {
+            let start = match self.nal_start {
+                Some(start) => start,
+                None => match find_start_code(&self.buffer, self.scan) {
+                    Some(start) => {
+                        // data before the first start code can't be decoded
+                        self.nal_start = Some(start);
+                        start
+                    }
+                    None => {
+                        // the start code may continue in the next read
+                        self.scan = self.buffer.len().saturating_sub(2).max(self.scan);
+                        self.consumed = self.scan;
+                        return None;
+                    }
+                },
+            };
+            let next = find_start_code(&self.buffer, self.scan.max(start + 3));
+            let end = match (next, self.end_of_stream) {
+                (Some(next), _) => next,
+                (None, true) => self.buffer.len(),
+                (None, false) => {
+                    self.scan = self.buffer.len().saturating_sub(2).max(start + 3);
+                    return None;
+                }
+            };
+            self.nal_start = next;
+            self.scan = next.map(|n| n + 3).unwrap_or(end);
 
-        if let Some(idx) = self.get_nal_mark() {
-            if let Some(last_offset) = self.last_nal {
-                // Last mark and current mark found, process packet
-                let packet = self.leftover_buffer[last_offset..idx].to_vec();
-                self.leftover_buffer = self.leftover_buffer[idx..].to_vec();
-                self.last_nal = Some(0);
-                self.curr_offset = 2;
-                return StreamAction::ProcessPacket(packet);
+            let payload = start + 3;
+            let mut last = end;
+            while last > payload && self.buffer[last - 1] == 0 {
+                last -= 1;
+            }
+            // the zero of a 4 byte start code was left over by the previous unit
+            let first = if start > self.consumed && self.buffer[start - 1] == 0 {
+                start - 1
             } else {
-                // Try your luck searcing for 0, 0, 1
-                // In case there is no 0, 0, 1 in the next try, you get ReadMore
-                self.curr_offset = idx + 2;
-                self.last_nal = Some(idx);
-                return StreamAction::CallNext;
+                start
+            };
+            self.consumed = last;
+            if last == payload {
+                continue;
             }
-        } else {
-            // No 0, 0, 1 mark here, read more data
-            return StreamAction::ReadMore;
+            return Some(Nal {
+                data: &self.buffer[first..last],
+                start_code: start + 3 - first,
+            });
         }
     }
+}
 
-    fn read_stream(&mut self, buffer: &mut Vec<u8>) {
-        self.leftover_buffer.append(buffer);
+// Decoder configuration of length prefixed H.264, the avcC box of MP4 files
+// (ISO/IEC 14496-15). Units carry the SPS and PPS with their header byte,
+// without the start code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AvcConfig {
+    pub profile: u8,
+    pub level: u8,
+    // size of the length prefix of the units, 1, 2 or 4 bytes
+    pub length_size: usize,
+    pub sps: Vec<Vec<u8>>,
+    pub pps: Vec<Vec<u8>>,
+}
+
+impl AvcConfig {
+    pub fn parse(record: &[u8]) -> Result<Self, Error> {
+        let err = |e: &str| Error::Video(format!("avcC: {e}"));
+        if record.len() < 7 {
+            return Err(err("record too short"));
+        }
+        if record[0] != 1 {
+            return Err(err(&format!("unsupported version {}", record[0])));
+        }
+        let length_size = (record[4] & 0x3) as usize + 1;
+        if length_size == 3 {
+            return Err(err("invalid length size 3"));
+        }
+        let mut pos = 6;
+        let units = |pos: &mut usize, count: usize| {
+            let mut units = Vec::with_capacity(count);
+            for _ in 0..count {
+                let len = record
+                    .get(*pos..*pos + 2)
+                    .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+                    .ok_or_else(|| err("truncated parameter set"))?;
+                let unit = record
+                    .get(*pos + 2..*pos + 2 + len)
+                    .ok_or_else(|| err("truncated parameter set"))?;
+                units.push(unit.to_vec());
+                *pos += 2 + len;
+            }
+            Ok::<_, Error>(units)
+        };
+        let sps = units(&mut pos, (record[5] & 0x1f) as usize)?;
+        let pps_count = *record.get(pos).ok_or_else(|| err("missing PPS count"))? as usize;
+        pos += 1;
+        let pps = units(&mut pos, pps_count)?;
+        Ok(Self {
+            profile: record[1],
+            level: record[3],
+            length_size,
+            sps,
+            pps,
+        })
+    }
+}
+
+// Format of the stream passed to `VideoStreamDecoder::send_stream`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum InputFormat {
+    // units separated by start codes, e.g. raw .h264 files or most drones
+    #[default]
+    AnnexB,
+    // units prefixed by their length, e.g. MP4 samples, with the avcC record
+    // of the stream
+    Avcc(AvcConfig),
+}
+
+// Larger units are taken for a corrupted length prefix, even the IDR frames
+// of 4K streams stay well below.
+const MAX_NAL_SIZE: usize = 8 << 20;
+
+// AvccParser splits the length prefixed stream into NAL units. The units are
+// copied behind a start code for the decoder, openh264 only takes Annex-B.
+// Samples may be split or joined arbitrarily, a unit is returned as soon as
+// all its bytes arrived.
+//
+// A corrupted length prefix would make the parser wait for data which never
+// comes. Lengths over `MAX_NAL_SIZE` or units with the forbidden header bit
+// drop the buffered data, the parsing continues with the next read.
+pub struct AvccParser {
+    length_size: usize,
+    buffer: Vec<u8>,
+    // bytes before were returned already
+    consumed: usize,
+    // last returned unit with the start code
+    nal: Vec<u8>,
+    // data was dropped since the last `take_dropped`
+    dropped: bool,
+}
+
+impl AvccParser {
+    // The parameter sets of the configuration are the first units returned.
+    pub fn new(config: &AvcConfig) -> Self {
+        let mut buffer = Vec::new();
+        for unit in config.sps.iter().chain(config.pps.iter()) {
+            let len = (unit.len() as u32).to_be_bytes();
+            buffer.extend_from_slice(&len[4 - config.length_size..]);
+            buffer.extend_from_slice(unit);
+        }
+        Self {
+            length_size: config.length_size,
+            buffer,
+            consumed: 0,
+            nal: Vec::new(),
+            dropped: false,
+        }
     }
 
-    fn get_nal_mark(&self) -> Option<usize> {
-        for i in self.curr_offset..self.leftover_buffer.len() - 2 {
-            if self.leftover_buffer[i] == 0
-                && self.leftover_buffer[i + 1] == 0
-                && self.leftover_buffer[i + 2] == 1
-            {
-                return Some(i);
+    pub fn read_stream(&mut self, buffer: &mut Vec<u8>) {
+        if self.consumed > 0 && self.consumed >= self.buffer.len() / 2 {
+            self.buffer.drain(..self.consumed);
+            self.consumed = 0;
+        }
+        self.buffer.append(buffer);
+    }
+
+    // Next complete unit, none when more data has to be read.
+    pub fn next_nal(&mut self) -> Option<Nal<'_>> {
+        loop {
+            let rest = &self.buffer[self.consumed..];
+            let prefix = rest.get(..self.length_size)?;
+            let len = prefix.iter().fold(0, |len, b| len << 8 | *b as usize);
+            let forbidden = len > 0 && rest.get(self.length_size).is_some_and(|h| h & 0x80 != 0);
+            if len > MAX_NAL_SIZE || forbidden {
+                tracing::warn!(len, "corrupted unit length, dropping the buffered data");
+                self.buffer.clear();
+                self.consumed = 0;
+                self.dropped = true;
+                return None;
+            }
+            let unit = rest.get(self.length_size..self.length_size + len)?;
+            self.nal.clear();
+            self.nal.extend_from_slice(&[0, 0, 0, 1]);
+            self.nal.extend_from_slice(unit);
+            self.consumed += self.length_size + len;
+            if len > 0 {
+                return Some(Nal {
+                    data: &self.nal,
+                    start_code: 4,
+                });
             }
         }
-        return None;
+    }
+
+    // True once after data was dropped on a corrupted length.
+    pub fn take_dropped(&mut self) -> bool {
+        std::mem::take(&mut self.dropped)
+    }
+}
+
+// Splits the input into units according to the input format.
+enum Splitter {
+    AnnexB(NalParser),
+    Avcc(AvccParser),
+}
+
+impl Splitter {
+    fn read_stream(&mut self, buffer: &mut Vec<u8>) {
+        match self {
+            Splitter::AnnexB(p) => p.read_stream(buffer),
+            Splitter::Avcc(p) => p.read_stream(buffer),
+        }
+    }
+
+    fn finish(&mut self) {
+        // length prefixed units are complete without looking further
+        if let Splitter::AnnexB(p) = self {
+            p.finish();
+        }
+    }
+
+    fn next_nal(&mut self) -> Option<Nal<'_>> {
+        match self {
+            Splitter::AnnexB(p) => p.next_nal(),
+            Splitter::Avcc(p) => p.next_nal(),
+        }
+    }
+
+    fn take_dropped(&mut self) -> bool {
+        match self {
+            Splitter::AnnexB(_) => false,
+            Splitter::Avcc(p) => p.take_dropped(),
+        }
+    }
+}
+
+// Picture size and coding of the stream, known once the SPS arrived or the
+// first picture was decoded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StreamInfo {
+    pub width: u32,
+    pub height: u32,
+    // 0 when the SPS couldn't be parsed, the same for the level
+    pub profile: u8,
+    // level times 10, e.g. 40 for 4.0
+    pub level: u8,
+}
+
+impl From<&Sps> for StreamInfo {
+    fn from(sps: &Sps) -> Self {
+        Self {
+            width: sps.width,
+            height: sps.height,
+            profile: sps.profile_idc,
+            level: sps.level_idc,
+        }
     }
 }
 
@@ -76,76 +421,300 @@ struct VideoStreamDecoderProps {
     frame_no: usize,
     packet_no: usize,
     packet_decode_ok: usize,
+    losses: usize,
+}
+
+// Asks the source for a keyframe, e.g. an RTCP picture loss indication or a
+// drone command. Called from the decoding thread.
+pub type KeyframeRequest = Box<dyn FnMut() + Send>;
+
+// Keyframe requests are repeated at most this often while degraded.
+const KEYFRAME_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+// Watches the stream for lost data. After a loss the pictures refer to
+// frames the decoder never got, the stream is degraded until the next IDR
+// frame, which doesn't depend on earlier ones.
+struct LossTracker {
+    freeze: bool,
+    degraded: bool,
+    // losses before the first IDR are expected, decoding starts anywhere
+    seen_idr: bool,
+    // frame_num of the last reference picture
+    prev_ref_frame_num: Option<u32>,
+    keyframe_request: Option<KeyframeRequest>,
+    last_request: Option<Instant>,
+}
+
+impl LossTracker {
+    fn new() -> Self {
+        Self {
+            freeze: false,
+            degraded: false,
+            seen_idr: false,
+            prev_ref_frame_num: None,
+            keyframe_request: None,
+            last_request: None,
+        }
+    }
+
+    fn lost(&mut self, reason: &str, losses: &mut usize) {
+        if self.seen_idr {
+            if !self.degraded {
+                tracing::warn!(reason, "video degraded until the next keyframe");
+            }
+            self.degraded = true;
+            *losses += 1;
+        }
+        self.request_keyframe();
+    }
+
+    // Asks again while degraded, the requested IDR frame may be lost too.
+    fn repeat_request(&mut self) {
+        if self.is_degraded() {
+            self.request_keyframe();
+        }
+    }
+
+    fn request_keyframe(&mut self) {
+        let due = self
+            .last_request
+            .map(|t| t.elapsed() >= KEYFRAME_REQUEST_INTERVAL)
+            .unwrap_or(true);
+        if let (true, Some(request)) = (due, self.keyframe_request.as_mut()) {
+            request();
+            self.last_request = Some(Instant::now());
+        }
+    }
+
+    // Checks the frame number of the first slice of a picture. It advances
+    // by one after each reference picture, unless the stream allows gaps.
+    fn picture(&mut self, header: &SliceHeader, sps: &Sps, idr: bool, reference: bool) -> bool {
+        let expected = self
+            .prev_ref_frame_num
+            .map(|prev| [prev, (prev + 1) % (1 << sps.log2_max_frame_num)]);
+        let gap = match expected {
+            Some(expected) => {
+                !idr && !sps.gaps_in_frame_num_allowed && !expected.contains(&header.frame_num)
+            }
+            None => false,
+        };
+        if reference {
+            self.prev_ref_frame_num = Some(header.frame_num);
+        }
+        gap
+    }
+
+    fn idr_decoded(&mut self) {
+        if self.degraded {
+            tracing::info!("video recovered");
+        }
+        self.degraded = false;
+        self.seen_idr = true;
+        self.last_request = None;
+    }
+
+    // With freeze, decoding starting in the middle of the stream is as bad
+    // as a loss, the pictures before the first IDR frame are broken too.
+    fn is_degraded(&self) -> bool {
+        self.degraded || self.freeze && !self.seen_idr
+    }
+
+    // Frames are not shown while degraded, the last good one stays.
+    fn is_frozen(&self) -> bool {
+        self.freeze && self.is_degraded()
+    }
+}
+
+// Start of the unit after the header, enough for the slice header fields
+// read by `SliceHeader::parse` without copying the whole slice.
+fn slice_header_rbsp(nal: &Nal) -> Vec<u8> {
+    let payload = nal.payload();
+    remove_emulation_prevention(&payload[1..payload.len().min(32)])
 }
 
 // Video stream decoder can decode h264 from byte stream received over network
 pub struct VideoStreamDecoder {
     decoder: openh264::decoder::Decoder,
     props: VideoStreamDecoderProps,
-    np: NalParser,
+    np: Splitter,
+    info: Option<StreamInfo>,
+    sps: Option<Sps>,
+    loss: LossTracker,
 }
 
 impl VideoStreamDecoder {
     pub fn new(skip_frames: usize) -> Self {
+        Self::with_format(skip_frames, &InputFormat::AnnexB)
+    }
+
+    pub fn with_format(skip_frames: usize, format: &InputFormat) -> Self {
+        let np = match format {
+            InputFormat::AnnexB => Splitter::AnnexB(NalParser::new()),
+            InputFormat::Avcc(config) => Splitter::Avcc(AvccParser::new(config)),
+        };
         Self {
             props: VideoStreamDecoderProps {
                 skip_frames,
                 frame_no: 0,
                 packet_no: 0,
                 packet_decode_ok: 0,
+                losses: 0,
             },
             decoder: openh264::decoder::Decoder::new().expect("can't create h264 decoder"),
-            np: NalParser::new(),
+            np,
+            info: None,
+            sps: None,
+            loss: LossTracker::new(),
         }
     }
 
+    // Keeps the last good frame after a loss until the next IDR frame,
+    // instead of showing the broken pictures.
+    pub fn set_freeze_on_loss(&mut self, freeze: bool) {
+        self.loss.freeze = freeze;
+    }
+
+    // Called when data is lost, repeated while the stream stays degraded.
+    pub fn set_keyframe_request(&mut self, request: KeyframeRequest) {
+        self.loss.keyframe_request = Some(request);
+    }
+
+    // Loss detected by the source, e.g. an RTP sequence gap.
+    pub fn report_loss(&mut self) {
+        self.loss
+            .lost("source lost packets", &mut self.props.losses);
+    }
+
+    // True from a loss until the next IDR frame. With `set_freeze_on_loss`
+    // also before the first IDR frame.
+    pub fn is_degraded(&self) -> bool {
+        self.loss.is_degraded()
+    }
+
+    // Format of the stream from the last SPS, none before the first one or
+    // the first decoded picture.
+    pub fn stream_info(&self) -> Option<StreamInfo> {
+        self.info
+    }
+
     pub fn send_stream(&mut self, buffer: &mut Vec<u8>) {
         self.np.read_stream(buffer);
     }
 
+    // Lets the last unit be decoded, Annex-B units are otherwise held until
+    // the next start code arrives.
+    pub fn end_stream(&mut self) {
+        self.np.finish();
+    }
+
     // This is the main function responsible for decoding images.
-    // You have to pass read write lock reference to the *pre-allocated* array where
-    // this function update the frames in RGB.
+    // You have to pass read write lock reference to the array where this
+    // function updates the frames in RGB. The array is resized to the picture
+    // size of the stream, which may change with every SPS. With
+    // `set_freeze_on_loss` the array is left alone from a loss until the
+    // next IDR frame.
     //
     // This function returns `StreamAction`:
     //  * CallNext - do next call to this function without reading more
     //  * ReadMore - you have to read more data
-    //  * ProcessPacket - never returned from here, hidden with CallNext
     pub fn decode_images(&mut self, target_image: &Arc<RwLock<Vec<u8>>>) -> StreamAction {
-        let r = self.np.get_packet();
-        match r {
-            StreamAction::ProcessPacket(img) => {
-                self.props.packet_no += 1;
-                let skip_frame = self.props.skip_frames != 0
-                    && self.props.frame_no % self.props.skip_frames != 0;
-
-                if let Ok(maybe_yuv) = self.decoder.decode(&img) {
-                    self.props.packet_decode_ok += 1;
-
-                    if let Some(yuv) = maybe_yuv {
-                        if !skip_frame {
-                            let mut g = target_image.write().unwrap();
-                            yuv.write_rgb8(&mut g);
-                            drop(g);
-                        }
-                        self.props.frame_no += 1;
+        self.loss.repeat_request();
+        if self.np.take_dropped() {
+            self.loss
+                .lost("corrupted unit length", &mut self.props.losses);
+        }
+        let Some(nal) = self.np.next_nal() else {
+            return StreamAction::ReadMore;
+        };
+        self.props.packet_no += 1;
+        let skip_frame =
+            self.props.skip_frames != 0 && self.props.frame_no % self.props.skip_frames != 0;
+
+        let nal_type = nal.nal_type();
+        if nal_type == NalType::Sps {
+            match Sps::parse(&nal.rbsp()) {
+                Ok(sps) => {
+                    let info = StreamInfo::from(&sps);
+                    if self.info != Some(info) {
+                        tracing::info!(
+                            width = info.width,
+                            height = info.height,
+                            profile = sps.profile_name(),
+                            level = info.level,
+                            "video format"
+                        );
+                    }
+                    self.info = Some(info);
+                    self.sps = Some(sps);
+                }
+                Err(e) => tracing::warn!("can't parse SPS: {e}"),
+            }
+        }
+        let idr = nal_type == NalType::Idr;
+        if let (NalType::Slice | NalType::Idr, Some(sps)) = (nal_type, self.sps.as_ref()) {
+            match SliceHeader::parse(&slice_header_rbsp(&nal), sps) {
+                Ok(header) if header.first_mb == 0 => {
+                    if self.loss.picture(&header, sps, idr, nal.ref_idc() != 0) {
+                        self.loss.lost("frame_num gap", &mut self.props.losses);
                     }
                 }
-                StreamAction::CallNext
+                Ok(_) => {}
+                Err(e) => tracing::debug!("can't parse slice header: {e}"),
             }
+        }
 
-            _ => r,
+        match self.decoder.decode(nal.data()) {
+            Ok(maybe_yuv) => {
+                self.props.packet_decode_ok += 1;
+                if idr {
+                    self.loss.idr_decoded();
+                }
+                if let Some(yuv) = maybe_yuv {
+                    let (width, height) = yuv.dimensions();
+                    // the decoder knows best, e.g. when the SPS didn't parse
+                    let info = self.info.get_or_insert(StreamInfo {
+                        width: 0,
+                        height: 0,
+                        profile: 0,
+                        level: 0,
+                    });
+                    info.width = width as u32;
+                    info.height = height as u32;
+                    if !skip_frame && !self.loss.is_frozen() {
+                        let mut g = target_image.write().unwrap();
+                        g.resize(width * height * 3, 0);
+                        yuv.write_rgb8(&mut g);
+                        drop(g);
+                    }
+                    self.props.frame_no += 1;
+                }
+            }
+            Err(e) => {
+                tracing::debug!("decode error: {e}");
+                self.loss.lost("decode error", &mut self.props.losses);
+            }
         }
+        StreamAction::CallNext
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::sync::{Arc, RwLock};
+    use std::{
+        sync::{Arc, RwLock},
+        time::Instant,
+    };
 
-    use crate::{utils, video::VideoStreamDecoder};
+    use crate::{
+        h264::{SliceHeader, Sps},
+        utils,
+        video::VideoStreamDecoder,
+    };
 
-    use super::NalParser;
+    use super::{
+        AvcConfig, AvccParser, InputFormat, LossTracker, NalParser, NalType,
+        KEYFRAME_REQUEST_INTERVAL,
+    };
 
     lazy_static! {
         static ref VIDEO_FRAME: Arc<RwLock<Vec<u8>>> =
@@ -169,80 +738,92 @@ mod test {
             vd.decode_images(image_rw_lock)
         );
         vd.np.read_stream(&mut v2);
+        assert_eq!(
+            super::StreamAction::ReadMore,
+            vd.decode_images(image_rw_lock)
+        );
+        vd.np.read_stream(&mut v3);
         assert_eq!(
             super::StreamAction::CallNext,
             vd.decode_images(image_rw_lock)
         );
+        assert_eq!(1, vd.props.packet_decode_ok);
+        let info = vd.stream_info().unwrap();
+        assert_eq!(
+            (960, 720, 77, 40),
+            (info.width, info.height, info.profile, info.level)
+        );
         assert_eq!(
             super::StreamAction::ReadMore,
             vd.decode_images(image_rw_lock)
         );
-        vd.np.read_stream(&mut v3);
+        vd.end_stream();
         assert_eq!(
             super::StreamAction::CallNext,
             vd.decode_images(image_rw_lock)
         );
-        assert_eq!(1, vd.props.packet_decode_ok);
+        assert_eq!(2, vd.props.packet_no);
+    }
+
+    fn nals(np: &mut NalParser) -> Vec<Vec<u8>> {
+        let mut nals = Vec::new();
+        while let Some(nal) = np.next_nal() {
+            nals.push(nal.data().to_vec());
+        }
+        nals
     }
 
     #[test]
     fn nal_mark_stream_boundary() {
-        //XXX: [0, 0, 1, 103, 77, 64, 40, 149, 160, 60, 5, 185, 0]
-        //XXX: [0, 0, 1, 104, 238, 56, 128, 0]
         let mut v1 = vec![1, 2, 3, 0];
         let mut v2 = vec![0, 1, 104, 238, 56, 128, 0];
         let mut v3 = vec![0, 0, 1, 104, 238, 56, 128, 0];
 
         let mut np = NalParser::new();
         // nothing read, read some data
-        assert_eq!(super::StreamAction::ReadMore, np.get_packet());
-        assert_eq!(None, np.last_nal);
+        assert_eq!(None, np.next_nal());
         np.read_stream(&mut v1);
 
         // no sign of 0, 0, 1 mark, read more
-        assert_eq!(super::StreamAction::ReadMore, np.get_packet());
+        assert_eq!(None, np.next_nal());
         np.read_stream(&mut v2);
 
-        // First 0, 0, 1 mark found at offset 3
-        assert_eq!(super::StreamAction::CallNext, np.get_packet());
-        assert_eq!(Some(3), np.last_nal);
-
-        // However no follow-up mark found till the end of current stream, hence, read more
-        assert_eq!(super::StreamAction::ReadMore, np.get_packet());
+        // start code found, the end of the unit is not known yet
+        assert_eq!(None, np.next_nal());
         np.read_stream(&mut v3);
 
-        // now the packet it complete, process it
-        assert_eq!(
-            super::StreamAction::ProcessPacket(vec![0, 0, 1, 104, 238, 56, 128, 0]),
-            np.get_packet()
-        );
-        assert_eq!(Some(0), np.last_nal);
-
-        // However no follow-up mark found till the end of current stream, hence, read more
-        assert_eq!(super::StreamAction::ReadMore, np.get_packet());
+        // the trailing zero belongs to the 4 byte start code of the next unit
+        assert_eq!(vec![vec![0, 0, 1, 104, 238, 56, 128]], nals(&mut np));
+        np.finish();
+        assert_eq!(vec![vec![0, 0, 0, 1, 104, 238, 56, 128]], nals(&mut np));
+        assert_eq!(None, np.next_nal());
     }
 
     #[test]
     fn nal_mark_empty() {
         let mut np = NalParser::new();
-        assert_eq!(super::StreamAction::ReadMore, np.get_packet());
-        assert_eq!(None, np.last_nal);
+        assert_eq!(None, np.next_nal());
+        np.finish();
+        assert_eq!(None, np.next_nal());
     }
 
     #[test]
     fn nal_mark_no_mark() {
         let mut np = NalParser::new();
         np.read_stream(&mut vec![2, 3]);
-        assert_eq!(super::StreamAction::ReadMore, np.get_packet());
-        assert_eq!(None, np.last_nal);
+        assert_eq!(None, np.next_nal());
+        np.finish();
+        assert_eq!(None, np.next_nal());
     }
 
     #[test]
     fn nal_mark_single_mark() {
         let mut np = NalParser::new();
         np.read_stream(&mut vec![0, 0, 1]);
-        assert_eq!(super::StreamAction::CallNext, np.get_packet());
-        assert_eq!(Some(0), np.last_nal);
+        assert_eq!(None, np.next_nal());
+        // start code without data is not a unit
+        np.finish();
+        assert_eq!(None, np.next_nal());
     }
 
     #[test]
@@ -251,42 +832,186 @@ mod test {
         np.read_stream(&mut vec![
             1, 2, 3, 4, 5, 0, 0, 1, 22, 33, 44, 0, 0, 0, 1, 0, 5, 6, 7, 0, 0, 1, 7, 8, 9,
         ]);
-        assert_eq!(super::StreamAction::CallNext, np.get_packet());
-        assert_eq!(Some(5), np.last_nal);
-        assert_eq!(
-            super::StreamAction::ProcessPacket(vec![0, 0, 1, 22, 33, 44, 0]),
-            np.get_packet()
-        );
         assert_eq!(
-            super::StreamAction::ProcessPacket(vec![0, 0, 1, 0, 5, 6, 7]),
-            np.get_packet()
+            vec![vec![0, 0, 1, 22, 33, 44], vec![0, 0, 0, 1, 0, 5, 6, 7]],
+            nals(&mut np)
         );
-        assert_eq!(super::StreamAction::ReadMore, np.get_packet());
+        np.finish();
+        assert_eq!(vec![vec![0, 0, 1, 7, 8, 9]], nals(&mut np));
     }
 
     #[test]
     fn nal_mark_multiple_marks() {
         let mut np = NalParser::new();
         np.read_stream(&mut vec![0, 0, 1, 2, 3, 4, 0, 0, 1]);
-        assert_eq!(super::StreamAction::CallNext, np.get_packet());
-        assert_eq!(Some(0), np.last_nal);
-        assert_eq!(
-            super::StreamAction::ProcessPacket(vec![0, 0, 1, 2, 3, 4]),
-            np.get_packet()
-        );
-        assert_eq!(super::StreamAction::ReadMore, np.get_packet());
-        assert_eq!(Some(0), np.last_nal);
+        assert_eq!(vec![vec![0, 0, 1, 2, 3, 4]], nals(&mut np));
         np.read_stream(&mut vec![2, 2, 2]);
-        assert_eq!(super::StreamAction::ReadMore, np.get_packet());
-        assert_eq!(Some(0), np.last_nal);
-        np.read_stream(&mut vec![3, 3, 3, 0, 0, 1, 5, 6, 7]);
+        assert_eq!(None, np.next_nal());
+        // start code split between reads
+        np.read_stream(&mut vec![3, 3, 3, 0, 0]);
+        assert_eq!(None, np.next_nal());
+        np.read_stream(&mut vec![0, 1, 5, 6, 7]);
+        assert_eq!(vec![vec![0, 0, 1, 2, 2, 2, 3, 3, 3]], nals(&mut np));
+        np.finish();
+        assert_eq!(vec![vec![0, 0, 0, 1, 5, 6, 7]], nals(&mut np));
+    }
+
+    #[test]
+    fn nal_header_and_rbsp() {
+        let mut np = NalParser::new();
+        np.read_stream(&mut vec![
+            0, 0, 0, 1, 0x67, 0, 0, 3, 1, 0, 0, 3, 0, 0, 3, 3, 0, 0, 1, 0x65, 0x88, 0, 0, 1, 0x01,
+            0x9a,
+        ]);
+        let nal = np.next_nal().unwrap();
+        assert_eq!(NalType::Sps, nal.nal_type());
+        assert_eq!(3, nal.ref_idc());
+        assert_eq!(vec![0, 0, 1, 0, 0, 0, 0, 3], nal.rbsp());
+        let nal = np.next_nal().unwrap();
+        assert_eq!(NalType::Idr, nal.nal_type());
+        assert_eq!(&[0x65, 0x88], nal.payload());
+        np.finish();
+        let nal = np.next_nal().unwrap();
+        assert_eq!(NalType::Slice, nal.nal_type());
+        assert_eq!(0, nal.ref_idc());
+    }
+
+    // avcC record with the SPS and PPS of `decode_h264_frame`
+    const AVCC: [u8; 24] = [
+        1, 77, 64, 40, 0xff, 0xe1, 0, 9, 103, 77, 64, 40, 149, 160, 60, 5, 185, 1, 0, 4, 104, 238,
+        56, 128,
+    ];
+
+    #[test]
+    fn avcc_config() {
+        let config = AvcConfig::parse(&AVCC).unwrap();
+        assert_eq!(77, config.profile);
+        assert_eq!(40, config.level);
+        assert_eq!(4, config.length_size);
         assert_eq!(
-            super::StreamAction::ProcessPacket(vec![0, 0, 1, 2, 2, 2, 3, 3, 3]),
-            np.get_packet()
+            vec![vec![103, 77, 64, 40, 149, 160, 60, 5, 185]],
+            config.sps
         );
-        assert_eq!(Some(0), np.last_nal);
-        assert_eq!(super::StreamAction::ReadMore, np.get_packet());
-        assert_eq!(Some(0), np.last_nal);
+        assert_eq!(vec![vec![104, 238, 56, 128]], config.pps);
+
+        let mut two_bytes = AVCC;
+        two_bytes[4] = 0xfd;
+        assert_eq!(2, AvcConfig::parse(&two_bytes).unwrap().length_size);
+        assert!(AvcConfig::parse(&AVCC[..20]).is_err());
+        assert!(AvcConfig::parse(&[0, 77, 64, 40, 0xff, 0xe0, 0]).is_err());
+    }
+
+    #[test]
+    fn avcc_samples() {
+        let mut config = AvcConfig::parse(&AVCC).unwrap();
+        config.length_size = 2;
+        let mut np = AvccParser::new(&config);
+        let nal = np.next_nal().unwrap();
+        assert_eq!(NalType::Sps, nal.nal_type());
+        assert_eq!(&[0, 0, 0, 1, 103, 77], &nal.data()[..6]);
+        assert_eq!(NalType::Pps, np.next_nal().unwrap().nal_type());
+        assert_eq!(None, np.next_nal());
+
+        // sample with two units, split inside the first one
+        np.read_stream(&mut vec![0, 3, 0x65, 0x88]);
+        assert_eq!(None, np.next_nal());
+        np.read_stream(&mut vec![0x84, 0, 0, 0, 2, 0x41, 0x9a]);
+        let nal = np.next_nal().unwrap();
+        assert_eq!(&[0, 0, 0, 1, 0x65, 0x88, 0x84], nal.data());
+        assert_eq!(NalType::Idr, nal.nal_type());
+        // empty units are skipped
+        assert_eq!(&[0x41, 0x9a], np.next_nal().unwrap().payload());
+        assert_eq!(None, np.next_nal());
+    }
+
+    #[test]
+    fn avcc_corrupted_length() {
+        let mut np = AvccParser::new(&AvcConfig::parse(&AVCC).unwrap());
+        assert_eq!(NalType::Sps, np.next_nal().unwrap().nal_type());
+        assert_eq!(NalType::Pps, np.next_nal().unwrap().nal_type());
+
+        // a length of 2 GB is never completed
+        np.read_stream(&mut vec![0x7f, 0xff, 0xff, 0xff, 0x65, 0x88]);
+        assert_eq!(None, np.next_nal());
+        assert!(np.take_dropped());
+        assert!(!np.take_dropped());
+        np.read_stream(&mut vec![0, 0, 0, 2, 0x41, 0x9a]);
+        assert_eq!(&[0x41, 0x9a], np.next_nal().unwrap().payload());
+
+        // a plausible length, but the header has the forbidden bit
+        np.read_stream(&mut vec![0, 0, 0, 3, 0x81, 1, 2, 0, 0, 0, 2, 0x41, 0x9b]);
+        assert_eq!(None, np.next_nal());
+        assert!(np.take_dropped());
+        assert!(np.buffer.is_empty());
+    }
+
+    #[test]
+    fn decode_avcc_parameter_sets() {
+        let config = AvcConfig::parse(&AVCC).unwrap();
+        let mut vd = VideoStreamDecoder::with_format(3, &InputFormat::Avcc(config));
+        let image_rw_lock = &VIDEO_FRAME;
+        while vd.decode_images(image_rw_lock) == super::StreamAction::CallNext {}
+        assert_eq!(2, vd.props.packet_no);
+        assert_eq!(2, vd.props.packet_decode_ok);
+    }
+
+    #[test]
+    fn loss_until_idr() {
+        let sps = Sps {
+            profile_idc: 66,
+            constraint_flags: 0,
+            level_idc: 30,
+            id: 0,
+            chroma_format_idc: 1,
+            separate_colour_plane: false,
+            log2_max_frame_num: 4,
+            gaps_in_frame_num_allowed: false,
+            frame_mbs_only: true,
+            width: 320,
+            height: 240,
+        };
+        let slice = |frame_num| SliceHeader {
+            first_mb: 0,
+            slice_type: 0,
+            pps_id: 0,
+            frame_num,
+        };
+        let requests = Arc::new(RwLock::new(0));
+        let r = requests.clone();
+        let mut loss = LossTracker::new();
+        loss.freeze = true;
+        loss.keyframe_request = Some(Box::new(move || *r.write().unwrap() += 1));
+        let mut losses = 0;
+
+        // decoding starts anywhere, errors before the first IDR only ask for
+        // one, nothing is shown till then
+        assert!(loss.is_frozen());
+        loss.lost("decode error", &mut losses);
+        assert_eq!((0, 1), (losses, *requests.read().unwrap()));
+        assert!(!loss.picture(&slice(0), &sps, true, true));
+        loss.idr_decoded();
+        assert!(!loss.picture(&slice(1), &sps, false, true));
+        // non-reference pictures don't advance the frame number
+        assert!(!loss.picture(&slice(2), &sps, false, false));
+        assert!(!loss.picture(&slice(2), &sps, false, true));
+        // 3 is missing
+        assert!(loss.picture(&slice(4), &sps, false, true));
+        loss.lost("frame_num gap", &mut losses);
+        loss.lost("decode error", &mut losses);
+        assert!(loss.is_frozen());
+        assert_eq!(2, losses);
+        // requests are not repeated right away, but every interval
+        assert_eq!(2, *requests.read().unwrap());
+        loss.repeat_request();
+        assert_eq!(2, *requests.read().unwrap());
+        loss.last_request = Some(Instant::now() - KEYFRAME_REQUEST_INTERVAL);
+        loss.repeat_request();
+        assert_eq!(3, *requests.read().unwrap());
+        loss.idr_decoded();
+        assert!(!loss.is_frozen());
+        // the frame number wraps around
+        loss.prev_ref_frame_num = Some(15);
+        assert!(!loss.picture(&slice(0), &sps, false, true));
     }
 
     #[cfg(home)]
@@ -325,18 +1050,12 @@ mod test {
                 let mut stream = include_bytes!("/home/mikc/git/libtello/video.dump").to_vec();
                 let mut np = NalParser::new();
                 np.read_stream(&mut stream);
+                np.finish();
                 let mut nals = 0;
                 let mut packet_len = Vec::new();
-                loop {
-                    let r = np.get_packet();
-                    match r {
-                        crate::video::StreamAction::CallNext => {}
-                        crate::video::StreamAction::ReadMore => break,
-                        crate::video::StreamAction::ProcessPacket(img) => {
-                            nals += 1;
-                            packet_len.push(img.len())
-                        }
-                    }
+                while let Some(nal) = np.next_nal() {
+                    nals += 1;
+                    packet_len.push(nal.data().len())
                 }
                 assert_eq!(719, nals);
                 assert_eq!(PACKETS.to_vec(), packet_len);
@@ -356,20 +1075,18 @@ mod test {
                 let mut nals = 0;
                 let mut packet_len = Vec::new();
                 loop {
-                    let r = np.get_packet();
-                    match r {
-                        crate::video::StreamAction::CallNext => {}
-                        crate::video::StreamAction::ReadMore => {
+                    match np.next_nal() {
+                        Some(nal) => {
+                            nals += 1;
+                            packet_len.push(nal.data().len())
+                        }
+                        None => {
                             let nread = reader.read(&mut buf).expect("buffer load error");
                             if nread == 0 {
                                 break;
                             }
                             np.read_stream(&mut buf[0..nread].to_vec());
                         }
-                        crate::video::StreamAction::ProcessPacket(img) => {
-                            nals += 1;
-                            packet_len.push(img.len())
-                        }
                     }
                 }
                 assert_eq!(719, nals);
@@ -0,0 +1,577 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::error::Error;
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+// RTP packet (RFC 3550), the payload excludes the CSRC list, the header
+// extension and the padding.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RtpPacket<'a> {
+    // set on the last packet of a frame
+    pub marker: bool,
+    pub payload_type: u8,
+    pub seq: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+    pub payload: &'a [u8],
+}
+
+impl<'a> RtpPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let err = |e: &str| Error::Video(format!("rtp: {e}"));
+        if data.len() < 12 {
+            return Err(err("packet too short"));
+        }
+        if data[0] >> 6 != 2 {
+            return Err(err(&format!("unsupported version {}", data[0] >> 6)));
+        }
+        let mut start = 12 + 4 * (data[0] & 0xf) as usize;
+        if data[0] & 0x10 != 0 {
+            let words = data
+                .get(start + 2..start + 4)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+                .ok_or_else(|| err("truncated header extension"))?;
+            start += 4 + 4 * words;
+        }
+        let mut end = data.len();
+        if data[0] & 0x20 != 0 {
+            end = end.saturating_sub(data[end - 1] as usize);
+        }
+        if start > end {
+            return Err(err("truncated packet"));
+        }
+        Ok(Self {
+            marker: data[1] & 0x80 != 0,
+            payload_type: data[1] & 0x7f,
+            seq: u16::from_be_bytes([data[2], data[3]]),
+            timestamp: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            ssrc: u32::from_be_bytes([data[8], data[9], data[10], data[11]]),
+            payload: &data[start..end],
+        })
+    }
+}
+
+// Puts the packets back in sequence order. Packets are held until the
+// missing ones arrive, up to `depth` packets, then the gap is given up and
+// counted as lost. A new SSRC or a sequence number far behind the expected
+// one means the sender restarted, the order starts over. A sequence number
+// far ahead is a burst loss, e.g. a Wi-Fi dropout.
+struct Reorder {
+    depth: usize,
+    // source of the current stream
+    ssrc: Option<u32>,
+    // sequence number expected next
+    next: Option<u16>,
+    pending: HashMap<u16, Vec<u8>>,
+}
+
+// Packet in sequence order, `gap` when packets before it were lost.
+struct Ordered {
+    payload: Vec<u8>,
+    gap: bool,
+}
+
+impl Reorder {
+    fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            ssrc: None,
+            next: None,
+            pending: HashMap::new(),
+        }
+    }
+
+    // Returns the packets ready in order. Late packets of a skipped gap and
+    // duplicates are dropped, `stats` counts them.
+    fn push(
+        &mut self,
+        ssrc: u32,
+        seq: u16,
+        payload: &[u8],
+        stats: &mut SourceStats,
+    ) -> Vec<Ordered> {
+        let next = *self.next.get_or_insert(seq);
+        // sequence numbers wrap around, so the distance is signed
+        let ahead = seq.wrapping_sub(next) as i16;
+        let window = 8 * self.depth.max(1);
+        let mut ready = Vec::new();
+        // the unit being assembled before the restart or loss is incomplete
+        let mut gap = false;
+        if self.ssrc.is_some_and(|s| s != ssrc) || -(ahead as isize) > window as isize {
+            tracing::warn!(ssrc, seq, expected = next, "rtp stream restarted");
+            ready = self.flush(stats);
+            self.next = Some(seq);
+            gap = true;
+        } else if ahead as isize > window as isize {
+            tracing::warn!(ssrc, seq, expected = next, "rtp packets lost");
+            ready = self.flush(stats);
+            self.skip_to(seq, stats);
+            gap = true;
+        } else if ahead < 0 || self.pending.contains_key(&seq) {
+            stats.late += 1;
+            return Vec::new();
+        }
+        self.ssrc = Some(ssrc);
+        self.pending.insert(seq, payload.to_vec());
+        ready.extend(self.drain(gap));
+        while self.pending.len() > self.depth {
+            self.skip_gap(stats);
+            ready.extend(self.drain(true));
+        }
+        ready
+    }
+
+    // Gives up all gaps, e.g. when no packets came for a while.
+    fn flush(&mut self, stats: &mut SourceStats) -> Vec<Ordered> {
+        let mut ready = Vec::new();
+        while !self.pending.is_empty() {
+            self.skip_gap(stats);
+            ready.extend(self.drain(true));
+        }
+        ready
+    }
+
+    fn drain(&mut self, mut gap: bool) -> Vec<Ordered> {
+        let mut ready = Vec::new();
+        while let Some(next) = self.next {
+            let Some(payload) = self.pending.remove(&next) else {
+                break;
+            };
+            ready.push(Ordered { payload, gap });
+            gap = false;
+            self.next = Some(next.wrapping_add(1));
+        }
+        ready
+    }
+
+    // Continues with the first pending packet.
+    fn skip_gap(&mut self, stats: &mut SourceStats) {
+        let Some(next) = self.next else {
+            return;
+        };
+        if let Some(first) = self
+            .pending
+            .keys()
+            .min_by_key(|seq| seq.wrapping_sub(next))
+            .copied()
+        {
+            self.skip_to(first, stats);
+        }
+    }
+
+    fn skip_to(&mut self, seq: u16, stats: &mut SourceStats) {
+        if let Some(next) = self.next {
+            stats.lost += seq.wrapping_sub(next) as u64;
+        }
+        self.pending
+            .retain(|s, _| s.wrapping_sub(seq) < u16::MAX / 2);
+        self.next = Some(seq);
+    }
+}
+
+// Counters of the received stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SourceStats {
+    pub packets: u64,
+    // packets never received
+    pub lost: u64,
+    // duplicates and packets arriving after their gap was given up
+    pub late: u64,
+    // units dropped because a fragment was lost
+    pub dropped_units: u64,
+}
+
+// RtpDepacketizer turns H.264 RTP packets (RFC 6184) into the Annex-B byte
+// stream taken by `VideoStreamDecoder`. Single NAL unit packets, STAP-A
+// aggregates and FU-A fragments are supported, the non-interleaved mode
+// used by cameras and drones.
+pub struct RtpDepacketizer {
+    reorder: Reorder,
+    // unit assembled from FU-A fragments, with the start code
+    fragments: Option<Vec<u8>>,
+    stats: SourceStats,
+}
+
+impl RtpDepacketizer {
+    // Up to `reorder_depth` packets are held back waiting for a missing one.
+    pub fn new(reorder_depth: usize) -> Self {
+        Self {
+            reorder: Reorder::new(reorder_depth),
+            fragments: None,
+            stats: SourceStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> SourceStats {
+        self.stats
+    }
+
+    // Takes a datagram, returns the units completed by it, each behind a
+    // start code. Malformed packets are dropped.
+    pub fn push(&mut self, datagram: &[u8]) -> Vec<u8> {
+        let packet = match RtpPacket::parse(datagram) {
+            Ok(p) => p,
+            Err(e) => {
+                tracing::warn!("{e}");
+                return Vec::new();
+            }
+        };
+        self.stats.packets += 1;
+        let ordered = self
+            .reorder
+            .push(packet.ssrc, packet.seq, packet.payload, &mut self.stats);
+        self.depacketize(ordered)
+    }
+
+    // Gives up waiting for missing packets and returns what is held back.
+    pub fn flush(&mut self) -> Vec<u8> {
+        let ordered = self.reorder.flush(&mut self.stats);
+        self.depacketize(ordered)
+    }
+
+    fn depacketize(&mut self, ordered: Vec<Ordered>) -> Vec<u8> {
+        let mut stream = Vec::new();
+        for packet in ordered {
+            if packet.gap && self.fragments.take().is_some() {
+                // the middle of the unit is missing
+                self.stats.dropped_units += 1;
+            }
+            self.payload(&packet.payload, &mut stream);
+        }
+        stream
+    }
+
+    fn payload(&mut self, payload: &[u8], stream: &mut Vec<u8>) {
+        let Some(&header) = payload.first() else {
+            return;
+        };
+        match header & 0x1f {
+            1..=23 => {
+                stream.extend_from_slice(&START_CODE);
+                stream.extend_from_slice(payload);
+            }
+            // STAP-A, units prefixed by their 16 bit size
+            24 => {
+                let mut rest = &payload[1..];
+                while rest.len() >= 2 {
+                    let size = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+                    let Some(unit) = rest.get(2..2 + size) else {
+                        tracing::warn!("truncated STAP-A");
+                        return;
+                    };
+                    if !unit.is_empty() {
+                        stream.extend_from_slice(&START_CODE);
+                        stream.extend_from_slice(unit);
+                    }
+                    rest = &rest[2 + size..];
+                }
+            }
+            // FU-A, the unit header is rebuilt from the indicator and the
+            // type in the fragment header
+            28 => {
+                let Some(&fu_header) = payload.get(1) else {
+                    return;
+                };
+                let start = fu_header & 0x80 != 0;
+                let end = fu_header & 0x40 != 0;
+                if start {
+                    if self.fragments.is_some() {
+                        self.stats.dropped_units += 1;
+                    }
+                    let mut unit = START_CODE.to_vec();
+                    unit.push(header & 0xe0 | fu_header & 0x1f);
+                    self.fragments = Some(unit);
+                }
+                // fragments without their start are useless
+                let Some(unit) = self.fragments.as_mut() else {
+                    return;
+                };
+                unit.extend_from_slice(&payload[2..]);
+                if end {
+                    stream.append(unit);
+                    self.fragments = None;
+                }
+            }
+            t => tracing::warn!("unsupported rtp payload type {t}"),
+        }
+    }
+}
+
+// What the drone sends over UDP.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SourceMode {
+    // Annex-B byte stream split into datagrams, e.g. the Tello
+    #[default]
+    Raw,
+    // H.264 in RTP packets
+    Rtp,
+}
+
+// VideoSource receives the video stream on a UDP socket and passes the
+// Annex-B stream to the channel of `VideoWidget`:
+//
+// ```
+// let (tx, rx) = mpsc::channel();
+// let source = VideoSource::bind("0.0.0.0:5600", SourceMode::Rtp)?.spawn(tx);
+// let video = VideoWidget::new(props, 0)
+//     .source_stats(source.stats())
+//     .on_window(&mut win, rx);
+// // when the video is not needed anymore
+// source.stop();
+// ```
+pub struct VideoSource {
+    socket: UdpSocket,
+    mode: SourceMode,
+    reorder_depth: usize,
+}
+
+impl VideoSource {
+    pub fn bind(addr: impl ToSocketAddrs, mode: SourceMode) -> Result<Self, Error> {
+        let socket = UdpSocket::bind(addr).map_err(|e| Error::Io(format!("video socket: {e}")))?;
+        // wakes up the receiving thread to give up waiting for lost packets
+        socket
+            .set_read_timeout(Some(Duration::from_millis(100)))
+            .map_err(|e| Error::Io(format!("video socket: {e}")))?;
+        Ok(Self {
+            socket,
+            mode,
+            reorder_depth: 32,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        self.socket
+            .local_addr()
+            .map_err(|e| Error::Io(format!("video socket: {e}")))
+    }
+
+    // Packets held back waiting for a missing one in the RTP mode.
+    pub fn reorder_depth(self, reorder_depth: usize) -> Self {
+        Self {
+            reorder_depth,
+            ..self
+        }
+    }
+
+    // Receives on its own thread until stopped by the handle or the receiver
+    // of the channel is gone. A gone receiver is only noticed when there is
+    // something to send.
+    pub fn spawn(self, tx: Sender<Vec<u8>>) -> SourceHandle {
+        let handle = SourceHandle {
+            stats: Arc::new(RwLock::new(SourceStats::default())),
+            stop: Arc::new(AtomicBool::new(false)),
+        };
+        let stats = handle.stats.clone();
+        let stop = handle.stop.clone();
+        thread::spawn(move || self.receive(tx, stats, stop));
+        handle
+    }
+
+    fn receive(self, tx: Sender<Vec<u8>>, stats: Arc<RwLock<SourceStats>>, stop: Arc<AtomicBool>) {
+        let mut rtp = RtpDepacketizer::new(self.reorder_depth);
+        let mut buf = vec![0; 65536];
+        loop {
+            // checked at least every read timeout
+            if stop.load(Ordering::Relaxed) {
+                tracing::info!("video source stopped");
+                return;
+            }
+            let stream = match self.socket.recv(&mut buf) {
+                Ok(n) => match self.mode {
+                    SourceMode::Raw => {
+                        stats.write().unwrap().packets += 1;
+                        buf[..n].to_vec()
+                    }
+                    SourceMode::Rtp => rtp.push(&buf[..n]),
+                },
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    match self.mode {
+                        SourceMode::Raw => Vec::new(),
+                        SourceMode::Rtp => rtp.flush(),
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("video socket: {e}");
+                    return;
+                }
+            };
+            if self.mode == SourceMode::Rtp {
+                *stats.write().unwrap() = rtp.stats();
+            }
+            if !stream.is_empty() && tx.send(stream).is_err() {
+                tracing::info!("video source closed");
+                return;
+            }
+        }
+    }
+}
+
+// Receiving thread of `VideoSource::spawn`.
+pub struct SourceHandle {
+    stats: Arc<RwLock<SourceStats>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl SourceHandle {
+    pub fn stats(&self) -> Arc<RwLock<SourceStats>> {
+        self.stats.clone()
+    }
+
+    // The thread ends within the socket read timeout and closes the socket.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::UdpSocket, sync::mpsc, thread, time::Duration};
+
+    use super::{RtpDepacketizer, RtpPacket, SourceMode, VideoSource};
+
+    fn rtp(seq: u16, payload: &[u8]) -> Vec<u8> {
+        rtp_from(1, seq, payload)
+    }
+
+    fn rtp_from(ssrc: u32, seq: u16, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x80, 96];
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(&[0, 0, 0, 0]);
+        packet.extend_from_slice(&ssrc.to_be_bytes());
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    #[test]
+    fn rtp_header() {
+        // one CSRC, extension of one word, 2 bytes padding
+        let data = [
+            0xb1, 0xe0, 0x12, 0x34, 0, 0, 0, 9, 0, 0, 0, 1, 0, 0, 0, 2, 0xbe, 0xde, 0, 1, 1, 2, 3,
+            4, 0x65, 0x88, 0, 2,
+        ];
+        let packet = RtpPacket::parse(&data).unwrap();
+        assert!(packet.marker);
+        assert_eq!(
+            (96, 0x1234, 9),
+            (packet.payload_type, packet.seq, packet.timestamp)
+        );
+        assert_eq!(&[0x65, 0x88], packet.payload);
+        assert!(RtpPacket::parse(&data[..20]).is_err());
+    }
+
+    #[test]
+    fn depacketize_single_stap_a_and_fu_a() {
+        let mut d = RtpDepacketizer::new(4);
+        assert_eq!(vec![0, 0, 0, 1, 0x09, 0xf0], d.push(&rtp(1, &[0x09, 0xf0])));
+        // SPS and PPS aggregated
+        assert_eq!(
+            vec![0, 0, 0, 1, 0x67, 1, 2, 0, 0, 0, 1, 0x68, 3],
+            d.push(&rtp(2, &[0x78, 0, 3, 0x67, 1, 2, 0, 2, 0x68, 3]))
+        );
+        // IDR slice in three fragments
+        assert!(d.push(&rtp(3, &[0x7c, 0x85, 1, 2])).is_empty());
+        assert!(d.push(&rtp(4, &[0x7c, 0x05, 3])).is_empty());
+        assert_eq!(
+            vec![0, 0, 0, 1, 0x65, 1, 2, 3, 4],
+            d.push(&rtp(5, &[0x7c, 0x45, 4]))
+        );
+        assert_eq!(5, d.stats().packets);
+    }
+
+    #[test]
+    fn reorder_and_gaps() {
+        let mut d = RtpDepacketizer::new(2);
+        assert_eq!(vec![0, 0, 0, 1, 0x41, 1], d.push(&rtp(65535, &[0x41, 1])));
+        // 0 is late, 1 waits for it
+        assert!(d.push(&rtp(1, &[0x41, 3])).is_empty());
+        assert_eq!(
+            vec![0, 0, 0, 1, 0x41, 2, 0, 0, 0, 1, 0x41, 3],
+            d.push(&rtp(0, &[0x41, 2]))
+        );
+        // fragment start, its middle 3 is lost
+        assert!(d.push(&rtp(2, &[0x5c, 0x81, 4])).is_empty());
+        assert!(d.push(&rtp(4, &[0x5c, 0x41, 6])).is_empty());
+        assert!(d.push(&rtp(5, &[0x41, 7])).is_empty());
+        // the gap is given up when more than 2 packets wait
+        assert_eq!(
+            vec![0, 0, 0, 1, 0x41, 7, 0, 0, 0, 1, 0x41, 8],
+            d.push(&rtp(6, &[0x41, 8]))
+        );
+        // arrives too late
+        assert!(d.push(&rtp(3, &[0x5c, 0x01, 5])).is_empty());
+        let stats = d.stats();
+        assert_eq!((1, 1, 1), (stats.lost, stats.late, stats.dropped_units));
+        // waiting packets are released by flush
+        assert!(d.push(&rtp(8, &[0x41, 9])).is_empty());
+        assert_eq!(vec![0, 0, 0, 1, 0x41, 9], d.flush());
+        assert_eq!(2, d.stats().lost);
+    }
+
+    #[test]
+    fn restart_with_lower_sequence_or_new_ssrc() {
+        let mut d = RtpDepacketizer::new(2);
+        assert_eq!(vec![0, 0, 0, 1, 0x41, 1], d.push(&rtp(5000, &[0x41, 1])));
+        // fragment start, the sender restarts before the unit is complete
+        assert!(d.push(&rtp(5001, &[0x5c, 0x81, 2])).is_empty());
+        // random initial sequence number below the old one
+        assert_eq!(vec![0, 0, 0, 1, 0x41, 3], d.push(&rtp(3000, &[0x41, 3])));
+        assert_eq!(vec![0, 0, 0, 1, 0x41, 4], d.push(&rtp(3001, &[0x41, 4])));
+        // a late packet close behind is still late
+        assert!(d.push(&rtp(2999, &[0x41, 5])).is_empty());
+        // new source, the close sequence number doesn't matter
+        assert_eq!(
+            vec![0, 0, 0, 1, 0x41, 6],
+            d.push(&rtp_from(2, 2998, &[0x41, 6]))
+        );
+        let stats = d.stats();
+        assert_eq!((0, 1, 1), (stats.lost, stats.late, stats.dropped_units));
+    }
+
+    #[test]
+    fn burst_loss_is_counted() {
+        let mut d = RtpDepacketizer::new(32);
+        assert_eq!(vec![0, 0, 0, 1, 0x41, 1], d.push(&rtp(10, &[0x41, 1])));
+        // fragment start, then 300 packets lost in a dropout
+        assert!(d.push(&rtp(11, &[0x5c, 0x81, 2])).is_empty());
+        assert_eq!(vec![0, 0, 0, 1, 0x41, 3], d.push(&rtp(312, &[0x41, 3])));
+        assert_eq!(vec![0, 0, 0, 1, 0x41, 4], d.push(&rtp(313, &[0x41, 4])));
+        let stats = d.stats();
+        assert_eq!((300, 0, 1), (stats.lost, stats.late, stats.dropped_units));
+    }
+
+    #[test]
+    fn receive_rtp_over_loopback() {
+        let source = VideoSource::bind("127.0.0.1:0", SourceMode::Rtp).unwrap();
+        let addr = source.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let handle = source.spawn(tx);
+
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.send_to(&rtp(7, &[0x7c, 0x85, 1]), addr).unwrap();
+        sender.send_to(&rtp(8, &[0x7c, 0x45, 2]), addr).unwrap();
+        let stream = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(vec![0, 0, 0, 1, 0x65, 1, 2], stream);
+        assert_eq!(2, handle.stats().read().unwrap().packets);
+
+        // the receiver is still there, the thread ends and frees the port
+        handle.stop();
+        thread::sleep(Duration::from_millis(300));
+        assert!(UdpSocket::bind(addr).is_ok());
+        drop(rx);
+    }
+}
@@ -0,0 +1,67 @@
+// Golden image tests render widgets offscreen and compare the result with
+// png files in tests/golden. SDL can only be initialized from one thread,
+// hence all cases run sequentially inside a single test.
+use rust_sdl_ui::{
+    color,
+    desktop::{self, CommonWidgetProps},
+    sdl, snapshot,
+};
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+// font rendering differs slightly between freetype versions
+const TOLERANCE: u8 = 8;
+const MAX_MISMATCHED: usize = 200;
+
+#[test]
+fn golden_widgets() {
+    {
+        let (mut win, mut canvas) = desktop::Window::new_headless(WIDTH, HEIGHT, 60);
+        let horizon = desktop::HorizonWidget::new(
+            CommonWidgetProps::new(&canvas).place(0.5, 0.5).rect(0.5),
+            40.0,
+            color::YELLOW.clone(),
+        )
+        .on_window(&mut win);
+        horizon.write().unwrap().set(10.0, 15.0, 0.0);
+        sdl::sdl_clear(&mut canvas, 10, 20, 30);
+        win.draw(&mut canvas);
+        snapshot::assert_golden(
+            &canvas,
+            "tests/golden/horizon.png",
+            TOLERANCE,
+            MAX_MISMATCHED,
+        );
+    }
+
+    {
+        let (mut win, mut canvas) = desktop::Window::new_headless(WIDTH, HEIGHT, 60);
+        let battery = desktop::BatteryStatusWidget::new(
+            CommonWidgetProps::new(&canvas)
+                .place(0.5, 0.5)
+                .size(0.2, 0.8),
+        )
+        .on_window(&mut win);
+        battery.write().unwrap().set(0.5);
+        sdl::sdl_clear(&mut canvas, 10, 20, 30);
+        win.draw(&mut canvas);
+        snapshot::assert_golden(
+            &canvas,
+            "tests/golden/battery.png",
+            TOLERANCE,
+            MAX_MISMATCHED,
+        );
+    }
+
+    {
+        let (mut win, mut canvas) = desktop::Window::new_headless(WIDTH, HEIGHT, 60);
+        let stick = desktop::GamepadStickWidget::new(
+            CommonWidgetProps::new(&canvas).place(0.5, 0.5).rect(0.5),
+        )
+        .on_window(&mut win);
+        stick.write().unwrap().set_stick((0.5, -0.25));
+        sdl::sdl_clear(&mut canvas, 10, 20, 30);
+        win.draw(&mut canvas);
+        snapshot::assert_golden(&canvas, "tests/golden/stick.png", TOLERANCE, MAX_MISMATCHED);
+    }
+}